@@ -0,0 +1,120 @@
+use kurbo::{Point, Rect};
+use lopdf::content::Operation;
+use lopdf::{dictionary, Dictionary, Document, Object, Stream};
+
+use crate::graphics::color::Color;
+use crate::{encode_operations, number_array, number_to_operand};
+
+// The corners of a piece of marked text in the order viewers expect in
+// `QuadPoints`: upper-left, upper-right, lower-left, lower-right.
+pub type Quad = [Point; 4];
+
+pub fn rect_quad(rect: Rect) -> Quad {
+    [
+        Point::new(rect.x0, rect.y1),
+        Point::new(rect.x1, rect.y1),
+        Point::new(rect.x0, rect.y0),
+        Point::new(rect.x1, rect.y0),
+    ]
+}
+
+// What viewers that do not synthesize an appearance show: a fill of each
+// quad multiplied with the text beneath, or a line along or through it.
+fn appearance(subtype: &str, quads: &[Quad], components: &[f32], rect: Rect) -> Stream {
+    let operands = |points: &[Point]| -> Vec<Object> {
+        points
+            .iter()
+            .flat_map(|p| [number_to_operand(p.x, 3), number_to_operand(p.y, 3)])
+            .collect()
+    };
+    let color = |stroke: bool| {
+        let operator = match (components.len(), stroke) {
+            (1, false) => "g",
+            (1, true) => "G",
+            (4, false) => "k",
+            (4, true) => "K",
+            (_, false) => "rg",
+            (_, true) => "RG",
+        };
+        let components = components.iter().map(|c| Object::Real(*c)).collect();
+        Operation::new(operator, components)
+    };
+    let mut operations = Vec::new();
+    let mut resources = Dictionary::new();
+    if subtype == "Highlight" {
+        resources.set(
+            "ExtGState",
+            dictionary! { "GS0" => dictionary! { "BM" => "Multiply" } },
+        );
+        operations.push(Operation::new("gs", vec!["GS0".into()]));
+        operations.push(color(false));
+        for [upper_left, upper_right, lower_left, lower_right] in quads {
+            operations.push(Operation::new("m", operands(&[*upper_left])));
+            operations.push(Operation::new("l", operands(&[*upper_right])));
+            operations.push(Operation::new("l", operands(&[*lower_right])));
+            operations.push(Operation::new("l", operands(&[*lower_left])));
+            operations.push(Operation::new("h", vec![]));
+        }
+        operations.push(Operation::new("f", vec![]));
+    } else {
+        operations.push(color(true));
+        for [upper_left, upper_right, lower_left, lower_right] in quads {
+            let height = (*upper_left - *lower_left).hypot();
+            // underlines sit a little above the bottom of the quad
+            let t = if subtype == "StrikeOut" { 0.5 } else { 0.1 };
+            let start = lower_left.lerp(*upper_left, t);
+            let end = lower_right.lerp(*upper_right, t);
+            operations.push(Operation::new(
+                "w",
+                vec![number_to_operand(height / 14., 3)],
+            ));
+            operations.push(Operation::new("m", operands(&[start])));
+            operations.push(Operation::new("l", operands(&[end])));
+            operations.push(Operation::new("S", vec![]));
+        }
+    }
+    Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => number_array(&[rect.x0, rect.y0, rect.x1, rect.y1]),
+            "Resources" => resources,
+        },
+        encode_operations(&operations).unwrap(),
+    )
+}
+
+// A text markup annotation (`Highlight`, `Underline`, `StrikeOut`, `Squiggly`)
+// covering each of `quads`, with its appearance stream added to `doc`.
+pub fn markup_annotation(
+    doc: &mut Document,
+    subtype: &str,
+    quads: &[Quad],
+    color: &Color,
+) -> Dictionary {
+    let rect = quads
+        .iter()
+        .flatten()
+        .map(|p| Rect::from_points(*p, *p))
+        .reduce(|a, b| a.union(b))
+        .unwrap_or(Rect::ZERO);
+    let quad_points: Vec<f64> = quads.iter().flatten().flat_map(|p| [p.x, p.y]).collect();
+    // annotation colours are limited to the device families
+    let components = match color {
+        Color::Lab(..) | Color::Spot(..) => {
+            let (r, g, b) = color.to_rgb();
+            vec![r, g, b]
+        }
+        color => color.components(),
+    };
+    let appearance = doc.add_object(appearance(subtype, quads, &components, rect));
+    dictionary! {
+        "Type" => "Annot",
+        "Subtype" => Object::Name(subtype.as_bytes().to_vec()),
+        "Rect" => number_array(&[rect.x0, rect.y0, rect.x1, rect.y1]),
+        "QuadPoints" => number_array(&quad_points),
+        "C" => Object::Array(components.into_iter().map(Object::from).collect()),
+        "F" => 4,
+        "AP" => dictionary! { "N" => appearance },
+    }
+}
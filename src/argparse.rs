@@ -1,6 +1,10 @@
 use std::fmt::Display;
 
-use crate::graphics::color::Color;
+use crate::graphics::color::convert::ColorConverter;
+use crate::graphics::color::{Color, ColorTolerance};
+use crate::{
+    ContrastLevel, DarkStyle, DecorationKind, Deficiency, FurnitureKind, Paint, StampPosition,
+};
 
 #[derive(Debug, Clone)]
 pub struct ArgRange(f32, f32);
@@ -35,6 +39,129 @@ impl ArgRange {
     }
 }
 
+// A fraction such as an opacity or a brightness, from 0 to 1.
+pub fn unit_parser(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(value) if (0. ..=1.).contains(&value) => Ok(value),
+        _ => Err(format!("Expected a number from 0 to 1, got {}", s)),
+    }
+}
+
+// The standard fonts with the Latin character set, which viewers have
+// without embedding.
+pub const STANDARD_FONTS: [&str; 12] = [
+    "Helvetica",
+    "Helvetica-Bold",
+    "Helvetica-Oblique",
+    "Helvetica-BoldOblique",
+    "Times-Roman",
+    "Times-Bold",
+    "Times-Italic",
+    "Times-BoldItalic",
+    "Courier",
+    "Courier-Bold",
+    "Courier-Oblique",
+    "Courier-BoldOblique",
+];
+
+// Page numbers from 1 as ranges such as `1,3-5,8-`.
+#[derive(Debug, Clone)]
+pub struct ArgPages(Vec<(usize, usize)>);
+
+impl ArgPages {
+    pub fn parser(s: &str) -> Result<Self, String> {
+        let error = || format!("Invalid pages {}; use ranges such as 1,3-5,8-", s);
+        let number = |n: &str| n.trim().parse::<usize>().ok().filter(|n| *n > 0);
+        let ranges = s
+            .split(',')
+            .map(|range| match range.split_once('-') {
+                Some((first, "")) => number(first).map(|first| (first, usize::MAX)),
+                Some((first, last)) => number(first).zip(number(last)),
+                None => number(range).map(|page| (page, page)),
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(error)?;
+        if let Some((first, last)) = ranges.iter().find(|(first, last)| first > last) {
+            return Err(format!(
+                "Invalid pages {}; the range {}-{} runs backwards",
+                s, first, last
+            ));
+        }
+        Ok(Self(ranges))
+    }
+    pub fn contains(&self, page: usize) -> bool {
+        self.0
+            .iter()
+            .any(|(first, last)| (*first..=*last).contains(&page))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArgStampPosition(pub StampPosition);
+
+impl ArgStampPosition {
+    pub fn parser(s: &str) -> Result<Self, String> {
+        let (x, y) = match s.trim() {
+            "center" => (0.5, 0.5),
+            "top" => (0.5, 1.),
+            "bottom" => (0.5, 0.),
+            "left" => (0., 0.5),
+            "right" => (1., 0.5),
+            "top-left" => (0., 1.),
+            "top-right" => (1., 1.),
+            "bottom-left" => (0., 0.),
+            "bottom-right" => (1., 0.),
+            s => {
+                return match s.split_once(',').map(|(x, y)| (x.trim().parse(), y.trim().parse())) {
+                    Some((Ok(x), Ok(y))) => Ok(Self(StampPosition::Absolute(kurbo::Point::new(x, y)))),
+                    _ => Err(format!(
+                        "Unknown position {}; use center, top, bottom, left, right, top-left, top-right, bottom-left, bottom-right or X,Y",
+                        s
+                    )),
+                }
+            }
+        };
+        Ok(Self(StampPosition::Relative(x, y)))
+    }
+}
+
+// A rectangle in default user space, on one page or on all of them.
+#[derive(Debug, Clone)]
+pub struct ArgPageRect {
+    pub page: Option<usize>,
+    pub rect: kurbo::Rect,
+}
+
+impl ArgPageRect {
+    pub fn parser(s: &str) -> Result<Self, String> {
+        let (page, rect) = match s.split_once(':') {
+            Some((page, rect)) => (
+                Some(
+                    page.trim()
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|p| *p > 0)
+                        .ok_or(format!("Invalid page number {}", page))?,
+                ),
+                rect,
+            ),
+            None => (None, s),
+        };
+        let values: Vec<f64> = rect
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "Rectangle must look like [PAGE:]X0,Y0,X1,Y1".to_string())?;
+        match values[..] {
+            [x0, y0, x1, y1] => Ok(Self {
+                page,
+                rect: kurbo::Rect::new(x0, y0, x1, y1).abs(),
+            }),
+            _ => Err("Rectangle must look like [PAGE:]X0,Y0,X1,Y1".to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ArgColor(pub Color);
 
@@ -45,39 +172,679 @@ impl Display for ArgColor {
 }
 
 impl ArgColor {
+    // Accepts `#rgb`, `#rrggbb`, CSS colour names, `gray()`, `rgb()`, `cmyk()`,
+    // `lab()` and `spot(NAME[, tint])`. Components are 0..1 numbers or
+    // percentages; gray and rgb also take 0..255 integers once any is above 1,
+    // and `gray255()` and `rgb255()` always take 0..255 numbers.
     pub fn parser(s: &str) -> Result<Self, String> {
-        let parse_regex =
-            regex::Regex::new(r"(?P<space>gray|rgb|cmyk)\((?P<params>([0-9.]+,? *)*)\)").unwrap();
-        if let Some(matches) = parse_regex.captures(s) {
-            let space = matches.name("space").unwrap().as_str();
-            let params: Vec<f32> = matches
-                .name("params")
-                .unwrap()
-                .as_str()
-                .split(",")
-                .filter_map(|m| m.trim().parse().ok())
-                .collect();
-            match space {
-                "gray" => {
-                    if let [g] = params[..] {
-                        return Ok(Self(Color::Gray(g)));
-                    }
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            return Self::hex(hex).map(Self);
+        }
+        if let Some((function, rest)) = s.split_once('(') {
+            let params = rest
+                .trim_end()
+                .strip_suffix(')')
+                .ok_or_else(|| format!("Missing ')' at the end of {}", s))?;
+            return Self::function(&function.trim().to_lowercase(), params).map(Self);
+        }
+        let name = s.to_lowercase().replace([' ', '-'], "");
+        match CSS_COLORS.iter().find(|(n, _)| *n == name) {
+            Some((_, rgb)) => Ok(Self(Color::RGB(
+                (rgb >> 16) as f32 / 255.,
+                ((rgb >> 8) & 0xff) as f32 / 255.,
+                (rgb & 0xff) as f32 / 255.,
+            ))),
+            None => Err(format!(
+                "Unknown color {:?}; use #rrggbb, a CSS color name, gray(), rgb(), rgb255(), cmyk(), lab() or spot()",
+                s
+            )),
+        }
+    }
+    fn hex(hex: &str) -> Result<Color, String> {
+        let digits: Vec<u32> = hex
+            .chars()
+            .map(|c| {
+                c.to_digit(16)
+                    .ok_or_else(|| format!("{:?} is not a hexadecimal digit in #{}", c, hex))
+            })
+            .collect::<Result<_, _>>()?;
+        let [r, g, b] = match digits[..] {
+            [r, g, b] => [r * 17, g * 17, b * 17],
+            [r1, r2, g1, g2, b1, b2] => [r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2],
+            _ => {
+                return Err(format!(
+                    "#{} must have 3 or 6 hexadecimal digits, not {}",
+                    hex,
+                    digits.len()
+                ))
+            }
+        }
+        .map(|v| v as f32 / 255.);
+        Ok(Color::RGB(r, g, b))
+    }
+    fn function(function: &str, params: &str) -> Result<Color, String> {
+        if function == "spot" {
+            // the name may contain spaces and is followed by an optional tint
+            let (name, tint) = match params.rsplit_once(',') {
+                Some((name, tint)) => (name, Self::component(tint, "tint")?),
+                None => (params, 1.),
+            };
+            let name = name.trim();
+            if name.is_empty() {
+                return Err("spot() needs the name of the ink".to_string());
+            }
+            Self::check_range(tint, 0., 1., "tint")?;
+            return Ok(Color::Spot(name.to_string(), tint));
+        }
+        let params: Vec<&str> = params
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|p| !p.is_empty())
+            .collect();
+        let (function, bytes) = match function.strip_suffix("255") {
+            Some(function @ ("gray" | "grey" | "rgb")) => (function, true),
+            _ => (function, false),
+        };
+        let expected = match function {
+            "gray" | "grey" => 1,
+            "rgb" | "lab" => 3,
+            "cmyk" => 4,
+            _ => {
+                return Err(format!(
+                    "Unknown color function {}(); use gray(), rgb(), gray255(), rgb255(), cmyk(), lab() or spot()",
+                    function
+                ))
+            }
+        };
+        if params.len() != expected {
+            return Err(format!(
+                "{}() takes {} components, but {} were given",
+                function,
+                expected,
+                params.len()
+            ));
+        }
+        if function == "lab" {
+            // percentages follow CSS: 100% is L 100 and a or b 125
+            let l = Self::scaled(params[0], 100., "L")?;
+            let a = Self::scaled(params[1], 125., "a")?;
+            let b = Self::scaled(params[2], 125., "b")?;
+            Self::check_range(l, 0., 100., "L")?;
+            return Ok(Color::Lab(l, a, b));
+        }
+        let mut values = params
+            .iter()
+            .map(|p| Self::component(p, "component"))
+            .collect::<Result<Vec<f32>, String>>()?;
+        let bytes = bytes
+            || function != "cmyk"
+                && params.iter().all(|p| p.parse::<u8>().is_ok())
+                && values.iter().any(|v| *v > 1.);
+        if bytes {
+            for (v, p) in values.iter_mut().zip(&params) {
+                if p.ends_with('%') {
+                    return Err(format!("{}255() takes 0..255 numbers, not {}", function, p));
+                }
+                Self::check_range(*v, 0., 255., "component")?;
+                *v /= 255.;
+            }
+        }
+        for v in &values {
+            Self::check_range(*v, 0., 1., "component").map_err(|e| {
+                if bytes || function == "cmyk" {
+                    e
+                } else {
+                    format!("{}; use {}255() for 0..255 numbers", e, function)
                 }
-                "rgb" => {
-                    if let [r, g, b] = params[..] {
-                        return Ok(Self(Color::RGB(r, g, b)));
-                    }
+            })?;
+        }
+        Ok(match values[..] {
+            [g] => Color::Gray(g),
+            [r, g, b] => Color::RGB(r, g, b),
+            [c, m, y, k] => Color::CMYK(c, m, y, k),
+            _ => unreachable!(),
+        })
+    }
+    // A number, or a percentage of 1.
+    fn component(s: &str, what: &str) -> Result<f32, String> {
+        Self::scaled(s, 1., what)
+    }
+    fn scaled(s: &str, percent_of: f32, what: &str) -> Result<f32, String> {
+        let s = s.trim();
+        let (number, scale) = match s.strip_suffix('%') {
+            Some(number) => (number, percent_of / 100.),
+            None => (s, 1.),
+        };
+        number
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .map(|v| v * scale)
+            .ok_or_else(|| format!("{} {:?} is not a number or percentage", what, s))
+    }
+    fn check_range(value: f32, min: f32, max: f32, what: &str) -> Result<(), String> {
+        if (min..=max).contains(&value) {
+            Ok(())
+        } else {
+            Err(format!("{} {} is outside {}..{}", what, value, min, max))
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgTableFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgTextFormat {
+    Json,
+    Hocr,
+    Tsv,
+    Plain,
+    Markdown,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgReportFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgContrastLevel {
+    Aa,
+    Aaa,
+}
+
+impl ArgContrastLevel {
+    pub fn to_level(&self) -> ContrastLevel {
+        match self {
+            Self::Aa => ContrastLevel::AA,
+            Self::Aaa => ContrastLevel::AAA,
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgDarkStyle {
+    Invert,
+    Remap,
+}
+
+impl ArgDarkStyle {
+    pub fn to_style(&self) -> DarkStyle {
+        match self {
+            Self::Invert => DarkStyle::Invert,
+            Self::Remap => DarkStyle::Remap,
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgDeficiency {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl ArgDeficiency {
+    pub fn to_deficiency(&self) -> Deficiency {
+        match self {
+            Self::Protanopia => Deficiency::Protanopia,
+            Self::Deuteranopia => Deficiency::Deuteranopia,
+            Self::Tritanopia => Deficiency::Tritanopia,
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgFurnitureAction {
+    Remove,
+    Artifact,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgFurnitureKind {
+    Header,
+    Footer,
+    Watermark,
+}
+
+impl ArgFurnitureKind {
+    pub fn to_kind(&self) -> FurnitureKind {
+        match self {
+            Self::Header => FurnitureKind::Header,
+            Self::Footer => FurnitureKind::Footer,
+            Self::Watermark => FurnitureKind::Watermark,
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgDecorationAction {
+    Remove,
+    Recolor,
+    Annotate,
+}
+
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum ArgDecorationKind {
+    Underline,
+    StrikeOut,
+    Highlight,
+}
+
+impl ArgDecorationKind {
+    pub fn to_kind(&self) -> DecorationKind {
+        match self {
+            Self::Underline => DecorationKind::Underline,
+            Self::StrikeOut => DecorationKind::StrikeOut,
+            Self::Highlight => DecorationKind::Highlight,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArgTolerance(pub ColorTolerance);
+
+impl Default for ArgTolerance {
+    fn default() -> Self {
+        Self(ColorTolerance::Exact)
+    }
+}
+
+impl Display for ArgTolerance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ArgTolerance {
+    pub fn parser(s: &str) -> Result<Self, String> {
+        if s.trim() == "exact" {
+            return Ok(Self(ColorTolerance::Exact));
+        }
+        let (kind, value) = s.split_once(':').unwrap_or(("de", s));
+        let value: f32 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Tolerance must be a number, got {}", value))?;
+        if !value.is_finite() {
+            return Err(format!("Tolerance must be a finite number, got {}", value));
+        }
+        if value < 0. {
+            return Err("Tolerance must not be negative".to_string());
+        }
+        match kind {
+            "de" => Ok(Self(ColorTolerance::DeltaE(value))),
+            "channel" => Ok(Self(ColorTolerance::Channel(value))),
+            _ => Err("Tolerance must look like exact, 2.0, de:2.0 or channel:0.02".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ArgColorProfile {
+    Naive,
+    Document,
+    File(std::path::PathBuf),
+}
+
+impl Display for ArgColorProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Naive => write!(f, "naive"),
+            Self::Document => write!(f, "document"),
+            Self::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl ArgColorProfile {
+    pub fn parser(s: &str) -> Result<Self, String> {
+        match s {
+            "naive" => Ok(Self::Naive),
+            "document" => Ok(Self::Document),
+            path => {
+                let path = std::path::PathBuf::from(path);
+                if path.is_file() {
+                    Ok(Self::File(path))
+                } else {
+                    Err(format!(
+                        "Expected naive, document or the path of an ICC profile, got {}",
+                        s
+                    ))
                 }
-                "cmyk" => {
-                    if let [c, m, y, k] = params[..] {
-                        return Ok(Self(Color::CMYK(c, m, y, k)));
-                    }
+            }
+        }
+    }
+    pub fn converter(&self, doc: &lopdf::Document) -> Result<ColorConverter, String> {
+        match self {
+            Self::Naive => {
+                let mut converter = ColorConverter::naive();
+                converter.add_inks(doc);
+                Ok(converter)
+            }
+            Self::Document => Ok(ColorConverter::from_document(doc)),
+            Self::File(path) => {
+                let data = std::fs::read(path).map_err(|e| e.to_string())?;
+                let mut converter = ColorConverter::naive();
+                converter.add_inks(doc);
+                if converter.add_profile(&data) {
+                    Ok(converter)
+                } else {
+                    Err(format!("{} is not a usable ICC profile", path.display()))
                 }
-                _ => panic!(),
             }
-            Err(r"The number of parameters is wrong".to_string())
-        } else {
-            Err(r"Cannot parse the color. Make sure it looks like rgb(0.1,0.7,0.8)".to_string())
         }
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct ArgColorRule {
+    pub from: Color,
+    pub to: Color,
+    pub targets: Vec<Paint>,
+}
+
+impl Display for ArgColorRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.from, self.to)
+    }
+}
+
+impl ArgColorRule {
+    pub fn parser(s: &str) -> Result<Self, String> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or("Rule must look like FROM=TO[:target]".to_string())?;
+        let (to, targets) = match to.rsplit_once(':') {
+            Some((to, target)) => (to, Self::targets(target)?),
+            None => (to, Paint::ALL.to_vec()),
+        };
+        Ok(Self {
+            from: ArgColor::parser(from.trim())?.0,
+            to: ArgColor::parser(to.trim())?.0,
+            targets,
+        })
+    }
+    fn targets(s: &str) -> Result<Vec<Paint>, String> {
+        match s.trim() {
+            "text-fill" => Ok(vec![Paint::TextFill]),
+            "text-stroke" => Ok(vec![Paint::TextStroke]),
+            "path-fill" => Ok(vec![Paint::PathFill]),
+            "path-stroke" => Ok(vec![Paint::PathStroke]),
+            "text" => Ok(vec![Paint::TextFill, Paint::TextStroke]),
+            "path" => Ok(vec![Paint::PathFill, Paint::PathStroke]),
+            "fill" => Ok(vec![Paint::TextFill, Paint::PathFill]),
+            "stroke" => Ok(vec![Paint::TextStroke, Paint::PathStroke]),
+            "all" => Ok(Paint::ALL.to_vec()),
+            t => Err(format!(
+                "Unknown target {}; use text-fill, text-stroke, path-fill, path-stroke, text, path, fill, stroke or all",
+                t
+            )),
+        }
+    }
+}
+
+// CSS named colours (CSS Color Module Level 4)
+const CSS_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(s: &str) -> Color {
+        ArgColor::parser(s).unwrap().0
+    }
+
+    #[test]
+    fn color_forms() {
+        assert_eq!(color("#f00"), Color::RGB(1., 0., 0.));
+        assert_eq!(color("#00ff00"), Color::RGB(0., 1., 0.));
+        assert_eq!(color("Dark Blue"), color("darkblue"));
+        assert_eq!(color("gray(50%)"), Color::Gray(0.5));
+        assert_eq!(color("rgb(0 0.5 1)"), Color::RGB(0., 0.5, 1.));
+        assert_eq!(color("cmyk(0,0,0,1)"), Color::CMYK(0., 0., 0., 1.));
+        assert_eq!(color("lab(50, 10%, -20)"), Color::Lab(50., 12.5, -20.));
+        assert_eq!(
+            color("spot(PANTONE 485 C)"),
+            Color::Spot("PANTONE 485 C".into(), 1.)
+        );
+        assert_eq!(color("spot(Gold, 0.4)"), Color::Spot("Gold".into(), 0.4));
+    }
+
+    #[test]
+    fn color_byte_scale_is_explicit() {
+        assert_eq!(color("rgb255(255, 0, 51)"), Color::RGB(1., 0., 0.2));
+        assert_eq!(color("gray255(0)"), Color::Gray(0.));
+        // integers above 1 are on the 0..255 scale too
+        assert_eq!(color("rgb(255, 0, 0)"), Color::RGB(1., 0., 0.));
+        assert_eq!(color("gray(51)"), Color::Gray(0.2));
+        assert_eq!(color("rgb(1, 0, 0)"), Color::RGB(1., 0., 0.));
+        assert!(ArgColor::parser("rgb(255, 0.5, 0)").is_err());
+        assert!(ArgColor::parser("rgb(256, 0, 0)").is_err());
+        assert!(ArgColor::parser("rgb255(50%, 0, 0)").is_err());
+        assert!(ArgColor::parser("rgb255(256, 0, 0)").is_err());
+        assert!(ArgColor::parser("cmyk255(0, 0, 0, 0)").is_err());
+    }
+
+    #[test]
+    fn color_errors() {
+        for s in [
+            "#12",
+            "#ggg",
+            "notacolor",
+            "rgb(1,2)",
+            "rgb(1,1,1",
+            "gray(x)",
+            "lab(101,0,0)",
+            "spot(, 1)",
+            "spot(Gold, 2)",
+            "hsl(0,0,0)",
+        ] {
+            assert!(ArgColor::parser(s).is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn pages() {
+        let pages = ArgPages::parser("1,3-5,8-").unwrap();
+        assert!(pages.contains(1));
+        assert!(!pages.contains(2));
+        assert!(pages.contains(4));
+        assert!(!pages.contains(7));
+        assert!(pages.contains(1000));
+        for s in ["", "0", "1,,2", "a-3", "-3", "2-x", "5-2"] {
+            assert!(ArgPages::parser(s).is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn tolerance() {
+        assert!(matches!(
+            ArgTolerance::parser("channel:0.02").unwrap().0,
+            ColorTolerance::Channel(v) if v == 0.02
+        ));
+        assert!(matches!(
+            ArgTolerance::parser("exact").unwrap().0,
+            ColorTolerance::Exact
+        ));
+        for s in ["NaN", "de:inf", "channel:-1", "x:1", "de:"] {
+            assert!(ArgTolerance::parser(s).is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn page_rect() {
+        let all = ArgPageRect::parser("10, 20, 0, 5").unwrap();
+        assert_eq!(all.page, None);
+        assert_eq!(all.rect, kurbo::Rect::new(0., 5., 10., 20.));
+        let one = ArgPageRect::parser("3:0,0,1,1").unwrap();
+        assert_eq!(one.page, Some(3));
+        for s in ["0:0,0,1,1", "x:0,0,1,1", "0,0,1", "0,0,1,1,1", "a,b,c,d"] {
+            assert!(ArgPageRect::parser(s).is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn unit() {
+        assert_eq!(unit_parser("0.25"), Ok(0.25));
+        assert_eq!(unit_parser("1"), Ok(1.));
+        assert!(unit_parser("-0.1").is_err());
+        assert!(unit_parser("1.5").is_err());
+        assert!(unit_parser("NaN").is_err());
+    }
+}
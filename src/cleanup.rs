@@ -0,0 +1,182 @@
+use std::fmt::Display;
+
+use kurbo::Rect;
+use lopdf::{content::Operation, Object};
+
+use crate::graphics::text::RenderingMode;
+use crate::{clip_rect, painted_bbox, xobject_bboxes, PdfModifier, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invisible {
+    // painted entirely outside the crop box or the clipping path
+    OffPage,
+    // text in rendering mode 3
+    InvisibleText,
+    ZeroSizeFont,
+    // painted with a constant alpha of 0
+    Transparent,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CleanupReport {
+    pub off_page: usize,
+    pub invisible_text: usize,
+    pub zero_size_font: usize,
+    pub transparent: usize,
+}
+
+impl CleanupReport {
+    fn count(&mut self, kind: Invisible) {
+        match kind {
+            Invisible::OffPage => self.off_page += 1,
+            Invisible::InvisibleText => self.invisible_text += 1,
+            Invisible::ZeroSizeFont => self.zero_size_font += 1,
+            Invisible::Transparent => self.transparent += 1,
+        }
+    }
+    pub fn total(&self) -> usize {
+        self.off_page + self.invisible_text + self.zero_size_font + self.transparent
+    }
+}
+
+impl Display for CleanupReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} off-page, {} invisible text, {} zero-size font, {} transparent",
+            self.off_page, self.invisible_text, self.zero_size_font, self.transparent
+        )
+    }
+}
+
+fn is_text(operation: &Operation) -> bool {
+    matches!(operation.operator.as_ref(), "Tj" | "TJ" | "'" | "\"")
+}
+
+fn paints_fill(operation: &Operation, state: &State) -> bool {
+    match operation.operator.as_ref() {
+        "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" => true,
+        "Tj" | "TJ" | "'" | "\"" => matches!(
+            state.graphics.text.rendering_mode,
+            RenderingMode::Fill | RenderingMode::FillAndStroke
+        ),
+        _ => false,
+    }
+}
+
+fn paints_stroke(operation: &Operation, state: &State) -> bool {
+    match operation.operator.as_ref() {
+        "S" | "s" | "B" | "B*" | "b" | "b*" => true,
+        "Tj" | "TJ" | "'" | "\"" => matches!(
+            state.graphics.text.rendering_mode,
+            RenderingMode::Stroke | RenderingMode::FillAndStroke
+        ),
+        _ => false,
+    }
+}
+
+pub fn invisible_kind(
+    operation: &Operation,
+    state: &State,
+    xobjects: &std::collections::HashMap<Vec<u8>, Rect>,
+    page: Rect,
+) -> Option<Invisible> {
+    if !matches!(
+        operation.operator.as_ref(),
+        "f" | "F"
+            | "f*"
+            | "S"
+            | "s"
+            | "B"
+            | "B*"
+            | "b"
+            | "b*"
+            | "Tj"
+            | "TJ"
+            | "'"
+            | "\""
+            | "Do"
+            | "BI"
+    ) {
+        return None;
+    }
+    let graphics = &state.graphics;
+    if is_text(operation) {
+        match graphics.text.rendering_mode {
+            RenderingMode::Invisible => return Some(Invisible::InvisibleText),
+            // the glyphs still shape the clipping path
+            RenderingMode::FillAndAddClippingPath
+            | RenderingMode::StrokeAndAddClippingPath
+            | RenderingMode::FillStrokeAddClippingPath
+            | RenderingMode::AddClippingPath => return None,
+            _ => (),
+        }
+        let matrix = graphics.ctm * graphics.text.line_matrix;
+        if graphics.text.font_size == Some(0.) || matrix.determinant() == 0. {
+            return Some(Invisible::ZeroSizeFont);
+        }
+    }
+    let fill = paints_fill(operation, state);
+    let stroke = paints_stroke(operation, state);
+    if (fill || stroke)
+        && !(fill && graphics.alpha_constant_non_stroke > 0.)
+        && !(stroke && graphics.alpha_constant_stroke > 0.)
+    {
+        return Some(Invisible::Transparent);
+    }
+    match painted_bbox(operation, state, xobjects, page) {
+        Some(bbox) if clip_rect(bbox, page).is_some() => None,
+        // an XObject that cannot be measured is left alone
+        None if operation.operator == "Do" => None,
+        _ => Some(Invisible::OffPage),
+    }
+}
+
+// Removing a show operation drops its text but keeps the line movement of
+// `'` and `"`, their spacing changes, and the advance of the text position.
+pub fn removed_text(operation: Operation, state: &State) -> Vec<Operation> {
+    let skip = state.skip_operations(&operation);
+    let mut operations = match operation.operator.as_ref() {
+        "'" => vec![Operation::new("T*", vec![])],
+        "\"" => {
+            let mut operands = operation.operands.into_iter();
+            let word_spacing = operands.next().unwrap_or(Object::from(0));
+            let character_spacing = operands.next().unwrap_or(Object::from(0));
+            vec![
+                Operation::new("Tw", vec![word_spacing]),
+                Operation::new("Tc", vec![character_spacing]),
+                Operation::new("T*", vec![]),
+            ]
+        }
+        _ => vec![],
+    };
+    operations.extend(skip);
+    operations
+}
+
+fn removed(operation: Operation, state: &State) -> Vec<Operation> {
+    match operation.operator.as_ref() {
+        "Tj" | "TJ" | "'" | "\"" => removed_text(operation, state),
+        "Do" | "BI" => vec![],
+        _ => vec![Operation::new("n", vec![])],
+    }
+}
+
+pub fn remove_invisible(modifier: &mut PdfModifier, page_id: (u32, u16)) -> CleanupReport {
+    let mut report = CleanupReport::default();
+    let page = match modifier.visible_box(page_id) {
+        Some(page) => page,
+        None => return report,
+    };
+    let xobjects = xobject_bboxes(modifier, page_id);
+    modifier.apply(page_id, &mut |operation, state| match invisible_kind(
+        &operation, state, &xobjects, page,
+    ) {
+        Some(kind) => {
+            report.count(kind);
+            removed(operation, state)
+        }
+        None => vec![operation],
+    });
+    report
+}
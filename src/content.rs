@@ -0,0 +1,260 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Object, StringFormat};
+
+// lopdf cannot parse inline images, so content is split around each
+// `BI ... ID ... EI` sequence. An inline image becomes a single `BI`
+// operation whose operands are its parameter dictionary and its data.
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0')
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+// Position just after the next token, with the token's range.
+fn next_token(bytes: &[u8], mut i: usize) -> Option<(usize, usize, usize)> {
+    loop {
+        while i < bytes.len() && is_whitespace(bytes[i]) {
+            i += 1;
+        }
+        if i < bytes.len() && bytes[i] == b'%' {
+            while i < bytes.len() && bytes[i] != b'\n' && bytes[i] != b'\r' {
+                i += 1;
+            }
+        } else {
+            break;
+        }
+    }
+    let start = i;
+    match bytes.get(i)? {
+        b'(' => {
+            let mut depth = 0;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'\\' => i += 1,
+                    b'(' => depth += 1,
+                    b')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            i += 1;
+                            break;
+                        }
+                    }
+                    _ => (),
+                }
+                i += 1;
+            }
+        }
+        b'<' if bytes.get(i + 1) != Some(&b'<') => {
+            while i < bytes.len() && bytes[i] != b'>' {
+                i += 1;
+            }
+            i += 1;
+        }
+        b'<' | b'>' => i += 2,
+        b'[' | b']' | b'{' | b'}' => i += 1,
+        b'/' => {
+            i += 1;
+            while i < bytes.len() && !is_whitespace(bytes[i]) && !is_delimiter(bytes[i]) {
+                i += 1;
+            }
+        }
+        _ => {
+            while i < bytes.len() && !is_whitespace(bytes[i]) && !is_delimiter(bytes[i]) {
+                i += 1;
+            }
+        }
+    }
+    let end = i.min(bytes.len());
+    Some((end, start, end))
+}
+
+fn components(dict: &Dictionary) -> Option<usize> {
+    if dict
+        .get(b"IM")
+        .or(dict.get(b"ImageMask"))
+        .and_then(|o| o.as_bool())
+        .unwrap_or(false)
+    {
+        return Some(1);
+    }
+    match dict.get(b"CS").or(dict.get(b"ColorSpace")).ok()? {
+        Object::Name(name) => match name.as_slice() {
+            b"G" | b"DeviceGray" | b"I" | b"Indexed" => Some(1),
+            b"RGB" | b"DeviceRGB" => Some(3),
+            b"CMYK" | b"DeviceCMYK" => Some(4),
+            _ => None,
+        },
+        Object::Array(array) => match array.first().and_then(|o| o.as_name().ok()) {
+            Some(b"I") | Some(b"Indexed") => Some(1),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Length of unfiltered image data, when it can be known from the parameters.
+fn data_length(dict: &Dictionary) -> Option<usize> {
+    if dict.get(b"F").or(dict.get(b"Filter")).is_ok() {
+        return None;
+    }
+    let get = |short: &[u8], long: &[u8]| {
+        dict.get(short)
+            .or(dict.get(long))
+            .and_then(|o| o.as_i64())
+            .ok()
+    };
+    let width = get(b"W", b"Width")? as usize;
+    let height = get(b"H", b"Height")? as usize;
+    let bpc = get(b"BPC", b"BitsPerComponent").unwrap_or(1) as usize;
+    Some((width * components(dict)? * bpc).div_ceil(8) * height)
+}
+
+// Returns the image's dictionary, data and the position after `EI`.
+fn parse_inline_image(bytes: &[u8], after_bi: usize) -> Option<(Dictionary, Vec<u8>, usize)> {
+    let mut i = after_bi;
+    let dict_end;
+    loop {
+        let (next, start, end) = next_token(bytes, i)?;
+        if &bytes[start..end] == b"ID" {
+            dict_end = start;
+            i = next;
+            break;
+        }
+        i = next;
+    }
+    let mut wrapped = b"<<".to_vec();
+    wrapped.extend_from_slice(&bytes[after_bi..dict_end]);
+    wrapped.extend_from_slice(b">> BI");
+    let dict = match Content::decode(&wrapped)
+        .ok()?
+        .operations
+        .pop()?
+        .operands
+        .pop()?
+    {
+        Object::Dictionary(dict) => dict,
+        _ => return None,
+    };
+    // a single whitespace byte separates `ID` from the data
+    let data_start = i + 1;
+    let is_end = |j: usize| {
+        bytes.get(j..j + 2) == Some(b"EI")
+            && bytes
+                .get(j + 2)
+                .is_none_or(|b| is_whitespace(*b) || is_delimiter(*b))
+    };
+    if let Some(length) = data_length(&dict) {
+        let mut j = data_start + length;
+        while j < bytes.len() && is_whitespace(bytes[j]) {
+            j += 1;
+        }
+        if is_end(j) {
+            return Some((dict, bytes[data_start..data_start + length].to_vec(), j + 2));
+        }
+    }
+    let mut j = data_start;
+    while j + 2 <= bytes.len() {
+        if j > data_start && is_whitespace(bytes[j - 1]) && is_end(j) {
+            return Some((dict, bytes[data_start..j - 1].to_vec(), j + 2));
+        }
+        j += 1;
+    }
+    None
+}
+
+pub fn decode_operations(bytes: &[u8]) -> Result<Vec<Operation>, lopdf::Error> {
+    let mut operations = Vec::new();
+    let mut chunk_start = 0;
+    let mut i = 0;
+    while let Some((next, start, end)) = next_token(bytes, i) {
+        if &bytes[start..end] == b"BI" {
+            if let Some((dict, data, after)) = parse_inline_image(bytes, next) {
+                operations.extend(Content::decode(&bytes[chunk_start..start])?.operations);
+                operations.push(Operation::new(
+                    "BI",
+                    vec![
+                        Object::Dictionary(dict),
+                        Object::String(data, StringFormat::Literal),
+                    ],
+                ));
+                chunk_start = after;
+                i = after;
+                continue;
+            }
+        }
+        i = next;
+    }
+    operations.extend(Content::decode(&bytes[chunk_start..])?.operations);
+    Ok(operations)
+}
+
+pub fn encode_operations(operations: &[Operation]) -> Result<Vec<u8>, lopdf::Error> {
+    let mut bytes = Vec::new();
+    let mut chunk: Vec<Operation> = Vec::new();
+    for operation in operations {
+        match (operation.operator.as_ref(), &operation.operands[..]) {
+            ("BI", [Object::Dictionary(dict), Object::String(data, _)]) => {
+                let chunk = std::mem::take(&mut chunk);
+                bytes.extend(Content { operations: chunk }.encode()?);
+                if !bytes.is_empty() && !bytes.ends_with(b"\n") {
+                    bytes.push(b'\n');
+                }
+                // the dictionary is written by lopdf and its brackets dropped
+                let encoded = Content {
+                    operations: vec![Operation::new("BI", vec![Object::Dictionary(dict.clone())])],
+                }
+                .encode()?;
+                let body = encoded.trim_ascii();
+                let body = body.strip_suffix(b"BI").unwrap_or(body).trim_ascii();
+                let body = body.strip_prefix(b"<<").unwrap_or(body);
+                let body = body.strip_suffix(b">>").unwrap_or(body);
+                bytes.extend_from_slice(b"BI ");
+                bytes.extend_from_slice(body);
+                bytes.extend_from_slice(b" ID ");
+                bytes.extend_from_slice(data);
+                bytes.extend_from_slice(b"\nEI\n");
+            }
+            _ => chunk.push(operation.clone()),
+        }
+    }
+    bytes.extend(Content { operations: chunk }.encode()?);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn inline_image_bytes() {
+        let dict = dictionary! {
+            "W" => 1,
+            "H" => 1,
+            "BPC" => 8,
+            "CS" => "G",
+            "DP" => Object::String(vec![0xff, 0x80], StringFormat::Literal),
+        };
+        let operations = vec![Operation::new(
+            "BI",
+            vec![
+                Object::Dictionary(dict),
+                Object::String(vec![0x9f], StringFormat::Literal),
+            ],
+        )];
+        let bytes = encode_operations(&operations).unwrap();
+        assert!(bytes.windows(2).any(|w| w == [0xff, 0x80]));
+        let decoded = decode_operations(&bytes).unwrap();
+        let [Object::Dictionary(dict), Object::String(data, _)] = &decoded[0].operands[..] else {
+            panic!("no inline image in {:?}", decoded);
+        };
+        assert_eq!(dict.get(b"DP").unwrap().as_str().unwrap(), [0xff, 0x80]);
+        assert_eq!(data, &[0x9f]);
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use kurbo::{BezPath, Point, Rect, Shape};
+
+use crate::graphics::color::convert::ColorConverter;
+use crate::graphics::color::Color;
+use crate::{page_fonts, painted_bbox, text_bbox, xobject_bboxes, Font};
+use crate::{recolor_operations, Paint, PdfModifier};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContrastLevel {
+    AA,
+    AAA,
+}
+
+impl ContrastLevel {
+    // WCAG 2 ratios for normal and large text
+    pub fn required(&self, large: bool) -> f32 {
+        match (self, large) {
+            (Self::AA, false) => 4.5,
+            (Self::AA, true) => 3.,
+            (Self::AAA, false) => 7.,
+            (Self::AAA, true) => 4.5,
+        }
+    }
+}
+
+// WCAG relative luminance of sRGB components in 0..1.
+pub fn relative_luminance((r, g, b): (f32, f32, f32)) -> f32 {
+    let linear = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+pub fn contrast_ratio(lhs: (f32, f32, f32), rhs: (f32, f32, f32)) -> f32 {
+    let (l, r) = (relative_luminance(lhs), relative_luminance(rhs));
+    (l.max(r) + 0.05) / (l.min(r) + 0.05)
+}
+
+// Text painted with some opacity shows the background through it.
+fn blend((r, g, b): (f32, f32, f32), (br, bg, bb): (f32, f32, f32), alpha: f32) -> (f32, f32, f32) {
+    (
+        alpha * r + (1. - alpha) * br,
+        alpha * g + (1. - alpha) * bg,
+        alpha * b + (1. - alpha) * bb,
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct ContrastIssue {
+    pub page: usize,
+    pub text: String,
+    pub position: Point,
+    // in default user space units
+    pub font_size: f32,
+    pub foreground: Color,
+    pub background: Color,
+    pub ratio: f32,
+    pub required: f32,
+    // what the text was changed to, when fixing
+    pub fixed: Option<Color>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContrastReport {
+    pub checked: usize,
+    // text over images, shadings or patterns, or across the edge of a fill,
+    // whose background is not known
+    pub unknown: usize,
+    pub issues: Vec<ContrastIssue>,
+}
+
+impl ContrastReport {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "checked": self.checked,
+            "unknown": self.unknown,
+            "issues": self
+                .issues
+                .iter()
+                .map(|i| serde_json::json!({
+                    "page": i.page,
+                    "text": i.text,
+                    "x": i.position.x,
+                    "y": i.position.y,
+                    "font_size": i.font_size,
+                    "foreground": i.foreground.to_string(),
+                    "background": i.background.to_string(),
+                    "ratio": i.ratio,
+                    "required": i.required,
+                    "fixed": i.fixed.as_ref().map(Color::to_string),
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl Display for ContrastReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for i in &self.issues {
+            write!(
+                f,
+                "page {} at ({:.0},{:.0}) {:.1}pt {:?}: {} on {} is {:.2}:1, needs {}:1",
+                i.page,
+                i.position.x,
+                i.position.y,
+                i.font_size,
+                i.text,
+                i.foreground,
+                i.background,
+                i.ratio,
+                i.required
+            )?;
+            match &i.fixed {
+                Some(fixed) => writeln!(f, ", now {}", fixed)?,
+                None => writeln!(f)?,
+            }
+        }
+        writeln!(
+            f,
+            "{} text runs checked, {} below the required contrast, {} over an unknown background",
+            self.checked,
+            self.issues.len(),
+            self.unknown
+        )
+    }
+}
+
+// Something painted beneath text: a filled path with its colour, or an
+// image, shading or pattern fill whose colour is not known.
+struct Backdrop {
+    id: usize,
+    area: BezPath,
+    color: Option<Color>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContrastChecker {
+    pub level: ContrastLevel,
+    // text is darkened on light backgrounds and lightened on dark ones
+    pub fix: bool,
+}
+
+impl Default for ContrastChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContrastChecker {
+    pub fn new() -> Self {
+        Self {
+            level: ContrastLevel::AA,
+            fix: false,
+        }
+    }
+    // Large text is 18pt, or 14pt in a bold font.
+    fn is_large(&self, font_size: f32, bold: bool) -> bool {
+        font_size >= 18. || (bold && font_size >= 14.)
+    }
+    // The colour of the same family and hue closest to `foreground` that
+    // reaches the required ratio.
+    fn fix_color(
+        &self,
+        converter: &ColorConverter,
+        foreground: &Color,
+        background: (f32, f32, f32),
+        alpha: f32,
+        required: f32,
+    ) -> Option<Color> {
+        let family = converter.family(foreground);
+        let (l, a, b) = converter.to_lab(foreground);
+        let darken =
+            contrast_ratio((0., 0., 0.), background) >= contrast_ratio((1., 1., 1.), background);
+        let passes = |color: &Color| {
+            contrast_ratio(
+                blend(converter.to_rgb(color), background, alpha),
+                background,
+            ) >= required
+        };
+        let mut lightness = l;
+        loop {
+            lightness = if darken {
+                (lightness - 1.).max(0.)
+            } else {
+                (lightness + 1.).min(100.)
+            };
+            // hue is kept while chroma fades towards black or white
+            let chroma = if darken {
+                lightness / l.max(1.)
+            } else {
+                (100. - lightness) / (100. - l).max(1.)
+            };
+            let candidate =
+                converter.convert(&Color::Lab(lightness, a * chroma, b * chroma), family);
+            if passes(&candidate) {
+                return Some(candidate);
+            }
+            if lightness <= 0. || lightness >= 100. {
+                return None;
+            }
+        }
+    }
+    pub fn check(
+        &self,
+        modifier: &mut PdfModifier,
+        page_id: (u32, u16),
+        page_number: usize,
+        converter: &ColorConverter,
+        report: &mut ContrastReport,
+    ) {
+        let page = modifier.visible_box(page_id).unwrap_or(Rect::ZERO);
+        let xobjects = xobject_bboxes(modifier, page_id);
+        let fonts = page_fonts(modifier.document(), page_id);
+        let mut backdrops: Vec<Backdrop> = Vec::new();
+        let mut fixes: HashMap<usize, (bool, Color)> = HashMap::new();
+        modifier.for_each(page_id, &mut |operation, state| {
+            let color = &state.graphics.color;
+            match operation.operator.as_ref() {
+                "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" => {
+                    let tracked =
+                        color.is_tracked(false) && state.graphics.alpha_constant_non_stroke >= 1.;
+                    for path in state.path.subpaths() {
+                        backdrops.push(Backdrop {
+                            id: state.id,
+                            area: state.graphics.ctm * path.clone(),
+                            color: tracked.then(|| color.non_stroke.clone()),
+                        });
+                    }
+                }
+                "Do" | "BI" | "sh" => {
+                    if let Some(bbox) = painted_bbox(&operation, state, &xobjects, page) {
+                        backdrops.push(Backdrop {
+                            id: state.id,
+                            area: bbox.to_path(0.1),
+                            color: None,
+                        });
+                    }
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    let Some(paint) = Paint::of(&operation, state).first().copied() else {
+                        return;
+                    };
+                    let Some(bbox) = text_bbox(&operation, state) else {
+                        return;
+                    };
+                    let Some(first) = state.shown_glyphs(&operation).into_iter().next() else {
+                        return;
+                    };
+                    let stroke = paint.is_stroke();
+                    if !color.is_tracked(stroke) {
+                        return;
+                    }
+                    report.checked += 1;
+                    // the topmost area painted under the run; a run crossing
+                    // its edge is on more than one background
+                    let corners = [
+                        Point::new(bbox.x0, bbox.y0),
+                        Point::new(bbox.x1, bbox.y0),
+                        Point::new(bbox.x0, bbox.y1),
+                        Point::new(bbox.x1, bbox.y1),
+                    ];
+                    let background = match backdrops.iter().rfind(|b| {
+                        b.id < state.id && b.area.bounding_box().intersect(bbox).area() > 0.
+                    }) {
+                        Some(Backdrop {
+                            area,
+                            color: Some(color),
+                            ..
+                        }) if corners.iter().all(|corner| area.contains(*corner)) => color.clone(),
+                        Some(_) => {
+                            report.unknown += 1;
+                            return;
+                        }
+                        // the page itself
+                        None => Color::Gray(1.),
+                    };
+                    let foreground = if stroke {
+                        &color.stroke
+                    } else {
+                        &color.non_stroke
+                    };
+                    let alpha = if stroke {
+                        state.graphics.alpha_constant_stroke
+                    } else {
+                        state.graphics.alpha_constant_non_stroke
+                    };
+                    let background_rgb = converter.to_rgb(&background);
+                    let ratio = contrast_ratio(
+                        blend(converter.to_rgb(foreground), background_rgb, alpha),
+                        background_rgb,
+                    );
+                    let origin = first.em * Point::ZERO;
+                    let font_size = (first.em * Point::new(0., 1.) - origin).hypot() as f32;
+                    let bold = state
+                        .graphics
+                        .text
+                        .font
+                        .as_ref()
+                        .and_then(|font| fonts.get(font))
+                        .is_some_and(Font::is_bold);
+                    let required = self.level.required(self.is_large(font_size, bold));
+                    if ratio >= required {
+                        return;
+                    }
+                    let fixed = if self.fix {
+                        self.fix_color(converter, foreground, background_rgb, alpha, required)
+                    } else {
+                        None
+                    };
+                    if let Some(fixed) = &fixed {
+                        fixes.insert(state.id, (stroke, fixed.clone()));
+                    }
+                    report.issues.push(ContrastIssue {
+                        page: page_number,
+                        text: state.shown_text(&operation),
+                        position: origin,
+                        font_size,
+                        foreground: foreground.clone(),
+                        background,
+                        ratio,
+                        required,
+                        fixed,
+                    });
+                }
+                _ => (),
+            }
+        });
+        if fixes.is_empty() {
+            return;
+        }
+        recolor_operations(modifier, page_id, &mut |id, paint| {
+            fixes
+                .get(&id)
+                .filter(|(stroke, _)| *stroke == paint.is_stroke())
+                .map(|(_, fixed)| fixed.clone())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn check(content: &str) -> ContrastReport {
+        let (mut modifier, page_id) = testing::modifier(content);
+        let mut report = ContrastReport::default();
+        ContrastChecker::new().check(
+            &mut modifier,
+            page_id,
+            1,
+            &ColorConverter::naive(),
+            &mut report,
+        );
+        report
+    }
+
+    #[test]
+    fn issue_position_and_size() {
+        let report = check("BT /F1 12 Tf 2 0 0 2 72 700 Tm 0.9 g (AA) Tj (Light) Tj ET");
+        assert_eq!(report.issues.len(), 2);
+        let light = &report.issues[1];
+        assert_eq!(light.text, "Light");
+        // after the two A of 667 units, at twice the size
+        assert!((light.position.x - (72. + 2. * 0.667 * 24.)).abs() < 0.01);
+        assert_eq!(light.position.y, 700.);
+        assert_eq!(light.font_size, 24.);
+    }
+
+    #[test]
+    fn runs_across_a_fill_edge() {
+        let report = check(
+            "0 g 0 0 100 792 re f BT /F1 12 Tf 1 g 20 700 Td (Inside) Tj 60 0 Td (Across) Tj ET",
+        );
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.unknown, 1);
+        assert!(report.issues.is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use kurbo::{Affine, Point, Rect};
+use lopdf::{content::Operation, Object};
+
+use crate::graphics::text::RenderingMode;
+use crate::{page_resource, PdfModifier, State};
+
+// Form XObjects are measured by their `/BBox`; images fill the unit square.
+pub fn xobject_bboxes(modifier: &PdfModifier, page_id: (u32, u16)) -> HashMap<Vec<u8>, Rect> {
+    let doc = modifier.document();
+    let mut result = HashMap::new();
+    if let Some(xobjects) = page_resource(doc, page_id, b"XObject") {
+        for (name, xobject) in xobjects {
+            let dict = match doc.dereference(xobject) {
+                Ok((_, Object::Stream(stream))) => &stream.dict,
+                _ => continue,
+            };
+            let to_f64 = |o: &Object| o.as_float().or(o.as_i64().map(|v| v as f32)).ok();
+            let bbox = match dict
+                .get(b"BBox")
+                .and_then(|o| o.as_array())
+                .map(|a| a.iter().filter_map(to_f64).collect::<Vec<_>>())
+                .as_deref()
+            {
+                Ok([x0, y0, x1, y1]) => {
+                    Rect::new((*x0).into(), (*y0).into(), (*x1).into(), (*y1).into())
+                }
+                _ => Rect::new(0., 0., 1., 1.),
+            };
+            let matrix = match dict
+                .get(b"Matrix")
+                .and_then(|o| o.as_array())
+                .map(|a| a.iter().filter_map(to_f64).collect::<Vec<_>>())
+                .as_deref()
+            {
+                Ok([a, b, c, d, e, f]) => Affine::new([*a, *b, *c, *d, *e, *f].map(f32::into)),
+                _ => Affine::IDENTITY,
+            };
+            result.insert(name.to_vec(), matrix.transform_rect_bbox(bbox));
+        }
+    }
+    result
+}
+
+// The glyph boxes of a text showing operation, from the font's widths,
+// ascent and descent.
+pub fn text_bbox(operation: &Operation, state: &State) -> Option<Rect> {
+    let text = &state.graphics.text;
+    if let RenderingMode::Invisible | RenderingMode::AddClippingPath = text.rendering_mode {
+        return None;
+    }
+    state
+        .shown_glyphs(operation)
+        .iter()
+        .map(|glyph| glyph.bbox)
+        .reduce(|a, b| a.union(b))
+}
+
+fn stroke_bbox(state: &State) -> Option<Rect> {
+    let bbox = state.path.bounding_box()?;
+    let half_width = f64::from(state.graphics.line.width.max(1.)) / 2.;
+    Some(
+        state
+            .graphics
+            .ctm
+            .transform_rect_bbox(bbox.inflate(half_width, half_width)),
+    )
+}
+
+fn fill_bbox(state: &State) -> Option<Rect> {
+    state
+        .path
+        .subpaths()
+        .iter()
+        .map(|path| kurbo::Shape::bounding_box(*path))
+        .reduce(|a, b| a.union(b))
+        .map(|bbox| state.graphics.ctm.transform_rect_bbox(bbox))
+}
+
+// The area a single operation paints on the page, in default user space,
+// before clipping.
+pub fn painted_bbox(
+    operation: &Operation,
+    state: &State,
+    xobjects: &HashMap<Vec<u8>, Rect>,
+    page: Rect,
+) -> Option<Rect> {
+    match operation.operator.as_ref() {
+        "f" | "F" | "f*" => fill_bbox(state),
+        "S" | "s" | "B" | "B*" | "b" | "b*" => stroke_bbox(state),
+        "Tj" | "TJ" | "'" | "\"" => text_bbox(operation, state),
+        "Do" => operation
+            .operands
+            .first()
+            .and_then(|o| o.as_name().ok())
+            .and_then(|name| xobjects.get(name))
+            .map(|bbox| state.graphics.ctm.transform_rect_bbox(*bbox)),
+        "BI" => Some(
+            state
+                .graphics
+                .ctm
+                .transform_rect_bbox(Rect::from_points(Point::ZERO, (1., 1.))),
+        ),
+        // shadings without a clip paint the whole page
+        "sh" => Some(page),
+        _ => None,
+    }
+    .and_then(|bbox| match state.graphics.clip {
+        Some(clip) => clip_rect(bbox, clip),
+        None => Some(bbox),
+    })
+}
+
+// The part of `rect` inside `clip`, or `None` when they do not touch.
+// Zero-width or zero-height rectangles such as straight lines are kept.
+pub fn clip_rect(rect: Rect, clip: Rect) -> Option<Rect> {
+    if rect.x0 <= clip.x1 && clip.x0 <= rect.x1 && rect.y0 <= clip.y1 && clip.y0 <= rect.y1 {
+        Some(rect.intersect(clip))
+    } else {
+        None
+    }
+}
+
+// Union of everything painted on the page that falls inside its visible box.
+pub fn content_bbox(modifier: &mut PdfModifier, page_id: (u32, u16)) -> Option<Rect> {
+    let page = modifier.visible_box(page_id)?;
+    let xobjects = xobject_bboxes(modifier, page_id);
+    let mut bbox: Option<Rect> = None;
+    modifier.for_each(page_id, &mut |operation, state| {
+        if let Some(painted) = painted_bbox(&operation, state, &xobjects, page)
+            .and_then(|painted| clip_rect(painted, page))
+        {
+            bbox = Some(bbox.map_or(painted, |b| b.union(painted)));
+        }
+    });
+    bbox
+}
@@ -0,0 +1,303 @@
+use std::collections::{HashMap, HashSet};
+
+use lopdf::content::Operation;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::graphics::color::convert::{ColorConverter, ColorFamily};
+use crate::graphics::color::{Color, ColorSpace};
+use crate::{
+    drawn_xobjects, object_to_f32, page_color_operators, page_resource, resource_name,
+    rewrite_colors, rewrite_stream_colors, PdfModifier,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DarkStyle {
+    // every RGB component inverted, which also turns hues around
+    Invert,
+    // lightness reversed between the foreground and background lightness,
+    // keeping hue and chroma
+    Remap,
+}
+
+#[derive(Debug, Clone)]
+pub struct DarkMode {
+    pub style: DarkStyle,
+    pub background: Color,
+    // what black content becomes with the remap style
+    pub foreground: Color,
+    // brightness left to images, 1 keeps them untouched
+    pub image_brightness: f32,
+}
+
+impl DarkMode {
+    pub fn new() -> Self {
+        Self {
+            style: DarkStyle::Remap,
+            background: Color::RGB(0.12, 0.12, 0.12),
+            foreground: Color::Gray(0.9),
+            image_brightness: 1.,
+        }
+    }
+    pub fn map(&self, converter: &ColorConverter, color: &Color) -> Color {
+        match self.style {
+            DarkStyle::Invert => {
+                let (r, g, b) = converter.to_rgb(color);
+                match color {
+                    Color::Gray(g) => Color::Gray(1. - g),
+                    _ => Color::RGB(1. - r, 1. - g, 1. - b),
+                }
+            }
+            DarkStyle::Remap => {
+                let (l, a, b) = converter.to_lab(color);
+                let dark = converter.to_lab(&self.background).0;
+                let light = converter.to_lab(&self.foreground).0;
+                let l = dark + (100. - l) / 100. * (light - dark);
+                match color {
+                    // gray stays gray, and in its own family
+                    Color::Gray(_) => converter.convert(&Color::Lab(l, 0., 0.), ColorFamily::Gray),
+                    _ => Color::Lab(l, a, b),
+                }
+            }
+        }
+    }
+    // Rewrites the page's colours and paints the background beneath them.
+    pub fn apply(
+        &self,
+        modifier: &mut PdfModifier,
+        page_id: (u32, u16),
+        converter: &ColorConverter,
+    ) {
+        rewrite_colors(modifier, page_id, &mut |color, _| {
+            Some(self.map(converter, color))
+        });
+        if let Some(page) = modifier.visible_box(page_id) {
+            let mut background = page_color_operators(modifier, page_id, &self.background, false);
+            background.extend([
+                Operation::new(
+                    "re",
+                    [page.x0, page.y0, page.width(), page.height()]
+                        .map(|v| Object::from(v as f32))
+                        .to_vec(),
+                ),
+                Operation::new("f", vec![]),
+            ]);
+            modifier.insert_content(page_id, &background, true);
+        }
+    }
+    // Rewrites the colours of the document's forms and tiling patterns,
+    // once for all the pages using them.
+    pub fn apply_to_forms(&self, modifier: &mut PdfModifier, converter: &ColorConverter) -> usize {
+        rewrite_stream_colors(modifier, &mut |color, _| Some(self.map(converter, color)))
+    }
+    // Darkens the image XObjects the pages draw through their `/Decode`
+    // arrays, so the image data itself is left as it is. Images other pages
+    // draw too are darkened in a copy; images used as masks of others keep
+    // their values, which are coverage. Returns the number of images changed.
+    pub fn dim_images(&self, modifier: &mut PdfModifier, pages: &[(u32, u16)]) -> usize {
+        if self.image_brightness >= 1. {
+            return 0;
+        }
+        let doc = modifier.document();
+        let masks: HashSet<ObjectId> = doc
+            .objects
+            .values()
+            .filter_map(|object| match object {
+                Object::Stream(stream) => Some(&stream.dict),
+                _ => None,
+            })
+            .flat_map(|dict| [dict.get(b"SMask"), dict.get(b"Mask")])
+            .filter_map(|o| o.and_then(|o| o.as_reference()).ok())
+            .collect();
+        let (mut drawn, mut shared) = (HashSet::new(), HashSet::new());
+        for (_, page_id) in doc.get_pages() {
+            let found = if pages.contains(&page_id) {
+                &mut drawn
+            } else {
+                &mut shared
+            };
+            let (direct, inherited) = doc.get_page_resources(page_id);
+            let inherited = inherited
+                .into_iter()
+                .filter_map(|id| doc.get_dictionary(id).ok());
+            for resources in direct.into_iter().chain(inherited) {
+                drawn_xobjects(doc, Some(resources), found);
+            }
+        }
+        let decodes: HashMap<ObjectId, Vec<Object>> = drawn
+            .into_iter()
+            .filter(|id| !masks.contains(id))
+            .filter_map(|id| match doc.get_object(id) {
+                Ok(Object::Stream(stream)) => Some((id, self.dimmed_decode(doc, &stream.dict)?)),
+                _ => None,
+            })
+            .collect();
+
+        // the page content draws a copy of a shared image under a new name;
+        // shared images only drawn through forms are left
+        let mut copies: HashMap<ObjectId, ObjectId> = HashMap::new();
+        for page_id in pages {
+            let Some(xobjects) = page_resource(modifier.document(), *page_id, b"XObject").cloned()
+            else {
+                continue;
+            };
+            let mut renames = HashMap::new();
+            for (name, object) in xobjects.iter() {
+                let Ok(id) = object.as_reference() else {
+                    continue;
+                };
+                let Some(decode) = decodes.get(&id).filter(|_| shared.contains(&id)) else {
+                    continue;
+                };
+                let copy = *copies.entry(id).or_insert_with(|| {
+                    let doc = modifier.document_mut();
+                    let mut stream = doc.get_object(id).unwrap().as_stream().unwrap().clone();
+                    stream.dict.set("Decode", decode.clone());
+                    doc.add_object(stream)
+                });
+                let (new_name, present) = resource_name(modifier, *page_id, b"XObject", "Im", copy);
+                if !present {
+                    modifier.add_resource(*page_id, "XObject", &new_name, Object::Reference(copy));
+                }
+                renames.insert(name.clone(), new_name);
+            }
+            if renames.is_empty() {
+                continue;
+            }
+            modifier.apply(*page_id, &mut |mut operation, _| {
+                if operation.operator == "Do" {
+                    if let Some(Object::Name(name)) = operation.operands.first_mut() {
+                        if let Some(new_name) = renames.get(name) {
+                            *name = new_name.clone();
+                        }
+                    }
+                }
+                vec![operation]
+            });
+        }
+        let doc = modifier.document_mut();
+        let mut count = copies.len();
+        for (id, decode) in decodes {
+            if shared.contains(&id) {
+                continue;
+            }
+            if let Ok(Object::Stream(stream)) = doc.get_object_mut(id) {
+                stream.dict.set("Decode", decode);
+                count += 1;
+            }
+        }
+        count
+    }
+    // The `/Decode` array darkening an image, None for images that are not
+    // in a device family or have no usable one.
+    fn dimmed_decode(&self, doc: &Document, dict: &Dictionary) -> Option<Vec<Object>> {
+        let factor = self.image_brightness;
+        if dict.get(b"Subtype").and_then(|o| o.as_name()).ok() != Some(b"Image")
+            || dict
+                .get(b"ImageMask")
+                .and_then(|o| o.as_bool())
+                .unwrap_or(false)
+        {
+            return None;
+        }
+        let family = ColorSpace::from_resource(doc, b"", dict.get(b"ColorSpace").ok()?).family();
+        let Some(family @ (ColorFamily::Gray | ColorFamily::RGB | ColorFamily::CMYK)) = family
+        else {
+            return None;
+        };
+        let n = family.components();
+        let decode: Vec<f32> = match dict.get(b"Decode").and_then(|o| o.as_array()) {
+            Ok(array) => array.iter().filter_map(object_to_f32).collect(),
+            Err(_) => [0., 1.].repeat(n),
+        };
+        if decode.len() != 2 * n {
+            return None;
+        }
+        Some(
+            decode
+                .iter()
+                .enumerate()
+                .map(|(i, v)| match family {
+                    // more black ink instead of less light
+                    ColorFamily::CMYK if i / 2 == 3 => 1. - (1. - v) * factor,
+                    ColorFamily::CMYK => *v,
+                    _ => v * factor,
+                })
+                .map(Object::Real)
+                .collect(),
+        )
+    }
+}
+
+impl Default for DarkMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_operations, testing};
+    use lopdf::{dictionary, Stream};
+
+    #[test]
+    fn shared_images_are_copied() {
+        let mut doc = testing::page("q 10 0 0 10 0 0 cm /Im0 Do Q", Dictionary::new());
+        let image = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "BitsPerComponent" => 8,
+                "ColorSpace" => "DeviceGray",
+            },
+            vec![128],
+        ));
+        let first = doc.page_iter().next().unwrap();
+        let resources = dictionary! { "XObject" => dictionary! { "Im0" => image } };
+        let page = doc.get_dictionary_mut(first).unwrap();
+        page.set("Resources", resources.clone());
+        let mut second = page.clone();
+        let content = doc.add_object(Stream::new(
+            Dictionary::new(),
+            b"q 10 0 0 10 0 0 cm /Im0 Do Q".to_vec(),
+        ));
+        second.set("Contents", content);
+        let second = doc.add_object(second);
+        let pages = doc
+            .catalog()
+            .unwrap()
+            .get(b"Pages")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let pages = doc.get_dictionary_mut(pages).unwrap();
+        pages.set("Kids", vec![first.into(), second.into()]);
+        pages.set("Count", 2);
+
+        let mut modifier = PdfModifier::from_document(doc);
+        let dark = DarkMode {
+            image_brightness: 0.5,
+            ..DarkMode::new()
+        };
+        assert_eq!(dark.dim_images(&mut modifier, &[first]), 1);
+        let doc = modifier.document();
+        let drawn = |page_id| {
+            let operations = decode_operations(&doc.get_page_content(page_id).unwrap()).unwrap();
+            let name = operations
+                .iter()
+                .find(|o| o.operator == "Do")
+                .map(|o| o.operands[0].as_name().unwrap().to_vec())
+                .unwrap();
+            let xobjects = page_resource(doc, page_id, b"XObject").unwrap();
+            let id = xobjects.get(&name).unwrap().as_reference().unwrap();
+            doc.get_object(id).unwrap().as_stream().unwrap().clone()
+        };
+        let dimmed = drawn(first);
+        let decode = dimmed.dict.get(b"Decode").unwrap().as_array().unwrap();
+        assert_eq!(object_to_f32(&decode[1]), Some(0.5));
+        // the other page keeps the original
+        assert!(!drawn(second).dict.has(b"Decode"));
+    }
+}
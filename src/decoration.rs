@@ -0,0 +1,254 @@
+use kurbo::{Point, Rect, Shape};
+
+use crate::graphics::color::Color;
+use crate::graphics::text::RenderingMode;
+use crate::PdfModifier;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationKind {
+    Underline,
+    StrikeOut,
+    Highlight,
+}
+
+impl DecorationKind {
+    pub fn annotation_subtype(&self) -> &'static str {
+        match self {
+            Self::Underline => "Underline",
+            Self::StrikeOut => "StrikeOut",
+            Self::Highlight => "Highlight",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Decoration {
+    // id of the painting operation that draws the decoration
+    pub id: usize,
+    pub kind: DecorationKind,
+    pub stroke: bool,
+    pub color: Color,
+    pub rect: Rect,
+    // ids of the show operations it decorates, with their boxes
+    pub text_ids: Vec<usize>,
+    pub text_rects: Vec<Rect>,
+}
+
+#[derive(Debug, Clone)]
+struct TextBox {
+    id: usize,
+    bbox: Rect,
+    // of each glyph
+    glyphs: Vec<Rect>,
+    baseline: f64,
+    size: f64,
+}
+
+#[derive(Debug, Clone)]
+struct Mark {
+    id: usize,
+    stroke: bool,
+    color: Color,
+    alpha: f32,
+    rects: Vec<Rect>,
+}
+
+// How much of the width of `rect` the glyphs of `texts` span.
+fn covered(rect: &Rect, texts: &[&TextBox]) -> f64 {
+    let mut spans: Vec<(f64, f64)> = texts
+        .iter()
+        .flat_map(|t| &t.glyphs)
+        .map(|g| (g.x0.max(rect.x0), g.x1.min(rect.x1)))
+        .filter(|(x0, x1)| x0 < x1)
+        .collect();
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut width = 0.;
+    let mut end = f64::NEG_INFINITY;
+    for (x0, x1) in spans {
+        width += (x1 - x0.max(end)).max(0.);
+        end = end.max(x1);
+    }
+    width
+}
+
+#[derive(Debug, Clone)]
+pub struct DecorationDetector {
+    // lines and rectangles thinner than this can be underlines or strike-throughs
+    pub max_thickness: f64,
+}
+
+impl Default for DecorationDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecorationDetector {
+    pub fn new() -> Self {
+        Self { max_thickness: 3. }
+    }
+    pub fn detect(&self, modifier: &mut PdfModifier, page_id: (u32, u16)) -> Vec<Decoration> {
+        let mut texts: Vec<TextBox> = Vec::new();
+        let mut marks: Vec<Mark> = Vec::new();
+        modifier.for_each(page_id, &mut |operation, state| {
+            let ctm = state.graphics.ctm;
+            match operation.operator.as_ref() {
+                "f" | "F" | "f*" => marks.push(Mark {
+                    id: state.id,
+                    stroke: false,
+                    color: state.graphics.color.non_stroke.clone(),
+                    alpha: state.graphics.alpha_constant_non_stroke,
+                    rects: state
+                        .path
+                        .subpaths()
+                        .iter()
+                        .map(|path| ctm.transform_rect_bbox(path.bounding_box()))
+                        .collect(),
+                }),
+                "S" | "s" | "B" | "B*" | "b" | "b*" => {
+                    let half_width = f64::from(state.graphics.line.width) / 2.;
+                    marks.push(Mark {
+                        id: state.id,
+                        stroke: true,
+                        color: state.graphics.color.stroke.clone(),
+                        alpha: state.graphics.alpha_constant_stroke,
+                        rects: state
+                            .path
+                            .lines()
+                            .into_iter()
+                            .map(|line| {
+                                let line = ctm * line;
+                                Rect::from_points(line.p0, line.p1).inflate(0., half_width)
+                            })
+                            .collect(),
+                    })
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    if let RenderingMode::Invisible | RenderingMode::AddClippingPath =
+                        state.graphics.text.rendering_mode
+                    {
+                        return;
+                    }
+                    let glyphs = state.shown_glyphs(&operation);
+                    let Some(first) = glyphs.first() else {
+                        return;
+                    };
+                    let origin = first.em * Point::ZERO;
+                    texts.push(TextBox {
+                        id: state.id,
+                        bbox: glyphs
+                            .iter()
+                            .map(|g| g.bbox)
+                            .reduce(|a, b| a.union(b))
+                            .unwrap(),
+                        glyphs: glyphs.iter().map(|g| g.bbox).collect(),
+                        baseline: origin.y,
+                        size: (first.em * Point::new(0., 1.) - origin).hypot(),
+                    });
+                }
+                _ => (),
+            }
+        });
+        marks
+            .iter()
+            .filter_map(|mark| self.classify(mark, &texts))
+            .collect()
+    }
+    // A painting operation is a decoration only if every part of it decorates
+    // some text in the same way.
+    fn classify(&self, mark: &Mark, texts: &[TextBox]) -> Option<Decoration> {
+        let mut kind = None;
+        let mut decorated: Vec<&TextBox> = Vec::new();
+        for rect in &mark.rects {
+            let (k, t) = self.classify_rect(rect, mark, texts)?;
+            if matches!(kind, Some(kind) if kind != k) {
+                return None;
+            }
+            kind = Some(k);
+            for t in t {
+                if !decorated.iter().any(|d| d.id == t.id) {
+                    decorated.push(t);
+                }
+            }
+        }
+        Some(Decoration {
+            id: mark.id,
+            kind: kind?,
+            stroke: mark.stroke,
+            color: mark.color.clone(),
+            rect: mark.rects.iter().copied().reduce(|a, b| a.union(b))?,
+            text_ids: decorated.iter().map(|t| t.id).collect(),
+            text_rects: decorated.iter().map(|t| t.bbox).collect(),
+        })
+    }
+    fn classify_rect<'a>(
+        &self,
+        rect: &Rect,
+        mark: &Mark,
+        texts: &'a [TextBox],
+    ) -> Option<(DecorationKind, Vec<&'a TextBox>)> {
+        let overlapping = texts
+            .iter()
+            .filter(|t| t.bbox.x0 < rect.x1 && rect.x0 < t.bbox.x1);
+        if rect.height() <= self.max_thickness && rect.width() > 3. * rect.height() {
+            let y = rect.center().y;
+            for kind in [DecorationKind::Underline, DecorationKind::StrikeOut] {
+                let decorated: Vec<&TextBox> = overlapping
+                    .clone()
+                    .filter(|t| {
+                        let position = (y - t.baseline) / t.size;
+                        match kind {
+                            DecorationKind::Underline => (-0.35..=0.05).contains(&position),
+                            _ => (0.15..=0.5).contains(&position),
+                        }
+                    })
+                    .collect();
+                // a line under or through text runs along its glyphs, where
+                // a table rule or separator reaches well past them
+                if !decorated.is_empty() && covered(rect, &decorated) >= 0.7 * rect.width() {
+                    return Some((kind, decorated));
+                }
+            }
+            None
+        } else if !mark.stroke && mark.alpha < 1. {
+            let decorated: Vec<&TextBox> = overlapping
+                .filter(|t| rect.height() <= 3. * t.size && rect.contains(t.bbox.center()))
+                .collect();
+            if decorated.is_empty() {
+                None
+            } else {
+                Some((DecorationKind::Highlight, decorated))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::modifier;
+
+    #[test]
+    fn rules() {
+        let (mut modifier, page_id) = modifier(concat!(
+            // underlined with a stroke that closes its path
+            "BT /F1 10 Tf 100 700 Td (Underlined) Tj ET ",
+            "0.5 w 100 698 m 148 698 l s ",
+            // struck through with a filled and stroked line
+            "BT /F1 10 Tf 100 650 Td (Struck) Tj ET ",
+            "100 653 m 128 653 l B ",
+            // a table rule just under a short cell text
+            "BT /F1 10 Tf 100 600 Td (Cell) Tj ET ",
+            "100 598 m 400 598 l S"
+        ));
+        let decorations = DecorationDetector::new().detect(&mut modifier, page_id);
+        let kinds: Vec<_> = decorations.iter().map(|d| d.kind).collect();
+        assert_eq!(
+            kinds,
+            [DecorationKind::Underline, DecorationKind::StrikeOut]
+        );
+        assert!(decorations.iter().all(|d| d.stroke));
+    }
+}
@@ -0,0 +1,905 @@
+use std::collections::HashMap;
+
+use lopdf::{Dictionary, Document, Object};
+
+use crate::{object_to_f32, page_resource};
+
+// Decodes the codes shown with a font to Unicode and encodes Unicode text
+// back into codes of the same font. Simple fonts map single bytes through
+// their encoding, differences and `/ToUnicode` CMap; composite fonts are
+// taken to use two-byte codes as with `Identity-H`, known only through
+// their `/ToUnicode` CMap.
+#[derive(Debug, Clone)]
+pub struct Font {
+    pub base_font: String,
+    two_byte: bool,
+    unicode: HashMap<u32, String>,
+    // glyph widths in thousandths of text space units
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+    // in thousandths of text space units, as widths
+    pub ascent: f32,
+    pub descent: f32,
+    bold: bool,
+}
+
+impl Font {
+    pub fn from_dict(doc: &Document, dict: &Dictionary) -> Self {
+        let name = |key: &[u8]| {
+            dict.get(key)
+                .and_then(|o| o.as_name())
+                .map(|n| String::from_utf8_lossy(n).to_string())
+                .unwrap_or_default()
+        };
+        let base_font = name(b"BaseFont");
+        let subtype = name(b"Subtype");
+        let two_byte = subtype == "Type0";
+        let descendant = dict
+            .get(b"DescendantFonts")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_array())
+            .ok()
+            .and_then(|a| a.first())
+            .and_then(|o| doc.dereference(o).ok())
+            .and_then(|(_, o)| o.as_dict().ok());
+        let descriptor = descendant
+            .unwrap_or(dict)
+            .get(b"FontDescriptor")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+            .ok();
+        let mut unicode = HashMap::new();
+        if !two_byte {
+            let symbolic = descriptor
+                .and_then(|d| d.get(b"Flags").and_then(|o| o.as_i64()).ok())
+                .is_some_and(|flags| flags & 4 != 0 && flags & 32 == 0);
+            let encoding = dict.get(b"Encoding").and_then(|o| doc.dereference(o)).ok();
+            let base = match encoding {
+                Some((_, Object::Name(name))) => Some(name.as_slice()),
+                Some((_, Object::Dictionary(encoding))) => {
+                    encoding.get(b"BaseEncoding").and_then(|o| o.as_name()).ok()
+                }
+                _ => None,
+            };
+            let table: fn(u8) -> Option<char> = match base {
+                Some(b"WinAnsiEncoding") => win_ansi,
+                Some(b"MacRomanEncoding") => mac_roman,
+                Some(b"StandardEncoding") => standard,
+                _ if subtype == "TrueType" => win_ansi,
+                _ => standard,
+            };
+            if !symbolic || base.is_some() {
+                for code in 0..=255u8 {
+                    if let Some(c) = table(code) {
+                        unicode.insert(u32::from(code), c.to_string());
+                    }
+                }
+            }
+            if let Some((_, Object::Dictionary(encoding))) = encoding {
+                if let Ok(differences) = encoding.get(b"Differences").and_then(|o| o.as_array()) {
+                    let mut code = 0;
+                    for entry in differences {
+                        match entry {
+                            Object::Integer(i) => code = *i as u32,
+                            Object::Name(name) => {
+                                if let Some(c) = glyph_unicode(name) {
+                                    unicode.insert(code, c.to_string());
+                                }
+                                code += 1;
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+            }
+        }
+        if let Ok((_, Object::Stream(stream))) =
+            dict.get(b"ToUnicode").and_then(|o| doc.dereference(o))
+        {
+            let data = match stream.dict.get(b"Filter") {
+                Ok(_) => stream.decompressed_content().unwrap_or_default(),
+                Err(_) => stream.content.clone(),
+            };
+            unicode.extend(parse_cmap(&data));
+        }
+        let mut widths = HashMap::new();
+        let default_width;
+        match descendant {
+            Some(descendant) => {
+                default_width = descendant
+                    .get(b"DW")
+                    .ok()
+                    .and_then(object_to_f32)
+                    .unwrap_or(1000.);
+                let w = descendant
+                    .get(b"W")
+                    .and_then(|o| doc.dereference(o))
+                    .and_then(|(_, o)| o.as_array())
+                    .map(|a| a.as_slice())
+                    .unwrap_or_default();
+                let mut i = 0;
+                while i + 1 < w.len() {
+                    let Some(first) = object_to_f32(&w[i]) else {
+                        break;
+                    };
+                    match doc.dereference(&w[i + 1]) {
+                        Ok((_, Object::Array(list))) => {
+                            for (j, width) in list.iter().enumerate() {
+                                if let Some(width) = object_to_f32(width) {
+                                    widths.insert(first as u32 + j as u32, width);
+                                }
+                            }
+                            i += 2;
+                        }
+                        Ok((_, last)) => {
+                            if let (Some(last), Some(width)) =
+                                (object_to_f32(last), w.get(i + 2).and_then(object_to_f32))
+                            {
+                                for code in first as u32..=last as u32 {
+                                    widths.insert(code, width);
+                                }
+                            }
+                            i += 3;
+                        }
+                        Err(_) => break,
+                    }
+                }
+            }
+            None => {
+                default_width = descriptor
+                    .and_then(|d| d.get(b"MissingWidth").ok())
+                    .and_then(object_to_f32)
+                    .unwrap_or(if base_font.contains("Courier") {
+                        600.
+                    } else {
+                        500.
+                    });
+                let first = dict
+                    .get(b"FirstChar")
+                    .ok()
+                    .and_then(object_to_f32)
+                    .unwrap_or(0.) as u32;
+                if let Ok(list) = dict
+                    .get(b"Widths")
+                    .and_then(|o| doc.dereference(o))
+                    .and_then(|(_, o)| o.as_array())
+                {
+                    for (i, width) in list.iter().enumerate() {
+                        if let Some(width) = doc
+                            .dereference(width)
+                            .ok()
+                            .and_then(|(_, o)| object_to_f32(o))
+                        {
+                            widths.insert(first + i as u32, width);
+                        }
+                    }
+                }
+                // the standard fonts may come without widths
+                let standard =
+                    if base_font.starts_with("Helvetica") || base_font.starts_with("Arial") {
+                        Some(&HELVETICA_WIDTHS)
+                    } else if base_font.starts_with("Times") {
+                        Some(&TIMES_WIDTHS)
+                    } else {
+                        None
+                    };
+                if let (true, Some(standard)) = (widths.is_empty(), standard) {
+                    for (i, width) in standard.iter().enumerate() {
+                        widths.insert(0x20 + i as u32, f32::from(*width));
+                    }
+                }
+            }
+        }
+        // Type 3 glyphs are measured in their own glyph space
+        if subtype == "Type3" {
+            let scale = dict
+                .get(b"FontMatrix")
+                .and_then(|o| o.as_array())
+                .ok()
+                .and_then(|m| m.first())
+                .and_then(object_to_f32)
+                .map_or(1., |a| a * 1000.);
+            widths.values_mut().for_each(|w| *w *= scale);
+        }
+        let metric = |key: &[u8], default: f32| {
+            descriptor
+                .and_then(|d| d.get(key).ok())
+                .and_then(object_to_f32)
+                .filter(|v| *v != 0.)
+                .unwrap_or(default)
+        };
+        let ascent = metric(b"Ascent", 800.);
+        let descent = metric(b"Descent", -200.);
+        let lowercase = base_font.to_lowercase();
+        let weight = descriptor
+            .and_then(|d| d.get(b"FontWeight").ok())
+            .and_then(object_to_f32)
+            .unwrap_or(400.);
+        let force_bold = descriptor
+            .and_then(|d| d.get(b"Flags").and_then(|o| o.as_i64()).ok())
+            .is_some_and(|flags| flags & (1 << 18) != 0);
+        let bold = ["bold", "black", "heavy", "semibold", "demi"]
+            .iter()
+            .any(|w| lowercase.contains(w))
+            || weight >= 600.
+            || force_bold;
+        Self {
+            base_font,
+            two_byte,
+            unicode,
+            widths,
+            default_width,
+            ascent,
+            descent,
+            bold,
+        }
+    }
+    // Subsets are named with six capital letters and a plus sign.
+    pub fn is_subset(&self) -> bool {
+        let name = self.base_font.as_bytes();
+        name.len() > 7 && name[6] == b'+' && name[..6].iter().all(u8::is_ascii_uppercase)
+    }
+    pub fn is_bold(&self) -> bool {
+        self.bold
+    }
+    pub fn code_length(&self) -> usize {
+        if self.two_byte {
+            2
+        } else {
+            1
+        }
+    }
+    pub fn codes(&self, bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks(self.code_length())
+            .map(|chunk| chunk.iter().fold(0, |code, b| code << 8 | u32::from(*b)))
+            .collect()
+    }
+    pub fn unicode(&self, code: u32) -> Option<&str> {
+        self.unicode.get(&code).map(String::as_str)
+    }
+    // Unknown codes become U+FFFD.
+    pub fn text(&self, bytes: &[u8]) -> String {
+        self.codes(bytes)
+            .into_iter()
+            .map(|code| self.unicode(code).unwrap_or("\u{fffd}"))
+            .collect()
+    }
+    pub fn width(&self, code: u32) -> f32 {
+        self.widths
+            .get(&code)
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+    // Whether the font program can be expected to have a glyph for the code.
+    // Subsets of simple fonts keep a width only for the glyphs they contain;
+    // composite fonts are known only by their `/ToUnicode` entries.
+    pub fn has_glyph(&self, code: u32) -> bool {
+        if self.two_byte {
+            self.unicode.contains_key(&code)
+        } else if self.is_subset() && !self.widths.is_empty() {
+            self.widths.get(&code).is_some_and(|w| *w > 0.)
+        } else {
+            true
+        }
+    }
+    // Codes for the text, or the first character the font cannot show.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, char> {
+        let mut reverse: HashMap<&str, u32> = HashMap::new();
+        let mut codes: Vec<(&u32, &String)> = self.unicode.iter().collect();
+        codes.sort();
+        for (code, unicode) in codes {
+            if self.has_glyph(*code) {
+                reverse.entry(unicode.as_str()).or_insert(*code);
+            }
+        }
+        let mut bytes = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            // ligatures and other multi-character glyphs first
+            let (code, length) = match reverse
+                .iter()
+                .filter(|(unicode, _)| unicode.len() > c.len_utf8() && rest.starts_with(*unicode))
+                .max_by_key(|(unicode, _)| unicode.len())
+            {
+                Some((unicode, code)) => (*code, unicode.len()),
+                None => match reverse.get(&rest[..c.len_utf8()]) {
+                    Some(code) => (*code, c.len_utf8()),
+                    None => return Err(c),
+                },
+            };
+            if self.two_byte {
+                bytes.extend_from_slice(&(code as u16).to_be_bytes());
+            } else {
+                bytes.push(code as u8);
+            }
+            rest = &rest[length..];
+        }
+        Ok(bytes)
+    }
+}
+
+// The fonts of a page by resource name.
+pub fn page_fonts(doc: &Document, page_id: (u32, u16)) -> HashMap<Vec<u8>, Font> {
+    page_resource(doc, page_id, b"Font")
+        .map(|fonts| {
+            fonts
+                .iter()
+                .filter_map(|(name, font)| match doc.dereference(font) {
+                    Ok((_, Object::Dictionary(dict))) => {
+                        Some((name.clone(), Font::from_dict(doc, dict)))
+                    }
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// The `bfchar` and `bfrange` mappings of a ToUnicode CMap.
+fn parse_cmap(data: &[u8]) -> HashMap<u32, String> {
+    #[derive(Debug)]
+    enum Token {
+        Hex(Vec<u8>),
+        Word(String),
+        ArrayStart,
+        ArrayEnd,
+    }
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'<' if data.get(i + 1) != Some(&b'<') => {
+                let end = data[i..]
+                    .iter()
+                    .position(|b| *b == b'>')
+                    .map_or(data.len(), |p| i + p);
+                let digits: Vec<u8> = data[i + 1..end]
+                    .iter()
+                    .filter(|b| b.is_ascii_hexdigit())
+                    .copied()
+                    .collect();
+                let bytes = digits
+                    .chunks(2)
+                    .filter_map(|pair| {
+                        let pair = if pair.len() == 1 {
+                            vec![pair[0], b'0']
+                        } else {
+                            pair.to_vec()
+                        };
+                        u8::from_str_radix(std::str::from_utf8(&pair).ok()?, 16).ok()
+                    })
+                    .collect();
+                tokens.push(Token::Hex(bytes));
+                i = end + 1;
+            }
+            b'[' => {
+                tokens.push(Token::ArrayStart);
+                i += 1;
+            }
+            b']' => {
+                tokens.push(Token::ArrayEnd);
+                i += 1;
+            }
+            b'%' => {
+                while i < data.len() && data[i] != b'\n' && data[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            b if b.is_ascii_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                while i < data.len()
+                    && !data[i].is_ascii_whitespace()
+                    && !b"<>[]%".contains(&data[i])
+                {
+                    i += 1;
+                }
+                if i == start {
+                    i += 1;
+                }
+                tokens.push(Token::Word(
+                    String::from_utf8_lossy(&data[start..i]).to_string(),
+                ));
+            }
+        }
+    }
+    let code = |bytes: &[u8]| bytes.iter().fold(0u32, |code, b| code << 8 | u32::from(*b));
+    let text = |bytes: &[u8]| {
+        if bytes.len() == 1 {
+            char::from(bytes[0]).to_string()
+        } else {
+            let units: Vec<u16> = bytes
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+    };
+    let mut result = HashMap::new();
+    let mut section = "";
+    let mut i = 0;
+    while i < tokens.len() {
+        match (&tokens[i], section) {
+            (Token::Word(word), _) if word == "beginbfchar" => section = "bfchar",
+            (Token::Word(word), _) if word == "beginbfrange" => section = "bfrange",
+            (Token::Word(word), _) if word.starts_with("end") => section = "",
+            (Token::Hex(source), "bfchar") => {
+                if let Some(Token::Hex(target)) = tokens.get(i + 1) {
+                    result.insert(code(source), text(target));
+                    i += 1;
+                }
+            }
+            (Token::Hex(low), "bfrange") => {
+                let (low, high) = match tokens.get(i + 1) {
+                    Some(Token::Hex(high)) => (code(low), code(high)),
+                    _ => {
+                        i += 1;
+                        continue;
+                    }
+                };
+                match tokens.get(i + 2) {
+                    Some(Token::Hex(target)) if !target.is_empty() => {
+                        let mut target = target.clone();
+                        for source in low..=high.min(low.saturating_add(0xffff)) {
+                            result.insert(source, text(&target));
+                            // the last byte counts up within the range
+                            let last = target.len() - 1;
+                            target[last] = target[last].wrapping_add(1);
+                        }
+                        i += 2;
+                    }
+                    Some(Token::ArrayStart) => {
+                        let mut j = i + 3;
+                        let mut source = low;
+                        while let Some(Token::Hex(target)) = tokens.get(j) {
+                            result.insert(source, text(target));
+                            source += 1;
+                            j += 1;
+                        }
+                        i = j;
+                    }
+                    _ => i += 1,
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    result
+}
+
+// Unicode of the glyph names used by the Latin encodings, in the form of
+// the Adobe Glyph List, and `uniXXXX` or `uXXXX` names.
+pub fn glyph_unicode(name: &[u8]) -> Option<char> {
+    let name = std::str::from_utf8(name).ok()?;
+    let name = name.split('.').next().unwrap_or(name);
+    if let Some(index) = WIN_ANSI_NAMES.iter().position(|n| *n == name) {
+        return win_ansi(index as u8 + 0x20);
+    }
+    if let Some((_, c)) = EXTRA_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(*c);
+    }
+    let hex = name.strip_prefix("uni").filter(|h| h.len() == 4).or(name
+        .strip_prefix('u')
+        .filter(|h| (4..=6).contains(&h.len())))?;
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+// The glyph name of a character, the reverse of `glyph_unicode`.
+pub fn glyph_name(c: char) -> String {
+    if let Some(index) = (0x20..=0xffu8).position(|code| win_ansi(code) == Some(c)) {
+        return WIN_ANSI_NAMES[index].to_string();
+    }
+    if let Some((name, _)) = EXTRA_NAMES.iter().find(|(_, e)| *e == c) {
+        return name.to_string();
+    }
+    match u32::from(c) {
+        code @ 0..=0xffff => format!("uni{:04X}", code),
+        code => format!("u{:X}", code),
+    }
+}
+
+// Glyph names of StandardEncoding, which Type 1 font programs use.
+pub fn standard_glyph_name(code: u8) -> Option<String> {
+    standard(code).map(glyph_name)
+}
+
+// Glyph names of WinAnsiEncoding from 0x20.
+const WIN_ANSI_NAMES: [&str; 224] = [
+    "space",
+    "exclam",
+    "quotedbl",
+    "numbersign",
+    "dollar",
+    "percent",
+    "ampersand",
+    "quotesingle",
+    "parenleft",
+    "parenright",
+    "asterisk",
+    "plus",
+    "comma",
+    "hyphen",
+    "period",
+    "slash",
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "colon",
+    "semicolon",
+    "less",
+    "equal",
+    "greater",
+    "question",
+    "at",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "bracketleft",
+    "backslash",
+    "bracketright",
+    "asciicircum",
+    "underscore",
+    "grave",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "braceleft",
+    "bar",
+    "braceright",
+    "asciitilde",
+    "",
+    "Euro",
+    "",
+    "quotesinglbase",
+    "florin",
+    "quotedblbase",
+    "ellipsis",
+    "dagger",
+    "daggerdbl",
+    "circumflex",
+    "perthousand",
+    "Scaron",
+    "guilsinglleft",
+    "OE",
+    "",
+    "Zcaron",
+    "",
+    "",
+    "quoteleft",
+    "quoteright",
+    "quotedblleft",
+    "quotedblright",
+    "bullet",
+    "endash",
+    "emdash",
+    "tilde",
+    "trademark",
+    "scaron",
+    "guilsinglright",
+    "oe",
+    "",
+    "zcaron",
+    "Ydieresis",
+    "nbspace",
+    "exclamdown",
+    "cent",
+    "sterling",
+    "currency",
+    "yen",
+    "brokenbar",
+    "section",
+    "dieresis",
+    "copyright",
+    "ordfeminine",
+    "guillemotleft",
+    "logicalnot",
+    "sfthyphen",
+    "registered",
+    "macron",
+    "degree",
+    "plusminus",
+    "twosuperior",
+    "threesuperior",
+    "acute",
+    "mu",
+    "paragraph",
+    "periodcentered",
+    "cedilla",
+    "onesuperior",
+    "ordmasculine",
+    "guillemotright",
+    "onequarter",
+    "onehalf",
+    "threequarters",
+    "questiondown",
+    "Agrave",
+    "Aacute",
+    "Acircumflex",
+    "Atilde",
+    "Adieresis",
+    "Aring",
+    "AE",
+    "Ccedilla",
+    "Egrave",
+    "Eacute",
+    "Ecircumflex",
+    "Edieresis",
+    "Igrave",
+    "Iacute",
+    "Icircumflex",
+    "Idieresis",
+    "Eth",
+    "Ntilde",
+    "Ograve",
+    "Oacute",
+    "Ocircumflex",
+    "Otilde",
+    "Odieresis",
+    "multiply",
+    "Oslash",
+    "Ugrave",
+    "Uacute",
+    "Ucircumflex",
+    "Udieresis",
+    "Yacute",
+    "Thorn",
+    "germandbls",
+    "agrave",
+    "aacute",
+    "acircumflex",
+    "atilde",
+    "adieresis",
+    "aring",
+    "ae",
+    "ccedilla",
+    "egrave",
+    "eacute",
+    "ecircumflex",
+    "edieresis",
+    "igrave",
+    "iacute",
+    "icircumflex",
+    "idieresis",
+    "eth",
+    "ntilde",
+    "ograve",
+    "oacute",
+    "ocircumflex",
+    "otilde",
+    "odieresis",
+    "divide",
+    "oslash",
+    "ugrave",
+    "uacute",
+    "ucircumflex",
+    "udieresis",
+    "yacute",
+    "thorn",
+    "ydieresis",
+];
+
+const EXTRA_NAMES: [(&str, char); 17] = [
+    ("fi", '\u{fb01}'),
+    ("fl", '\u{fb02}'),
+    ("ff", '\u{fb00}'),
+    ("ffi", '\u{fb03}'),
+    ("ffl", '\u{fb04}'),
+    ("dotlessi", '\u{131}'),
+    ("Lslash", '\u{141}'),
+    ("lslash", '\u{142}'),
+    ("fraction", '\u{2044}'),
+    ("minus", '\u{2212}'),
+    ("hungarumlaut", '\u{2dd}'),
+    ("ogonek", '\u{2db}'),
+    ("caron", '\u{2c7}'),
+    ("breve", '\u{2d8}'),
+    ("dotaccent", '\u{2d9}'),
+    ("ring", '\u{2da}'),
+    ("nonbreakingspace", '\u{a0}'),
+];
+
+// Widths of the printable ASCII glyphs of two standard fonts.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+const TIMES_WIDTHS: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444, 921, 722, 667, 667, 722, 611,
+    556, 722, 722, 333, 389, 722, 611, 889, 722, 722, 556, 722, 667, 556, 611, 722, 722, 944, 722,
+    722, 611, 333, 278, 333, 469, 500, 333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500,
+    278, 778, 500, 500, 500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+
+fn win_ansi(code: u8) -> Option<char> {
+    const HIGH: [u16; 32] = [
+        0x20ac, 0, 0x201a, 0x192, 0x201e, 0x2026, 0x2020, 0x2021, 0x2c6, 0x2030, 0x160, 0x2039,
+        0x152, 0, 0x17d, 0, 0, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013, 0x2014, 0x2dc,
+        0x2122, 0x161, 0x203a, 0x153, 0, 0x17e, 0x178,
+    ];
+    match code {
+        0x20..=0x7e | 0xa0..=0xff => Some(char::from(code)),
+        0x80..=0x9f => char::from_u32(HIGH[usize::from(code - 0x80)].into()).filter(|c| *c != '\0'),
+        _ => None,
+    }
+}
+
+fn mac_roman(code: u8) -> Option<char> {
+    const HIGH: [u16; 128] = [
+        0xc4, 0xc5, 0xc7, 0xc9, 0xd1, 0xd6, 0xdc, 0xe1, 0xe0, 0xe2, 0xe4, 0xe3, 0xe5, 0xe7, 0xe9,
+        0xe8, 0xea, 0xeb, 0xed, 0xec, 0xee, 0xef, 0xf1, 0xf3, 0xf2, 0xf4, 0xf6, 0xf5, 0xfa, 0xf9,
+        0xfb, 0xfc, 0x2020, 0xb0, 0xa2, 0xa3, 0xa7, 0x2022, 0xb6, 0xdf, 0xae, 0xa9, 0x2122, 0xb4,
+        0xa8, 0x2260, 0xc6, 0xd8, 0x221e, 0xb1, 0x2264, 0x2265, 0xa5, 0xb5, 0x2202, 0x2211, 0x220f,
+        0x3c0, 0x222b, 0xaa, 0xba, 0x3a9, 0xe6, 0xf8, 0xbf, 0xa1, 0xac, 0x221a, 0x192, 0x2248,
+        0x2206, 0xab, 0xbb, 0x2026, 0xa0, 0xc0, 0xc3, 0xd5, 0x152, 0x153, 0x2013, 0x2014, 0x201c,
+        0x201d, 0x2018, 0x2019, 0xf7, 0x25ca, 0xff, 0x178, 0x2044, 0x20ac, 0x2039, 0x203a, 0xfb01,
+        0xfb02, 0x2021, 0xb7, 0x201a, 0x201e, 0x2030, 0xc2, 0xca, 0xc1, 0xcb, 0xc8, 0xcd, 0xce,
+        0xcf, 0xcc, 0xd3, 0xd4, 0xf8ff, 0xd2, 0xda, 0xdb, 0xd9, 0x131, 0x2c6, 0x2dc, 0xaf, 0x2d8,
+        0x2d9, 0x2da, 0xb8, 0x2dd, 0x2db, 0x2c7,
+    ];
+    match code {
+        0x20..=0x7e => Some(char::from(code)),
+        0x80..=0xff => char::from_u32(HIGH[usize::from(code - 0x80)].into()),
+        _ => None,
+    }
+}
+
+fn standard(code: u8) -> Option<char> {
+    const HIGH: [(u8, u16); 54] = [
+        (0xa1, 0xa1),
+        (0xa2, 0xa2),
+        (0xa3, 0xa3),
+        (0xa4, 0x2044),
+        (0xa5, 0xa5),
+        (0xa6, 0x192),
+        (0xa7, 0xa7),
+        (0xa8, 0xa4),
+        (0xa9, 0x27),
+        (0xaa, 0x201c),
+        (0xab, 0xab),
+        (0xac, 0x2039),
+        (0xad, 0x203a),
+        (0xae, 0xfb01),
+        (0xaf, 0xfb02),
+        (0xb1, 0x2013),
+        (0xb2, 0x2020),
+        (0xb3, 0x2021),
+        (0xb4, 0xb7),
+        (0xb6, 0xb6),
+        (0xb7, 0x2022),
+        (0xb8, 0x201a),
+        (0xb9, 0x201e),
+        (0xba, 0x201d),
+        (0xbb, 0xbb),
+        (0xbc, 0x2026),
+        (0xbd, 0x2030),
+        (0xbf, 0xbf),
+        (0xc1, 0x60),
+        (0xc2, 0xb4),
+        (0xc3, 0x2c6),
+        (0xc4, 0x2dc),
+        (0xc5, 0xaf),
+        (0xc6, 0x2d8),
+        (0xc7, 0x2d9),
+        (0xc8, 0xa8),
+        (0xca, 0x2da),
+        (0xcb, 0xb8),
+        (0xcd, 0x2dd),
+        (0xce, 0x2db),
+        (0xcf, 0x2c7),
+        (0xd0, 0x2014),
+        (0xe1, 0xc6),
+        (0xe3, 0xaa),
+        (0xe8, 0x141),
+        (0xe9, 0xd8),
+        (0xea, 0x152),
+        (0xeb, 0xba),
+        (0xf1, 0xe6),
+        (0xf5, 0x131),
+        (0xf8, 0x142),
+        (0xf9, 0xf8),
+        (0xfa, 0x153),
+        (0xfb, 0xdf),
+    ];
+    match code {
+        0x27 => Some('\u{2019}'),
+        0x60 => Some('\u{2018}'),
+        0x20..=0x7e => Some(char::from(code)),
+        _ => HIGH
+            .iter()
+            .find(|(c, _)| *c == code)
+            .and_then(|(_, u)| char::from_u32((*u).into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cmap() {
+        let cmap = parse_cmap(
+            b"/CIDInit /ProcSet findresource begin\n\
+              2 beginbfchar\n<01> <0041>\n<0002> <D83DDE00>\nendbfchar\n\
+              % a comment <03> <0043>\n\
+              2 beginbfrange\n<10> <12> <0061>\n<20> <21> [<0078> <00660069>]\nendbfrange\n",
+        );
+        assert_eq!(cmap.get(&1).map(String::as_str), Some("A"));
+        assert_eq!(cmap.get(&2).map(String::as_str), Some("\u{1F600}"));
+        assert_eq!(cmap.get(&3), None);
+        assert_eq!(cmap.get(&0x11).map(String::as_str), Some("b"));
+        assert_eq!(cmap.get(&0x12).map(String::as_str), Some("c"));
+        assert_eq!(cmap.get(&0x21).map(String::as_str), Some("fi"));
+    }
+
+    #[test]
+    fn cmap_malformed() {
+        assert!(parse_cmap(b"").is_empty());
+        assert!(parse_cmap(b"beginbfchar <01").is_empty());
+        let cmap = parse_cmap(b"beginbfrange <FFFFFFFF> <FFFFFFFF> <0041> <0A> <0B> <> endbfrange");
+        assert_eq!(cmap.get(&0xFFFFFFFF).map(String::as_str), Some("A"));
+        assert_eq!(cmap.get(&0x0A), None);
+        // an odd digit is padded with a zero
+        let cmap = parse_cmap(b"beginbfchar <1> <0041> endbfchar");
+        assert_eq!(cmap.get(&0x10).map(String::as_str), Some("A"));
+    }
+}
@@ -0,0 +1,747 @@
+use lopdf::{Dictionary, Document, Object, Stream};
+
+// PDF functions (ISO 32000-1, 7.10): sampled, exponential, stitching and
+// PostScript calculator functions, as used by shadings and tint transforms.
+
+#[derive(Debug, Clone)]
+pub enum Function {
+    Sampled {
+        domain: Vec<(f32, f32)>,
+        range: Vec<(f32, f32)>,
+        size: Vec<usize>,
+        encode: Vec<(f32, f32)>,
+        decode: Vec<(f32, f32)>,
+        // normalised to 0..1
+        samples: Vec<f32>,
+    },
+    Exponential {
+        domain: (f32, f32),
+        c0: Vec<f32>,
+        c1: Vec<f32>,
+        n: f32,
+    },
+    Stitching {
+        domain: (f32, f32),
+        functions: Vec<Function>,
+        bounds: Vec<f32>,
+        encode: Vec<(f32, f32)>,
+    },
+    Calculator {
+        domain: Vec<(f32, f32)>,
+        range: Vec<(f32, f32)>,
+        program: Vec<Token>,
+    },
+    // an array of single output functions
+    Array(Vec<Function>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Number(f64),
+    Operator(String),
+    Block(Vec<Token>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    Number(f64),
+    Bool(bool),
+}
+
+fn numbers(dict: &Dictionary, key: &[u8]) -> Option<Vec<f32>> {
+    dict.get(key)
+        .and_then(|o| o.as_array())
+        .ok()?
+        .iter()
+        .map(|o| o.as_float().ok().or(o.as_i64().ok().map(|i| i as f32)))
+        .collect()
+}
+
+fn pairs(values: &[f32]) -> Vec<(f32, f32)> {
+    values.chunks_exact(2).map(|c| (c[0], c[1])).collect()
+}
+
+fn interpolate(x: f32, (x0, x1): (f32, f32), (y0, y1): (f32, f32)) -> f32 {
+    if x1 == x0 {
+        y0
+    } else {
+        y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+    }
+}
+
+fn clip(x: f32, (min, max): (f32, f32)) -> f32 {
+    x.max(min.min(max)).min(max.max(min))
+}
+
+impl Function {
+    pub fn from_object(doc: &Document, object: &Object) -> Option<Self> {
+        let object = doc.dereference(object).ok()?.1;
+        if let Ok(array) = object.as_array() {
+            return array
+                .iter()
+                .map(|o| Self::from_object(doc, o))
+                .collect::<Option<Vec<_>>>()
+                .map(Self::Array);
+        }
+        let (dict, data) = match object {
+            Object::Dictionary(dict) => (dict, None),
+            Object::Stream(stream) => (
+                &stream.dict,
+                Some(
+                    stream
+                        .decompressed_content()
+                        .unwrap_or_else(|_| stream.content.clone()),
+                ),
+            ),
+            _ => return None,
+        };
+        let domain = pairs(&numbers(dict, b"Domain")?);
+        match dict.get(b"FunctionType").and_then(|o| o.as_i64()).ok()? {
+            0 => {
+                let range = pairs(&numbers(dict, b"Range")?);
+                let size: Vec<usize> = numbers(dict, b"Size")?
+                    .iter()
+                    .map(|s| *s as usize)
+                    .collect();
+                let encode = numbers(dict, b"Encode")
+                    .map(|e| pairs(&e))
+                    .unwrap_or_else(|| size.iter().map(|s| (0., *s as f32 - 1.)).collect());
+                let decode = numbers(dict, b"Decode")
+                    .map(|d| pairs(&d))
+                    .unwrap_or_else(|| range.clone());
+                // every input corner is visited, so their number is kept small
+                if domain.is_empty()
+                    || domain.len() > 16
+                    || size.len() != domain.len()
+                    || encode.len() != domain.len()
+                    || decode.len() != range.len()
+                    || size.contains(&0)
+                {
+                    return None;
+                }
+                let bits = dict.get(b"BitsPerSample").and_then(|o| o.as_i64()).ok()?;
+                if !(1..=32).contains(&bits) {
+                    return None;
+                }
+                let count = size
+                    .iter()
+                    .try_fold(range.len(), |count, s| count.checked_mul(*s))?;
+                let samples = read_samples(&data?, bits as usize, count)?;
+                Some(Self::Sampled {
+                    domain,
+                    range,
+                    size,
+                    encode,
+                    decode,
+                    samples,
+                })
+            }
+            2 => {
+                let c0 = numbers(dict, b"C0").unwrap_or_else(|| vec![0.]);
+                let c1 = numbers(dict, b"C1").unwrap_or_else(|| vec![1.]);
+                let n = dict
+                    .get(b"N")
+                    .ok()
+                    .and_then(|o| o.as_float().ok().or(o.as_i64().ok().map(|i| i as f32)))?;
+                Some(Self::Exponential {
+                    domain: *domain.first()?,
+                    c0,
+                    c1,
+                    n,
+                })
+            }
+            3 => {
+                let functions = doc
+                    .dereference(dict.get(b"Functions").ok()?)
+                    .ok()?
+                    .1
+                    .as_array()
+                    .ok()?
+                    .iter()
+                    .map(|o| Self::from_object(doc, o))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Self::Stitching {
+                    domain: *domain.first()?,
+                    functions,
+                    bounds: numbers(dict, b"Bounds")?,
+                    encode: pairs(&numbers(dict, b"Encode")?),
+                })
+            }
+            4 => Some(Self::Calculator {
+                domain,
+                range: pairs(&numbers(dict, b"Range")?),
+                program: parse_program(&data?)?,
+            }),
+            _ => None,
+        }
+    }
+    pub fn evaluate(&self, input: &[f32]) -> Vec<f32> {
+        match self {
+            Self::Sampled {
+                domain,
+                range,
+                size,
+                encode,
+                decode,
+                samples,
+            } => {
+                // position in the sample grid for each input
+                let position: Vec<f32> = domain
+                    .iter()
+                    .enumerate()
+                    .map(|(i, d)| {
+                        let x = clip(input.get(i).copied().unwrap_or(d.0), *d);
+                        clip(interpolate(x, *d, encode[i]), (0., size[i] as f32 - 1.))
+                    })
+                    .collect();
+                let outputs = range.len();
+                let mut result = vec![0.; outputs];
+                // multilinear interpolation over the surrounding corners
+                for corner in 0..(1usize << position.len()) {
+                    let mut weight = 1.;
+                    let mut index = 0;
+                    let mut stride = 1;
+                    for (i, p) in position.iter().enumerate() {
+                        let low = p.floor();
+                        let high = (low as usize + 1).min(size[i] - 1);
+                        let (coordinate, w) = if corner & (1 << i) == 0 {
+                            (low as usize, 1. - (p - low))
+                        } else {
+                            (high, p - low)
+                        };
+                        weight *= w;
+                        index += coordinate * stride;
+                        stride *= size[i];
+                    }
+                    if weight == 0. {
+                        continue;
+                    }
+                    for (o, value) in result.iter_mut().enumerate() {
+                        *value += weight * samples.get(index * outputs + o).copied().unwrap_or(0.);
+                    }
+                }
+                result
+                    .iter()
+                    .enumerate()
+                    .map(|(o, v)| clip(interpolate(*v, (0., 1.), decode[o]), range[o]))
+                    .collect()
+            }
+            Self::Exponential { domain, c0, c1, n } => {
+                let x = clip(input.first().copied().unwrap_or(0.), *domain);
+                c0.iter()
+                    .zip(c1)
+                    .map(|(c0, c1)| c0 + x.powf(*n) * (c1 - c0))
+                    .collect()
+            }
+            Self::Stitching {
+                domain,
+                functions,
+                bounds,
+                encode,
+            } => {
+                let x = clip(input.first().copied().unwrap_or(0.), *domain);
+                let k = bounds.iter().take_while(|b| x >= **b).count();
+                let low = if k == 0 { domain.0 } else { bounds[k - 1] };
+                let high = bounds.get(k).copied().unwrap_or(domain.1);
+                match (functions.get(k), encode.get(k)) {
+                    (Some(function), Some(encode)) => {
+                        function.evaluate(&[interpolate(x, (low, high), *encode)])
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            Self::Calculator {
+                domain,
+                range,
+                program,
+            } => {
+                let mut stack: Vec<Value> = domain
+                    .iter()
+                    .enumerate()
+                    .map(|(i, d)| {
+                        Value::Number(clip(input.get(i).copied().unwrap_or(d.0), *d) as f64)
+                    })
+                    .collect();
+                execute(program, &mut stack);
+                let start = stack.len().saturating_sub(range.len());
+                range
+                    .iter()
+                    .enumerate()
+                    .map(|(i, r)| match stack.get(start + i) {
+                        Some(Value::Number(v)) => clip(*v as f32, *r),
+                        _ => r.0,
+                    })
+                    .collect()
+            }
+            Self::Array(functions) => functions.iter().flat_map(|f| f.evaluate(input)).collect(),
+        }
+    }
+    pub fn domain(&self) -> Vec<(f32, f32)> {
+        match self {
+            Self::Sampled { domain, .. } | Self::Calculator { domain, .. } => domain.clone(),
+            Self::Exponential { domain, .. } | Self::Stitching { domain, .. } => vec![*domain],
+            Self::Array(functions) => functions.first().map(|f| f.domain()).unwrap_or_default(),
+        }
+    }
+}
+
+fn read_samples(data: &[u8], bits: usize, count: usize) -> Option<Vec<f32>> {
+    if data.len().checked_mul(8)? < bits.checked_mul(count)? {
+        return None;
+    }
+    let max = ((1u64 << bits) - 1) as f32;
+    let mut samples = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut value: u64 = 0;
+        for bit in i * bits..(i + 1) * bits {
+            let byte = *data.get(bit / 8)?;
+            value = (value << 1) | ((byte >> (7 - bit % 8)) & 1) as u64;
+        }
+        samples.push(value as f32 / max);
+    }
+    Some(samples)
+}
+
+// Builds a sampled function with 8 bit samples and `samples` points along
+// each input.
+pub fn sample_function<F: Fn(&[f32]) -> Vec<f32>>(
+    domain: &[(f32, f32)],
+    range: &[(f32, f32)],
+    samples: usize,
+    function: F,
+) -> Stream {
+    let mut data = Vec::new();
+    let total = samples.pow(domain.len() as u32);
+    for i in 0..total {
+        let mut rest = i;
+        let input: Vec<f32> = domain
+            .iter()
+            .map(|d| {
+                let step = rest % samples;
+                rest /= samples;
+                interpolate(step as f32, (0., samples as f32 - 1.), *d)
+            })
+            .collect();
+        let output = function(&input);
+        for (o, r) in range.iter().enumerate() {
+            let v = clip(output.get(o).copied().unwrap_or(r.0), *r);
+            data.push((interpolate(v, *r, (0., 255.))).round() as u8);
+        }
+    }
+    let flatten = |values: &[(f32, f32)]| {
+        values
+            .iter()
+            .flat_map(|(a, b)| [Object::Real(*a), Object::Real(*b)])
+            .collect::<Vec<_>>()
+    };
+    let mut dict = Dictionary::new();
+    dict.set("FunctionType", 0);
+    dict.set("Domain", flatten(domain));
+    dict.set("Range", flatten(range));
+    dict.set(
+        "Size",
+        domain
+            .iter()
+            .map(|_| Object::from(samples as i64))
+            .collect::<Vec<_>>(),
+    );
+    dict.set("BitsPerSample", 8);
+    Stream::new(dict, data)
+}
+
+fn parse_program(data: &[u8]) -> Option<Vec<Token>> {
+    let text = String::from_utf8_lossy(data);
+    let spaced = text.replace('{', " { ").replace('}', " } ");
+    let mut words = spaced.split_whitespace();
+    // the program is enclosed in braces
+    if words.next()? != "{" {
+        return None;
+    }
+    parse_block(&mut words, 0)
+}
+
+// Deeper nesting than real programs use is refused rather than recursed into.
+const MAX_NESTING: usize = 100;
+
+fn parse_block<'a, I: Iterator<Item = &'a str>>(words: &mut I, depth: usize) -> Option<Vec<Token>> {
+    if depth >= MAX_NESTING {
+        return None;
+    }
+    let mut tokens = Vec::new();
+    loop {
+        match words.next()? {
+            "{" => tokens.push(Token::Block(parse_block(words, depth + 1)?)),
+            "}" => return Some(tokens),
+            word => tokens.push(match word.parse::<f64>() {
+                Ok(number) => Token::Number(number),
+                Err(_) => Token::Operator(word.to_string()),
+            }),
+        }
+    }
+}
+
+fn execute(program: &[Token], stack: &mut Vec<Value>) {
+    let mut blocks: Vec<&Vec<Token>> = Vec::new();
+    for token in program {
+        match token {
+            Token::Number(n) => stack.push(Value::Number(*n)),
+            Token::Block(block) => blocks.push(block),
+            Token::Operator(op) => match op.as_str() {
+                "if" => {
+                    let block = blocks.pop();
+                    if let (Some(Value::Bool(true)), Some(block)) = (stack.pop(), block) {
+                        execute(block, stack);
+                    }
+                }
+                "ifelse" => {
+                    let otherwise = blocks.pop();
+                    let then = blocks.pop();
+                    if let (Some(Value::Bool(condition)), Some(then), Some(otherwise)) =
+                        (stack.pop(), then, otherwise)
+                    {
+                        execute(if condition { then } else { otherwise }, stack);
+                    }
+                }
+                op => operate(op, stack),
+            },
+        }
+    }
+}
+
+fn operate(op: &str, stack: &mut Vec<Value>) {
+    let number = |stack: &mut Vec<Value>| match stack.pop() {
+        Some(Value::Number(n)) => n,
+        Some(Value::Bool(b)) => b as i64 as f64,
+        None => 0.,
+    };
+    let push = |stack: &mut Vec<Value>, n: f64| stack.push(Value::Number(n));
+    match op {
+        "abs" | "ceiling" | "cos" | "cvi" | "cvr" | "floor" | "ln" | "log" | "neg" | "round"
+        | "sin" | "sqrt" | "truncate" => {
+            let a = number(stack);
+            let result = match op {
+                "abs" => a.abs(),
+                "ceiling" => a.ceil(),
+                "cos" => a.to_radians().cos(),
+                "cvi" | "truncate" => a.trunc(),
+                "floor" => a.floor(),
+                "ln" => a.ln(),
+                "log" => a.log10(),
+                "neg" => -a,
+                "round" => (a + 0.5).floor(),
+                "sin" => a.to_radians().sin(),
+                "sqrt" => a.sqrt(),
+                _ => a,
+            };
+            push(stack, result);
+        }
+        "add" | "atan" | "div" | "exp" | "idiv" | "mod" | "mul" | "sub" | "bitshift" => {
+            let b = number(stack);
+            let a = number(stack);
+            let result = match op {
+                "add" => a + b,
+                "atan" => {
+                    let angle = a.atan2(b).to_degrees();
+                    if angle < 0. {
+                        angle + 360.
+                    } else {
+                        angle
+                    }
+                }
+                "div" => a / b,
+                "exp" => a.powf(b),
+                "idiv" => (a as i64).checked_div(b as i64).unwrap_or(0) as f64,
+                "mod" => (a as i64).checked_rem(b as i64).unwrap_or(0) as f64,
+                "mul" => a * b,
+                "sub" => a - b,
+                // bits shifted out of the 64 are lost
+                _ => {
+                    let shift = b.abs().min(64.) as u32;
+                    if b >= 0. {
+                        (a as i64).checked_shl(shift).unwrap_or(0) as f64
+                    } else {
+                        (a as i64).checked_shr(shift).unwrap_or((a as i64) >> 63) as f64
+                    }
+                }
+            };
+            push(stack, result);
+        }
+        "eq" | "ne" | "gt" | "ge" | "lt" | "le" => {
+            let b = stack.pop();
+            let a = stack.pop();
+            let result = match (a, b) {
+                (Some(Value::Number(a)), Some(Value::Number(b))) => match op {
+                    "eq" => a == b,
+                    "ne" => a != b,
+                    "gt" => a > b,
+                    "ge" => a >= b,
+                    "lt" => a < b,
+                    _ => a <= b,
+                },
+                (a, b) => (a == b) == (op == "eq"),
+            };
+            stack.push(Value::Bool(result));
+        }
+        "and" | "or" | "xor" => match (stack.pop(), stack.pop()) {
+            (Some(Value::Bool(b)), Some(Value::Bool(a))) => stack.push(Value::Bool(match op {
+                "and" => a && b,
+                "or" => a || b,
+                _ => a ^ b,
+            })),
+            (Some(b), Some(a)) => {
+                let (a, b) = (
+                    match a {
+                        Value::Number(n) => n as i64,
+                        Value::Bool(b) => b as i64,
+                    },
+                    match b {
+                        Value::Number(n) => n as i64,
+                        Value::Bool(b) => b as i64,
+                    },
+                );
+                push(
+                    stack,
+                    match op {
+                        "and" => a & b,
+                        "or" => a | b,
+                        _ => a ^ b,
+                    } as f64,
+                );
+            }
+            _ => (),
+        },
+        "not" => match stack.pop() {
+            Some(Value::Bool(b)) => stack.push(Value::Bool(!b)),
+            Some(Value::Number(n)) => push(stack, !(n as i64) as f64),
+            None => (),
+        },
+        "true" => stack.push(Value::Bool(true)),
+        "false" => stack.push(Value::Bool(false)),
+        "dup" => {
+            if let Some(top) = stack.last().copied() {
+                stack.push(top);
+            }
+        }
+        "exch" => {
+            let len = stack.len();
+            if len >= 2 {
+                stack.swap(len - 1, len - 2);
+            }
+        }
+        "pop" => {
+            stack.pop();
+        }
+        "copy" => {
+            let n = number(stack) as usize;
+            let start = stack.len().saturating_sub(n);
+            let copied: Vec<Value> = stack[start..].to_vec();
+            stack.extend(copied);
+        }
+        "index" => {
+            let n = number(stack) as usize;
+            if n < stack.len() {
+                stack.push(stack[stack.len() - 1 - n]);
+            }
+        }
+        "roll" => {
+            let j = number(stack) as i64;
+            let n = number(stack) as usize;
+            if n > 0 && n <= stack.len() {
+                let start = stack.len() - n;
+                let shift = j.rem_euclid(n as i64) as usize;
+                stack[start..].rotate_right(shift);
+            }
+        }
+        // unknown operators leave the stack untouched
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn function(object: Object) -> Option<Function> {
+        let mut doc = Document::with_version("1.5");
+        let id = doc.add_object(object);
+        Function::from_object(&doc, &Object::Reference(id))
+    }
+
+    fn sampled(dict: Dictionary, data: Vec<u8>) -> Option<Function> {
+        function(Object::Stream(Stream::new(dict, data)))
+    }
+
+    #[test]
+    fn sampled_interpolates() {
+        let f = sampled(
+            dictionary! {
+                "FunctionType" => 0,
+                "Domain" => vec![0.into(), 1.into()],
+                "Range" => vec![0.into(), 1.into()],
+                "Size" => vec![2.into()],
+                "BitsPerSample" => 8,
+            },
+            vec![0, 255],
+        )
+        .unwrap();
+        assert_eq!(f.evaluate(&[0.5]), vec![0.5]);
+        // inputs are clipped to the domain
+        assert_eq!(f.evaluate(&[2.]), vec![1.]);
+        assert_eq!(f.evaluate(&[]), vec![0.]);
+    }
+
+    #[test]
+    fn sampled_rejects_inconsistent_dictionaries() {
+        let base = || {
+            dictionary! {
+                "FunctionType" => 0,
+                "Domain" => vec![0.into(), 1.into()],
+                "Range" => vec![0.into(), 1.into()],
+                "Size" => vec![2.into()],
+                "BitsPerSample" => 8,
+            }
+        };
+        let with = |key: &str, value: Object| {
+            let mut dict = base();
+            dict.set(key, value);
+            dict
+        };
+        assert!(sampled(with("Size", vec![0.into()].into()), vec![0, 255]).is_none());
+        assert!(sampled(with("Size", vec![2.into(), 2.into()].into()), vec![0; 4]).is_none());
+        assert!(sampled(with("Encode", vec![].into()), vec![0, 255]).is_none());
+        assert!(sampled(with("Decode", vec![0.into()].into()), vec![0, 255]).is_none());
+        assert!(sampled(with("BitsPerSample", 64.into()), vec![0; 16]).is_none());
+        assert!(sampled(with("BitsPerSample", 0.into()), vec![0, 255]).is_none());
+        // too few samples
+        assert!(sampled(base(), vec![0]).is_none());
+        assert!(sampled(with("BitsPerSample", 32.into()), vec![0xff; 8]).is_some());
+    }
+
+    #[test]
+    fn exponential_and_stitching() {
+        let exponential = |c0: f32, c1: f32| {
+            Object::Dictionary(dictionary! {
+                "FunctionType" => 2,
+                "Domain" => vec![0.into(), 1.into()],
+                "C0" => vec![c0.into()],
+                "C1" => vec![c1.into()],
+                "N" => 1,
+            })
+        };
+        let f = function(exponential(0., 1.)).unwrap();
+        assert_eq!(f.evaluate(&[0.25]), vec![0.25]);
+        let f = function(Object::Dictionary(dictionary! {
+            "FunctionType" => 3,
+            "Domain" => vec![0.into(), 1.into()],
+            "Functions" => vec![exponential(0., 1.), exponential(1., 0.)],
+            "Bounds" => vec![0.5.into()],
+            "Encode" => vec![0.into(), 1.into(), 0.into(), 1.into()],
+        }))
+        .unwrap();
+        assert_eq!(f.evaluate(&[0.25]), vec![0.5]);
+        assert_eq!(f.evaluate(&[0.75]), vec![0.5]);
+    }
+
+    fn calculator(program: &str, inputs: usize, outputs: usize) -> Function {
+        let pairs = |n: usize| -> Vec<Object> {
+            (0..n)
+                .flat_map(|_| [Object::from(-1000), Object::from(1000)])
+                .collect()
+        };
+        sampled_calculator(pairs(inputs), pairs(outputs), program)
+    }
+
+    fn sampled_calculator(domain: Vec<Object>, range: Vec<Object>, program: &str) -> Function {
+        function(Object::Stream(Stream::new(
+            dictionary! {
+                "FunctionType" => 4,
+                "Domain" => domain,
+                "Range" => range,
+            },
+            program.as_bytes().to_vec(),
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn calculator_operators() {
+        assert_eq!(
+            calculator("{ 2 mul 1 add }", 1, 1).evaluate(&[3.]),
+            vec![7.]
+        );
+        assert_eq!(
+            calculator("{ exch sub }", 2, 1).evaluate(&[1., 4.]),
+            vec![3.]
+        );
+        assert_eq!(
+            calculator("{ 0 gt { 1 } { 2 } ifelse }", 1, 1).evaluate(&[-1.]),
+            vec![2.]
+        );
+        assert_eq!(calculator("{ 0 idiv }", 1, 1).evaluate(&[5.]), vec![0.]);
+        // the range clips the result
+        assert_eq!(
+            calculator("{ 1000 mul }", 1, 1).evaluate(&[5.]),
+            vec![1000.]
+        );
+    }
+
+    #[test]
+    fn calculator_shifts_are_clamped() {
+        assert_eq!(
+            calculator("{ 2 bitshift }", 1, 1).evaluate(&[3.]),
+            vec![12.]
+        );
+        assert_eq!(
+            calculator("{ -1 bitshift }", 1, 1).evaluate(&[6.]),
+            vec![3.]
+        );
+        assert_eq!(
+            calculator("{ 64 bitshift }", 1, 1).evaluate(&[1.]),
+            vec![0.]
+        );
+        assert_eq!(
+            calculator("{ 1e9 bitshift }", 1, 1).evaluate(&[1.]),
+            vec![0.]
+        );
+        assert_eq!(
+            calculator("{ -100 bitshift }", 1, 1).evaluate(&[-8.]),
+            vec![-1.]
+        );
+    }
+
+    #[test]
+    fn calculator_stack_underflow() {
+        // an empty stack leaves the low end of the range
+        assert_eq!(
+            calculator("{ pop pop add roll index }", 1, 1).evaluate(&[1.]),
+            vec![-1000.]
+        );
+        assert!(function(Object::Stream(Stream::new(
+            dictionary! {
+                "FunctionType" => 4,
+                "Domain" => vec![0.into(), 1.into()],
+                "Range" => vec![0.into(), 1.into()],
+            },
+            b"{ 1 add".to_vec(),
+        )))
+        .is_none());
+    }
+
+    #[test]
+    fn calculator_nesting_is_bounded() {
+        let nested = |depth: usize| {
+            let program = format!("{{ {}{} }}", "{ ".repeat(depth), "} ".repeat(depth));
+            function(Object::Stream(Stream::new(
+                dictionary! {
+                    "FunctionType" => 4,
+                    "Domain" => vec![0.into(), 1.into()],
+                    "Range" => vec![0.into(), 1.into()],
+                },
+                program.into_bytes(),
+            )))
+        };
+        assert!(nested(10).is_some());
+        assert!(nested(100_000).is_none());
+    }
+}
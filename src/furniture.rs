@@ -0,0 +1,307 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+
+use kurbo::Rect;
+use lopdf::content::Operation;
+use lopdf::{dictionary, Object};
+
+use crate::{page_resource, painted_bbox, removed_text, xobject_bboxes, PdfModifier};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FurnitureKind {
+    Header,
+    Footer,
+    Watermark,
+}
+
+impl FurnitureKind {
+    // the artifact subtype of pagination artifacts
+    pub fn artifact_subtype(&self) -> &'static str {
+        match self {
+            Self::Header => "Header",
+            Self::Footer => "Footer",
+            Self::Watermark => "Watermark",
+        }
+    }
+}
+
+impl Display for FurnitureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Header => write!(f, "header"),
+            Self::Footer => write!(f, "footer"),
+            Self::Watermark => write!(f, "watermark"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Content {
+    // with each run of digits replaced so that page numbers match
+    Text(String),
+    // a hash of the XObject's stream
+    XObject(u64),
+}
+
+// Text or an XObject found at about the same place on several pages.
+#[derive(Debug, Clone)]
+pub struct Furniture {
+    pub kind: FurnitureKind,
+    // the text, or the name of the XObject, as on the first page
+    pub label: String,
+    pub bbox: Rect,
+    content: Content,
+    // the operations painting it on each page, by page index
+    occurrences: Vec<(usize, usize)>,
+}
+
+impl Furniture {
+    pub fn pages(&self) -> usize {
+        self.occurrences
+            .iter()
+            .map(|(page, _)| page)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FurnitureReport {
+    pub pages: usize,
+    pub furniture: Vec<Furniture>,
+}
+
+impl Display for FurnitureReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for furniture in &self.furniture {
+            writeln!(
+                f,
+                "{}: {:?} at {:.0},{:.0},{:.0},{:.0} on {} of {} pages",
+                furniture.kind,
+                furniture.label,
+                furniture.bbox.x0,
+                furniture.bbox.y0,
+                furniture.bbox.x1,
+                furniture.bbox.y1,
+                furniture.pages(),
+                self.pages
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FurnitureReport {
+    // The operations of the furniture of the kinds on a page, by operation
+    // index.
+    fn operations(&self, page: usize, kinds: &[FurnitureKind]) -> Vec<(usize, FurnitureKind)> {
+        self.furniture
+            .iter()
+            .filter(|f| kinds.contains(&f.kind))
+            .flat_map(|f| {
+                f.occurrences
+                    .iter()
+                    .filter(|(p, _)| *p == page)
+                    .map(|(_, op)| (*op, f.kind))
+            })
+            .collect()
+    }
+    // Removes the furniture of the kinds from a page, given by its index
+    // among the pages the report was made for.
+    pub fn remove(
+        &self,
+        modifier: &mut PdfModifier,
+        page_id: (u32, u16),
+        page: usize,
+        kinds: &[FurnitureKind],
+    ) {
+        let operations = self.operations(page, kinds);
+        if operations.is_empty() {
+            return;
+        }
+        modifier.apply(page_id, &mut |operation, state| {
+            if !operations.iter().any(|(op, _)| *op == state.id) {
+                return vec![operation];
+            }
+            match operation.operator.as_ref() {
+                "Do" => vec![],
+                // the text position moves on as it would have
+                _ => removed_text(operation, state),
+            }
+        });
+    }
+    // Wraps the furniture of the kinds on a page in `/Artifact` marked
+    // content so that assistive technology skips it.
+    pub fn mark_artifacts(
+        &self,
+        modifier: &mut PdfModifier,
+        page_id: (u32, u16),
+        page: usize,
+        kinds: &[FurnitureKind],
+    ) {
+        let operations = self.operations(page, kinds);
+        if operations.is_empty() {
+            return;
+        }
+        modifier.apply(page_id, &mut |operation, state| match operations
+            .iter()
+            .find(|(op, _)| *op == state.id)
+        {
+            Some((_, kind)) => vec![
+                Operation::new(
+                    "BDC",
+                    vec![
+                        Object::Name(b"Artifact".to_vec()),
+                        Object::Dictionary(dictionary! {
+                            "Type" => "Pagination",
+                            "Subtype" => kind.artifact_subtype(),
+                        }),
+                    ],
+                ),
+                operation,
+                Operation::new("EMC", vec![]),
+            ],
+            None => vec![operation],
+        });
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FurnitureDetector {
+    // share of the pages something must be on
+    pub min_ratio: f64,
+    // how far in points positions may differ between pages
+    pub tolerance: f64,
+    // share of the page height at the top and bottom taken as header and
+    // footer space
+    pub margin: f64,
+}
+
+impl Default for FurnitureDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FurnitureDetector {
+    pub fn new() -> Self {
+        Self {
+            min_ratio: 0.6,
+            tolerance: 4.,
+            margin: 0.15,
+        }
+    }
+    // Positions match when they are on the same level and aligned on the
+    // left, the centre or the right, as page numbers of different widths
+    // are.
+    fn same_place(&self, a: Rect, b: Rect) -> bool {
+        let close = |x: f64, y: f64| (x - y).abs() <= self.tolerance;
+        close(a.center().y, b.center().y)
+            && (close(a.x0, b.x0) || close(a.center().x, b.center().x) || close(a.x1, b.x1))
+    }
+    pub fn detect(&self, modifier: &mut PdfModifier) -> FurnitureReport {
+        let pages = modifier.pages();
+        let mut clusters: Vec<Furniture> = Vec::new();
+        let mut page_boxes = Vec::new();
+        for (page, page_id) in pages.iter().enumerate() {
+            let page_box = modifier
+                .visible_box(*page_id)
+                .unwrap_or(Rect::new(0., 0., 612., 792.));
+            page_boxes.push(page_box);
+            let bboxes = xobject_bboxes(modifier, *page_id);
+            let xobjects: Vec<(Vec<u8>, u64)> =
+                page_resource(modifier.document(), *page_id, b"XObject")
+                    .map(|dict| {
+                        dict.iter()
+                            .filter_map(|(name, object)| {
+                                let (_, object) = modifier.document().dereference(object).ok()?;
+                                let stream = object.as_stream().ok()?;
+                                let mut hasher = DefaultHasher::new();
+                                stream.content.hash(&mut hasher);
+                                Some((name.clone(), hasher.finish()))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+            let mut found: Vec<(usize, String, Content, Rect)> = Vec::new();
+            modifier.for_each(
+                *page_id,
+                &mut |operation, state| match operation.operator.as_ref() {
+                    "Tj" | "TJ" | "'" | "\"" => {
+                        let glyphs = state.shown_glyphs(&operation);
+                        let text: String = glyphs.iter().map(|g| g.text.as_str()).collect();
+                        let bbox = glyphs.iter().map(|g| g.bbox).reduce(|a, b| a.union(b));
+                        if let (false, Some(bbox)) = (text.trim().is_empty(), bbox) {
+                            let mut key = String::new();
+                            for c in text.trim().chars() {
+                                match c {
+                                    '0'..='9' if key.ends_with('#') => (),
+                                    '0'..='9' => key.push('#'),
+                                    c => key.push(c),
+                                }
+                            }
+                            found.push((
+                                state.id,
+                                text.trim().to_string(),
+                                Content::Text(key),
+                                bbox,
+                            ));
+                        }
+                    }
+                    "Do" => {
+                        let name = operation.operands.first().and_then(|o| o.as_name().ok());
+                        let hash = name.and_then(|name| xobjects.iter().find(|(n, _)| n == name));
+                        let bbox = painted_bbox(&operation, state, &bboxes, page_box);
+                        if let (Some((name, hash)), Some(bbox)) = (hash, bbox) {
+                            found.push((
+                                state.id,
+                                String::from_utf8_lossy(name).into_owned(),
+                                Content::XObject(*hash),
+                                bbox,
+                            ));
+                        }
+                    }
+                    _ => (),
+                },
+            );
+            for (op, label, content, bbox) in found {
+                match clusters
+                    .iter_mut()
+                    .find(|c| c.content == content && self.same_place(c.bbox, bbox))
+                {
+                    Some(cluster) => cluster.occurrences.push((page, op)),
+                    None => clusters.push(Furniture {
+                        kind: FurnitureKind::Watermark,
+                        label,
+                        bbox,
+                        content,
+                        occurrences: vec![(page, op)],
+                    }),
+                }
+            }
+        }
+        let required = ((self.min_ratio * pages.len() as f64).ceil() as usize).max(2);
+        let furniture = clusters
+            .into_iter()
+            .filter(|c| c.pages() >= required)
+            .map(|mut c| {
+                let page_box = page_boxes[c.occurrences[0].0];
+                let margin = self.margin * page_box.height();
+                c.kind = if c.bbox.y0 >= page_box.y1 - margin {
+                    FurnitureKind::Header
+                } else if c.bbox.y1 <= page_box.y0 + margin {
+                    FurnitureKind::Footer
+                } else {
+                    FurnitureKind::Watermark
+                };
+                c
+            })
+            .collect();
+        FurnitureReport {
+            pages: pages.len(),
+            furniture,
+        }
+    }
+}
@@ -0,0 +1,705 @@
+use std::collections::HashMap;
+
+use kurbo::{Affine, BezPath, Point, Vec2};
+use lopdf::{Dictionary, Document, Object};
+
+use crate::{glyph_name, standard_glyph_name, stream_content, Font};
+
+// Collects the outline of a glyph from ttf-parser.
+struct OutlinePath(BezPath);
+
+impl ttf_parser::OutlineBuilder for OutlinePath {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to((f64::from(x), f64::from(y)));
+    }
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to((f64::from(x), f64::from(y)));
+    }
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0
+            .quad_to((f64::from(x1), f64::from(y1)), (f64::from(x), f64::from(y)));
+    }
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.curve_to(
+            (f64::from(x1), f64::from(y1)),
+            (f64::from(x2), f64::from(y2)),
+            (f64::from(x), f64::from(y)),
+        );
+    }
+    fn close(&mut self) {
+        self.0.close_path();
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Type1Program {
+    matrix: Affine,
+    // the built-in encoding
+    encoding: HashMap<u8, String>,
+    char_strings: HashMap<String, Vec<u8>>,
+    subrs: Vec<Vec<u8>>,
+}
+
+// eexec and charstring encryption
+fn decrypt(data: &[u8], key: u16, skip: usize) -> Vec<u8> {
+    let mut r = key;
+    let mut plain = Vec::with_capacity(data.len());
+    for c in data {
+        plain.push(c ^ (r >> 8) as u8);
+        r = u16::from(*c)
+            .wrapping_add(r)
+            .wrapping_mul(52845)
+            .wrapping_add(22719);
+    }
+    plain.into_iter().skip(skip).collect()
+}
+
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|w| w == pattern)
+}
+
+// Whitespace separated tokens of PostScript, with binary data read by length.
+struct Tokens<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn next(&mut self) -> Option<&'a [u8]> {
+        while self.data.get(self.position)?.is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start = self.position;
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+        Some(&self.data[start..self.position])
+    }
+    fn number(&mut self) -> Option<usize> {
+        std::str::from_utf8(self.next()?).ok()?.parse().ok()
+    }
+    // `length RD binary`, with a single space before the binary data
+    fn binary(&mut self) -> Option<&'a [u8]> {
+        let length = self.number()?;
+        self.next()?;
+        let start = self.position + 1;
+        let data = self.data.get(start..start + length)?;
+        self.position = start + length;
+        Some(data)
+    }
+}
+
+impl Type1Program {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let start = find(data, b"eexec")? + 5;
+        let clear = &data[..start];
+        let mut encrypted = &data[start..];
+        while encrypted.first()?.is_ascii_whitespace() {
+            encrypted = &encrypted[1..];
+        }
+        // the encrypted part may be written in hexadecimal
+        let hex: Vec<u8>;
+        if encrypted.len() >= 4 && encrypted[..4].iter().all(u8::is_ascii_hexdigit) {
+            let digits: Vec<u8> = encrypted
+                .iter()
+                .take_while(|b| b.is_ascii_hexdigit() || b.is_ascii_whitespace())
+                .filter(|b| b.is_ascii_hexdigit())
+                .copied()
+                .collect();
+            hex = digits
+                .chunks_exact(2)
+                .filter_map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+                .collect();
+            encrypted = &hex;
+        }
+        let private = decrypt(encrypted, 55665, 4);
+
+        let mut matrix = Affine::scale(0.001);
+        if let Some(position) = find(clear, b"/FontMatrix") {
+            let values: Vec<f64> = clear[position + 11..]
+                .split(|b| b.is_ascii_whitespace() || b"[]{}".contains(b))
+                .filter(|t| !t.is_empty())
+                .take(6)
+                .filter_map(|t| std::str::from_utf8(t).ok()?.parse().ok())
+                .collect();
+            if let [a, b, c, d, e, f] = values[..] {
+                matrix = Affine::new([a, b, c, d, e, f]);
+            }
+        }
+        let mut encoding = HashMap::new();
+        if find(clear, b"/Encoding StandardEncoding").is_some() {
+            for code in 0..=255 {
+                if let Some(name) = standard_glyph_name(code) {
+                    encoding.insert(code, name);
+                }
+            }
+        } else {
+            // dup code /name put
+            let mut tokens = Tokens {
+                data: clear,
+                position: 0,
+            };
+            while let Some(token) = tokens.next() {
+                if token != b"dup" {
+                    continue;
+                }
+                let code = tokens.number();
+                let name = tokens.next().and_then(|n| n.strip_prefix(b"/"));
+                if let (Some(code), Some(name)) = (code, name) {
+                    if let Ok(code) = u8::try_from(code) {
+                        encoding.insert(code, String::from_utf8_lossy(name).into_owned());
+                    }
+                }
+            }
+        }
+
+        let len_iv = find(&private, b"/lenIV")
+            .and_then(|position| {
+                Tokens {
+                    data: &private,
+                    position: position + 6,
+                }
+                .next()
+                .and_then(|t| std::str::from_utf8(t).ok()?.parse::<i64>().ok())
+            })
+            .unwrap_or(4);
+        let charstring = |data: &[u8]| match usize::try_from(len_iv) {
+            Ok(skip) => decrypt(data, 4330, skip),
+            // not encrypted
+            Err(_) => data.to_vec(),
+        };
+        let mut subrs = Vec::new();
+        if let Some(position) = find(&private, b"/Subrs") {
+            let mut tokens = Tokens {
+                data: &private,
+                position: position + 6,
+            };
+            let count = tokens.number().unwrap_or(0);
+            subrs = vec![Vec::new(); count];
+            while let Some(token) = tokens.next() {
+                match token {
+                    b"dup" => {
+                        let index = tokens.number()?;
+                        let data = tokens.binary()?;
+                        if let Some(subr) = subrs.get_mut(index) {
+                            *subr = charstring(data);
+                        }
+                    }
+                    b"array" | b"NP" | b"|" | b"noaccess" | b"put" => (),
+                    _ => break,
+                }
+            }
+        }
+        let mut char_strings = HashMap::new();
+        let position = find(&private, b"/CharStrings")?;
+        let mut tokens = Tokens {
+            data: &private,
+            position: position + 12,
+        };
+        while let Some(token) = tokens.next() {
+            match token {
+                b"end" => break,
+                name if name.len() > 1 && name[0] == b'/' => {
+                    let data = tokens.binary()?;
+                    char_strings.insert(
+                        String::from_utf8_lossy(&name[1..]).into_owned(),
+                        charstring(data),
+                    );
+                }
+                _ => (),
+            }
+        }
+        Some(Self {
+            matrix,
+            encoding,
+            char_strings,
+            subrs,
+        })
+    }
+    fn outline(&self, name: &str) -> Option<BezPath> {
+        let mut interpreter = Type1Interpreter {
+            program: self,
+            path: BezPath::new(),
+            stack: Vec::new(),
+            results: Vec::new(),
+            point: Point::ZERO,
+            offset: Vec2::ZERO,
+            flex: None,
+            open: false,
+        };
+        interpreter.run(self.char_strings.get(name)?, 0);
+        Some(interpreter.path)
+    }
+}
+
+// Runs Type 1 charstrings, leaving hints aside.
+struct Type1Interpreter<'a> {
+    program: &'a Type1Program,
+    path: BezPath,
+    stack: Vec<f64>,
+    // results of other subroutines, taken with `pop`
+    results: Vec<f64>,
+    point: Point,
+    // where the accent of an accented character goes
+    offset: Vec2,
+    // the points of a flex being collected
+    flex: Option<Vec<Point>>,
+    open: bool,
+}
+
+impl Type1Interpreter<'_> {
+    fn move_by(&mut self, delta: Vec2) {
+        self.point += delta;
+        match &mut self.flex {
+            Some(points) => points.push(self.point),
+            None => {
+                self.path.move_to(self.point);
+                self.open = true;
+            }
+        }
+    }
+    fn line_by(&mut self, delta: Vec2) {
+        if !self.open {
+            self.path.move_to(self.point);
+            self.open = true;
+        }
+        self.point += delta;
+        self.path.line_to(self.point);
+    }
+    fn curve_by(&mut self, d1: Vec2, d2: Vec2, d3: Vec2) {
+        if !self.open {
+            self.path.move_to(self.point);
+            self.open = true;
+        }
+        let p1 = self.point + d1;
+        let p2 = p1 + d2;
+        self.point = p2 + d3;
+        self.path.curve_to(p1, p2, self.point);
+    }
+    // Accented characters draw two glyphs of StandardEncoding.
+    fn seac(&mut self, base: f64, accent: f64, offset: Vec2, depth: usize) {
+        let glyph = |code: f64| {
+            standard_glyph_name(code as u8).and_then(|name| self.program.char_strings.get(&name))
+        };
+        if let (Some(base), Some(accent)) = (glyph(base), glyph(accent)) {
+            self.stack.clear();
+            self.run(base, depth + 1);
+            self.stack.clear();
+            self.offset = offset;
+            self.run(accent, depth + 1);
+        }
+    }
+    // Whether `endchar` was reached.
+    fn run(&mut self, code: &[u8], depth: usize) -> bool {
+        if depth > 10 {
+            return true;
+        }
+        let mut i = 0;
+        while i < code.len() {
+            let v = code[i];
+            i += 1;
+            let operand = match v {
+                32..=246 => Some(f64::from(v) - 139.),
+                247..=250 => {
+                    let w = f64::from(*code.get(i).unwrap_or(&0));
+                    i += 1;
+                    Some((f64::from(v) - 247.) * 256. + w + 108.)
+                }
+                251..=254 => {
+                    let w = f64::from(*code.get(i).unwrap_or(&0));
+                    i += 1;
+                    Some(-(f64::from(v) - 251.) * 256. - w - 108.)
+                }
+                255 => {
+                    let bytes = code.get(i..i + 4).unwrap_or(&[0; 4]);
+                    i += 4;
+                    Some(f64::from(i32::from_be_bytes([
+                        bytes[0], bytes[1], bytes[2], bytes[3],
+                    ])))
+                }
+                _ => None,
+            };
+            if let Some(operand) = operand {
+                self.stack.push(operand);
+                continue;
+            }
+            let operator = if v == 12 {
+                i += 1;
+                1200 + u16::from(*code.get(i - 1).unwrap_or(&0))
+            } else {
+                u16::from(v)
+            };
+            let s = std::mem::take(&mut self.stack);
+            let arg = |n: usize| s.get(n).copied().unwrap_or(0.);
+            match operator {
+                // hsbw
+                13 => self.point = Point::new(arg(0), 0.) + self.offset,
+                // sbw
+                1207 => self.point = Point::new(arg(0), arg(1)) + self.offset,
+                21 => self.move_by(Vec2::new(arg(0), arg(1))),
+                22 => self.move_by(Vec2::new(arg(0), 0.)),
+                4 => self.move_by(Vec2::new(0., arg(0))),
+                5 => self.line_by(Vec2::new(arg(0), arg(1))),
+                6 => self.line_by(Vec2::new(arg(0), 0.)),
+                7 => self.line_by(Vec2::new(0., arg(0))),
+                8 => self.curve_by(
+                    Vec2::new(arg(0), arg(1)),
+                    Vec2::new(arg(2), arg(3)),
+                    Vec2::new(arg(4), arg(5)),
+                ),
+                30 => self.curve_by(
+                    Vec2::new(0., arg(0)),
+                    Vec2::new(arg(1), arg(2)),
+                    Vec2::new(arg(3), 0.),
+                ),
+                31 => self.curve_by(
+                    Vec2::new(arg(0), 0.),
+                    Vec2::new(arg(1), arg(2)),
+                    Vec2::new(0., arg(3)),
+                ),
+                9 if self.open => {
+                    self.path.close_path();
+                    self.open = false;
+                }
+                10 => {
+                    let mut s = s;
+                    let index = s.pop().unwrap_or(-1.);
+                    self.stack = s;
+                    let subr = usize::try_from(index as i64)
+                        .ok()
+                        .and_then(|index| self.program.subrs.get(index));
+                    if let Some(subr) = subr {
+                        if self.run(subr, depth + 1) {
+                            return true;
+                        }
+                    }
+                }
+                11 => {
+                    self.stack = s;
+                    return false;
+                }
+                14 => return true,
+                1206 => {
+                    self.seac(arg(3), arg(4), Vec2::new(arg(1) - arg(0), arg(2)), depth);
+                    return true;
+                }
+                // div
+                1212 => {
+                    let mut s = s;
+                    let b = s.pop().unwrap_or(1.);
+                    let a = s.pop().unwrap_or(0.);
+                    s.push(if b != 0. { a / b } else { 0. });
+                    self.stack = s;
+                }
+                // callothersubr
+                1216 => {
+                    let mut s = s;
+                    let number = s.pop().unwrap_or(-1.) as i64;
+                    let count = (s.pop().unwrap_or(0.).max(0.) as usize).min(s.len());
+                    let args = s.split_off(s.len() - count);
+                    self.stack = s;
+                    match number {
+                        // flex: seven points, the first being a reference
+                        0 => {
+                            let points = self.flex.take().unwrap_or_default();
+                            if let [_, p1, p2, p3, p4, p5, p6, ..] = points[..] {
+                                if !self.open {
+                                    self.path.move_to(points[0]);
+                                    self.open = true;
+                                }
+                                self.path.curve_to(p1, p2, p3);
+                                self.path.curve_to(p4, p5, p6);
+                                self.point = p6;
+                            }
+                            self.results = vec![self.point.y, self.point.x];
+                        }
+                        1 => self.flex = Some(Vec::new()),
+                        2 => (),
+                        _ => self.results = args.into_iter().rev().collect(),
+                    }
+                }
+                // pop
+                1217 => {
+                    let mut s = s;
+                    s.push(self.results.pop().unwrap_or(0.));
+                    self.stack = s;
+                }
+                // setcurrentpoint
+                1233 => self.point = Point::new(arg(0), arg(1)),
+                // hints and dotsection
+                _ => (),
+            }
+        }
+        false
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Program {
+    // TrueType or OpenType
+    Face(Vec<u8>),
+    // bare CFF
+    Cff(Vec<u8>),
+    Type1(Type1Program),
+}
+
+// The outlines of the glyphs of a font, read from the font program embedded
+// in it.
+#[derive(Debug, Clone)]
+pub struct GlyphOutlines {
+    program: Program,
+    font: Font,
+    composite: bool,
+    symbolic: bool,
+    // whether the font dictionary gives an encoding, over the built-in one
+    encoded: bool,
+    // glyph names of `/Differences`
+    differences: HashMap<u32, String>,
+    // glyph ids by CID, when not the same
+    cid_to_gid: Option<Vec<u16>>,
+}
+
+impl GlyphOutlines {
+    // Fails with the reason when the font has no outlines to read.
+    pub fn from_dict(doc: &Document, dict: &Dictionary) -> Result<Self, String> {
+        let name = |dict: &Dictionary, key: &[u8]| {
+            dict.get(key)
+                .and_then(|o| doc.dereference(o))
+                .and_then(|(_, o)| o.as_name())
+                .map(|n| n.to_vec())
+                .unwrap_or_default()
+        };
+        let subtype = name(dict, b"Subtype");
+        let composite = subtype == b"Type0";
+        if subtype == b"Type3" {
+            return Err("Type 3 font".to_string());
+        }
+        let font_dict = if composite {
+            let encoding = name(dict, b"Encoding");
+            if encoding != b"Identity-H" {
+                return Err(format!(
+                    "CMap {}",
+                    String::from_utf8_lossy(&encoding).trim()
+                ));
+            }
+            dict.get(b"DescendantFonts")
+                .and_then(|o| doc.dereference(o))
+                .and_then(|(_, o)| o.as_array())
+                .ok()
+                .and_then(|a| a.first())
+                .and_then(|o| doc.dereference(o).ok())
+                .and_then(|(_, o)| o.as_dict().ok())
+                .ok_or("no descendant font")?
+        } else {
+            dict
+        };
+        let descriptor = font_dict
+            .get(b"FontDescriptor")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+            .map_err(|_| "not embedded")?;
+        let file = |key: &[u8]| match descriptor.get(key).and_then(|o| doc.dereference(o)) {
+            Ok((_, Object::Stream(stream))) => stream_content(stream).map(|data| (stream, data)),
+            _ => None,
+        };
+        let program = if let Some((_, data)) = file(b"FontFile2") {
+            ttf_parser::Face::parse(&data, 0).map_err(|e| e.to_string())?;
+            Program::Face(data)
+        } else if let Some((stream, data)) = file(b"FontFile3") {
+            if name(&stream.dict, b"Subtype") == b"OpenType" {
+                ttf_parser::Face::parse(&data, 0).map_err(|e| e.to_string())?;
+                Program::Face(data)
+            } else {
+                ttf_parser::cff::Table::parse(&data).ok_or("invalid CFF font program")?;
+                Program::Cff(data)
+            }
+        } else if let Some((_, data)) = file(b"FontFile") {
+            Program::Type1(Type1Program::parse(&data).ok_or("invalid Type 1 font program")?)
+        } else {
+            return Err("not embedded".to_string());
+        };
+        let symbolic = descriptor
+            .get(b"Flags")
+            .and_then(|o| o.as_i64())
+            .is_ok_and(|flags| flags & 4 != 0);
+        let encoding = dict.get(b"Encoding").and_then(|o| doc.dereference(o)).ok();
+        let encoded = match encoding {
+            Some((_, Object::Name(_))) => true,
+            Some((_, Object::Dictionary(encoding))) => encoding.has(b"BaseEncoding"),
+            _ => false,
+        };
+        let mut differences = HashMap::new();
+        if let Some((_, Object::Dictionary(encoding))) = encoding {
+            if let Ok(array) = encoding.get(b"Differences").and_then(|o| o.as_array()) {
+                let mut code = 0;
+                for entry in array {
+                    match entry {
+                        Object::Integer(i) => code = *i as u32,
+                        Object::Name(name) => {
+                            differences.insert(code, String::from_utf8_lossy(name).into_owned());
+                            code += 1;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        let cid_to_gid = match font_dict
+            .get(b"CIDToGIDMap")
+            .and_then(|o| doc.dereference(o))
+        {
+            Ok((_, Object::Stream(stream))) => stream_content(stream).map(|data| {
+                data.chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect()
+            }),
+            _ => match &program {
+                // CID-keyed CFF maps CIDs with its charset
+                Program::Cff(data) if composite => {
+                    let table = ttf_parser::cff::Table::parse(data);
+                    table
+                        .filter(|t| t.glyph_cid(ttf_parser::GlyphId(1)).is_some())
+                        .map(|t| {
+                            let mut map = Vec::new();
+                            for gid in 0..t.number_of_glyphs() {
+                                let cid =
+                                    usize::from(t.glyph_cid(ttf_parser::GlyphId(gid)).unwrap_or(0));
+                                if map.len() <= cid {
+                                    map.resize(cid + 1, 0);
+                                }
+                                map[cid] = gid;
+                            }
+                            map
+                        })
+                }
+                _ => None,
+            },
+        };
+        Ok(Self {
+            program,
+            font: Font::from_dict(doc, dict),
+            composite,
+            symbolic,
+            encoded,
+            differences,
+            cid_to_gid,
+        })
+    }
+    // Glyph names a simple font may give a code, best first.
+    fn names(&self, code: u32) -> Vec<String> {
+        let mut names = Vec::new();
+        if let Some(name) = self.differences.get(&code) {
+            names.push(name.clone());
+        }
+        if !self.symbolic || self.encoded {
+            let mut chars = self.font.unicode(code).unwrap_or("").chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                names.push(glyph_name(c));
+            }
+        }
+        names
+    }
+    fn cid_gid(&self, cid: u32) -> u16 {
+        match &self.cid_to_gid {
+            Some(map) => map.get(cid as usize).copied().unwrap_or(0),
+            None => cid as u16,
+        }
+    }
+    fn face_gid(&self, face: &ttf_parser::Face, code: u32) -> Option<ttf_parser::GlyphId> {
+        if self.composite {
+            return Some(ttf_parser::GlyphId(self.cid_gid(code)));
+        }
+        let subtables: Vec<ttf_parser::cmap::Subtable> = face
+            .tables()
+            .cmap
+            .map(|cmap| cmap.subtables.into_iter().collect())
+            .unwrap_or_default();
+        let subtable = |platform: ttf_parser::PlatformId, encoding: u16| {
+            subtables
+                .iter()
+                .find(|s| s.platform_id == platform && s.encoding_id == encoding)
+        };
+        // glyph names the post table knows, then Unicode
+        if let Some(gid) = self
+            .names(code)
+            .iter()
+            .find_map(|name| face.glyph_index_by_name(name))
+        {
+            return Some(gid);
+        }
+        if !self.symbolic || self.encoded {
+            let mut chars = self.font.unicode(code).unwrap_or("").chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                if let Some(gid) = face.glyph_index(c) {
+                    return Some(gid);
+                }
+            }
+        }
+        // symbolic fonts map codes themselves, often from U+F000
+        if let Some(symbol) = subtable(ttf_parser::PlatformId::Windows, 0) {
+            for base in [0, 0xf000, 0xf100, 0xf200] {
+                if let Some(gid) = symbol.glyph_index(base + code) {
+                    return Some(gid);
+                }
+            }
+        }
+        subtable(ttf_parser::PlatformId::Macintosh, 0)
+            .and_then(|s| s.glyph_index(code))
+            .or_else(|| {
+                subtables
+                    .is_empty()
+                    .then_some(ttf_parser::GlyphId(code as u16))
+            })
+    }
+    // The outline of the glyph of a code in text space for a font size of
+    // one, None for codes the font has no glyph for.
+    pub fn outline(&self, code: u32) -> Option<BezPath> {
+        let mut path = OutlinePath(BezPath::new());
+        let matrix = match &self.program {
+            Program::Face(data) => {
+                let face = ttf_parser::Face::parse(data, 0).ok()?;
+                // glyph 0 is .notdef, drawn for missing glyphs
+                let gid = self.face_gid(&face, code).filter(|gid| gid.0 != 0)?;
+                face.outline_glyph(gid, &mut path);
+                Affine::scale(1. / f64::from(face.units_per_em()))
+            }
+            Program::Cff(data) => {
+                let table = ttf_parser::cff::Table::parse(data)?;
+                let gid = if self.composite {
+                    Some(ttf_parser::GlyphId(self.cid_gid(code)))
+                } else {
+                    // names are looked up glyph by glyph as the
+                    // predefined charsets have no reverse mapping
+                    let by_name = |name: &str| {
+                        (0..table.number_of_glyphs())
+                            .map(ttf_parser::GlyphId)
+                            .find(|gid| table.glyph_name(*gid) == Some(name))
+                    };
+                    self.names(code)
+                        .iter()
+                        .find_map(|name| by_name(name))
+                        .or_else(|| table.glyph_index(code as u8))
+                        .or_else(|| standard_glyph_name(code as u8).and_then(|n| by_name(&n)))
+                };
+                let gid = gid.filter(|gid| gid.0 != 0)?;
+                let _ = table.outline(gid, &mut path);
+                let m = table.matrix();
+                Affine::new([m.sx, m.ky, m.kx, m.sy, m.tx, m.ty].map(f64::from))
+            }
+            Program::Type1(program) => {
+                path.0 = self
+                    .names(code)
+                    .into_iter()
+                    .chain(program.encoding.get(&(code as u8)).cloned())
+                    .filter(|name| name != ".notdef")
+                    .find_map(|name| program.outline(&name))?;
+                program.matrix
+            }
+        };
+        Some(matrix * path.0)
+    }
+}
@@ -0,0 +1,969 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use lopdf::content::Operation;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::graphics::color::convert::{ColorConverter, ColorFamily};
+use crate::graphics::color::{Color, ColorSpace};
+use crate::{
+    decode_operations, decode_samples, deflate, encode_operations, filter_names, object_to_f32,
+    page_resource, sample_function, stream_content, Function, PdfModifier, State,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct GrayscaleReport {
+    pub operators: usize,
+    pub color_spaces: usize,
+    pub shadings: usize,
+    pub images: usize,
+    // what was left in colour, and why
+    pub skipped: Vec<String>,
+}
+
+impl Display for GrayscaleReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} colour operators, {} colour spaces, {} shadings, {} images converted",
+            self.operators, self.color_spaces, self.shadings, self.images
+        )?;
+        if !self.skipped.is_empty() {
+            write!(f, ", {} kept in colour", self.skipped.len())?;
+        }
+        Ok(())
+    }
+}
+
+fn gray(converter: &ColorConverter, color: Option<Color>) -> f32 {
+    match color.map(|c| converter.convert(&c, ColorFamily::Gray)) {
+        Some(Color::Gray(g)) => g,
+        _ => 0.,
+    }
+}
+
+fn device_gray() -> Object {
+    Object::Name(b"DeviceGray".to_vec())
+}
+
+fn is_device_gray(doc: &Document, object: &Object) -> bool {
+    matches!(
+        doc.dereference(object).map(|(_, o)| o),
+        Ok(Object::Name(name)) if name == b"DeviceGray" || name == b"G"
+    )
+}
+
+struct ImageParams<'a> {
+    width: usize,
+    height: usize,
+    bits: usize,
+    family: ColorFamily,
+    filters: Vec<Vec<u8>>,
+    parms: Option<&'a Dictionary>,
+    decode: Option<Vec<f32>>,
+}
+
+// Converts the samples of an image to 8 bit gray.
+fn gray_pixels(
+    converter: &ColorConverter,
+    content: &[u8],
+    params: &ImageParams,
+) -> Result<Vec<u8>, String> {
+    let samples = decode_samples(
+        content,
+        &params.filters,
+        params.parms,
+        params.bits,
+        params.family.components(),
+    )?;
+    let (data, bits) = (samples.data, samples.bits);
+    // JPEG data tells its own colour family
+    let family = match samples.components {
+        n if n == params.family.components() => params.family,
+        1 => ColorFamily::Gray,
+        3 => ColorFamily::RGB,
+        _ => ColorFamily::CMYK,
+    };
+    if bits != 8 && bits != 16 {
+        return Err(format!("{} bits per component", bits));
+    }
+    let components = family.components();
+    let decode: Vec<(f32, f32)> = match (&params.decode, family) {
+        (Some(decode), _) => decode.chunks_exact(2).map(|c| (c[0], c[1])).collect(),
+        (None, ColorFamily::Lab) => vec![(0., 100.), (-100., 100.), (-100., 100.)],
+        (None, _) => vec![(0., 1.); components],
+    };
+    let bytes = bits / 8;
+    let samples = params.width * params.height;
+    let mut result = Vec::with_capacity(samples);
+    let mut cache: HashMap<&[u8], u8> = HashMap::new();
+    for pixel in data.chunks_exact(components * bytes).take(samples) {
+        if let Some(value) = cache.get(pixel) {
+            result.push(*value);
+            continue;
+        }
+        let values: Vec<f32> = pixel
+            .chunks_exact(bytes)
+            .zip(&decode)
+            .map(|(sample, (min, max))| {
+                let v = match sample {
+                    [v] => *v as f32 / 255.,
+                    [high, low] => (*high as f32 * 256. + *low as f32) / 65535.,
+                    _ => 0.,
+                };
+                min + v * (max - min)
+            })
+            .collect();
+        let value = (gray(converter, family.color(&values)) * 255.).round() as u8;
+        cache.insert(pixel, value);
+        result.push(value);
+    }
+    if result.len() < samples {
+        return Err("truncated image data".to_string());
+    }
+    Ok(result)
+}
+
+struct Grayscale<'a> {
+    doc: &'a Document,
+    converter: &'a ColorConverter,
+    next_id: u32,
+    new_objects: Vec<(ObjectId, Object)>,
+    report: GrayscaleReport,
+}
+
+impl<'a> Grayscale<'a> {
+    fn add(&mut self, object: Object) -> Object {
+        let id = (self.next_id, 0);
+        self.next_id += 1;
+        self.new_objects.push((id, object));
+        Object::Reference(id)
+    }
+    fn family(&self, space: &Object) -> Option<ColorFamily> {
+        let space = match self.doc.dereference(space).ok()?.1 {
+            // abbreviations used by inline images
+            Object::Name(name) if name == b"G" => device_gray(),
+            Object::Name(name) if name == b"RGB" => Object::Name(b"DeviceRGB".to_vec()),
+            Object::Name(name) if name == b"CMYK" => Object::Name(b"DeviceCMYK".to_vec()),
+            object => object.clone(),
+        };
+        ColorSpace::from_resource(self.doc, b"", &space).family()
+    }
+    // The gray replacement for a colour space, None when it stays as it is.
+    fn space(&mut self, object: &Object) -> Option<Object> {
+        let doc = self.doc;
+        match doc.dereference(object).ok()?.1 {
+            Object::Name(name) => match name.as_slice() {
+                b"DeviceRGB" | b"DeviceCMYK" | b"RGB" | b"CMYK" => {
+                    self.report.color_spaces += 1;
+                    Some(device_gray())
+                }
+                _ => None,
+            },
+            Object::Array(array) => match array.first()?.as_name().ok()? {
+                b"CalGray" | b"CalRGB" | b"Lab" | b"ICCBased" => {
+                    self.report.color_spaces += 1;
+                    Some(device_gray())
+                }
+                b"Indexed" | b"I" => {
+                    let base = array.get(1)?;
+                    let hival = array.get(2)?.as_i64().ok()?;
+                    let lookup = match doc.dereference(array.get(3)?).ok()?.1 {
+                        Object::String(data, _) => data.clone(),
+                        Object::Stream(stream) => stream_content(stream)?,
+                        _ => return None,
+                    };
+                    match self.family(base) {
+                        Some(_) if is_device_gray(doc, base) => None,
+                        Some(family) => {
+                            let n = family.components();
+                            let table: Vec<u8> = lookup
+                                .chunks_exact(n)
+                                .take(hival as usize + 1)
+                                .map(|entry| {
+                                    let values: Vec<f32> = entry
+                                        .iter()
+                                        .enumerate()
+                                        .map(|(i, v)| match (family, i) {
+                                            (ColorFamily::Lab, 0) => *v as f32 / 255. * 100.,
+                                            (ColorFamily::Lab, _) => *v as f32 / 255. * 200. - 100.,
+                                            _ => *v as f32 / 255.,
+                                        })
+                                        .collect();
+                                    (gray(self.converter, family.color(&values)) * 255.).round()
+                                        as u8
+                                })
+                                .collect();
+                            self.report.color_spaces += 1;
+                            Some(Object::Array(vec![
+                                array[0].clone(),
+                                device_gray(),
+                                Object::Integer(hival),
+                                Object::String(table, StringFormat::Hexadecimal),
+                            ]))
+                        }
+                        // a Separation or DeviceN base keeps its components
+                        None => {
+                            let base = self.space(base)?;
+                            Some(Object::Array(vec![
+                                array[0].clone(),
+                                base,
+                                Object::Integer(hival),
+                                array[3].clone(),
+                            ]))
+                        }
+                    }
+                }
+                kind @ (b"Separation" | b"DeviceN") => {
+                    let inputs = if kind == b"Separation" {
+                        1
+                    } else {
+                        doc.dereference(array.get(1)?)
+                            .ok()?
+                            .1
+                            .as_array()
+                            .ok()?
+                            .len()
+                    };
+                    let alternate = array.get(2)?;
+                    let family = self.family(alternate)?;
+                    if is_device_gray(doc, alternate) {
+                        return None;
+                    }
+                    let name = String::from_utf8_lossy(kind);
+                    let samples = match inputs {
+                        1 => 256,
+                        2 => 33,
+                        3 => 17,
+                        4 => 9,
+                        _ => {
+                            self.report
+                                .skipped
+                                .push(format!("{} space with {} colorants", name, inputs));
+                            return None;
+                        }
+                    };
+                    let Some(function) = Function::from_object(doc, array.get(3)?) else {
+                        self.report
+                            .skipped
+                            .push(format!("{} space with an unsupported tint transform", name));
+                        return None;
+                    };
+                    let converter = self.converter;
+                    let tint =
+                        sample_function(&vec![(0., 1.); inputs], &[(0., 1.)], samples, |x| {
+                            vec![gray(converter, family.color(&function.evaluate(x)))]
+                        });
+                    let mut space = array.clone();
+                    space[2] = device_gray();
+                    space[3] = self.add(Object::Stream(tint));
+                    self.report.color_spaces += 1;
+                    Some(Object::Array(space))
+                }
+                b"Pattern" => {
+                    let base = self.space(array.get(1)?)?;
+                    Some(Object::Array(vec![array[0].clone(), base]))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    fn shading(&mut self, dict: &mut Dictionary) -> bool {
+        let Ok(space) = dict.get(b"ColorSpace").cloned() else {
+            return false;
+        };
+        let family = match self.family(&space) {
+            Some(_) if is_device_gray(self.doc, &space) => return false,
+            Some(family) => family,
+            None => {
+                return match self.space(&space) {
+                    Some(space) => {
+                        dict.set("ColorSpace", space);
+                        self.report.shadings += 1;
+                        true
+                    }
+                    None => false,
+                };
+            }
+        };
+        if let Ok(function) = dict.get(b"Function") {
+            let Some(function) = Function::from_object(self.doc, function) else {
+                self.report
+                    .skipped
+                    .push("shading with an unsupported function".to_string());
+                return false;
+            };
+            let domain = function.domain();
+            let samples = if domain.len() == 1 { 256 } else { 33 };
+            let converter = self.converter;
+            let sampled = sample_function(&domain, &[(0., 1.)], samples, |x| {
+                vec![gray(converter, family.color(&function.evaluate(x)))]
+            });
+            let sampled = self.add(Object::Stream(sampled));
+            dict.set("Function", sampled);
+        } else if family != ColorFamily::Gray {
+            // mesh shadings without a function carry colours in their data
+            self.report
+                .skipped
+                .push("mesh shading with colours per vertex".to_string());
+            return false;
+        }
+        if let Ok(background) = dict.get(b"Background").and_then(|o| o.as_array()) {
+            let values: Vec<f32> = background.iter().filter_map(object_to_f32).collect();
+            let value = gray(self.converter, family.color(&values));
+            dict.set("Background", vec![Object::Real(value)]);
+        }
+        dict.set("ColorSpace", device_gray());
+        self.report.shadings += 1;
+        true
+    }
+    fn image(&mut self, id: ObjectId, stream: &mut Stream) -> bool {
+        let dict = &stream.dict;
+        if dict
+            .get(b"ImageMask")
+            .and_then(|o| o.as_bool())
+            .unwrap_or(false)
+        {
+            return false;
+        }
+        let Ok(space) = dict.get(b"ColorSpace").cloned() else {
+            // JPX images may carry their colour space in the data
+            self.report
+                .skipped
+                .push(format!("image {} {} R without a colour space", id.0, id.1));
+            return false;
+        };
+        let family = match self.family(&space) {
+            Some(_) if is_device_gray(self.doc, &space) => return false,
+            Some(ColorFamily::Gray) => {
+                stream.dict.set("ColorSpace", device_gray());
+                self.report.images += 1;
+                return true;
+            }
+            Some(family) => family,
+            None => {
+                return match self.space(&space) {
+                    Some(space) => {
+                        stream.dict.set("ColorSpace", space);
+                        self.report.images += 1;
+                        true
+                    }
+                    None => false,
+                };
+            }
+        };
+        let get = |key: &[u8]| dict.get(key).and_then(|o| o.as_i64()).unwrap_or(0) as usize;
+        let params = ImageParams {
+            width: get(b"Width"),
+            height: get(b"Height"),
+            bits: get(b"BitsPerComponent"),
+            family,
+            filters: filter_names(dict.get(b"Filter").ok()),
+            parms: dict
+                .get(b"DecodeParms")
+                .and_then(|o| self.doc.dereference(o))
+                .and_then(|(_, o)| o.as_dict())
+                .ok(),
+            decode: dict
+                .get(b"Decode")
+                .and_then(|o| o.as_array())
+                .ok()
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|o| o.as_float().or(o.as_i64().map(|i| i as f32)).ok())
+                        .collect()
+                }),
+        };
+        match gray_pixels(self.converter, &stream.content, &params) {
+            Ok(pixels) => {
+                stream.dict.set("ColorSpace", device_gray());
+                stream.dict.set("BitsPerComponent", 8);
+                stream.dict.remove(b"Decode");
+                stream.set_plain_content(pixels);
+                self.report.images += 1;
+                true
+            }
+            Err(reason) => {
+                self.report
+                    .skipped
+                    .push(format!("image {} {} R: {}", id.0, id.1, reason));
+                false
+            }
+        }
+    }
+    fn inline_image(
+        &mut self,
+        dict: &Dictionary,
+        data: &[u8],
+        spaces: &HashMap<Vec<u8>, Object>,
+        label: &str,
+    ) -> Option<Operation> {
+        let get = |short: &[u8], long: &[u8]| dict.get(short).or(dict.get(long)).ok();
+        if get(b"IM", b"ImageMask").and_then(|o| o.as_bool().ok()) == Some(true) {
+            return None;
+        }
+        let space = get(b"CS", b"ColorSpace")?;
+        // a named space other than a device one refers to the resources
+        let resolved = match space {
+            Object::Name(name) => spaces.get(name).unwrap_or(space),
+            _ => space,
+        };
+        let mut converted = dict.clone();
+        let data = match self.family(resolved) {
+            Some(ColorFamily::Gray) => return None,
+            Some(family) => {
+                let number = |short: &[u8], long: &[u8]| {
+                    get(short, long).and_then(|o| o.as_i64().ok()).unwrap_or(0) as usize
+                };
+                let params = ImageParams {
+                    width: number(b"W", b"Width"),
+                    height: number(b"H", b"Height"),
+                    bits: number(b"BPC", b"BitsPerComponent"),
+                    family,
+                    filters: filter_names(get(b"F", b"Filter")),
+                    parms: get(b"DP", b"DecodeParms").and_then(|o| o.as_dict().ok()),
+                    decode: get(b"D", b"Decode")
+                        .and_then(|o| o.as_array().ok())
+                        .map(|a| {
+                            a.iter()
+                                .filter_map(|o| o.as_float().or(o.as_i64().map(|i| i as f32)).ok())
+                                .collect()
+                        }),
+                };
+                match gray_pixels(self.converter, data, &params) {
+                    Ok(pixels) => {
+                        for key in [
+                            b"ColorSpace".as_slice(),
+                            b"BitsPerComponent",
+                            b"Filter",
+                            b"DecodeParms",
+                            b"Decode",
+                            b"D",
+                            b"DP",
+                        ] {
+                            converted.remove(key);
+                        }
+                        converted.set("CS", Object::Name(b"G".to_vec()));
+                        converted.set("BPC", 8);
+                        converted.set("F", Object::Name(b"Fl".to_vec()));
+                        deflate(&pixels)
+                    }
+                    Err(reason) => {
+                        self.report
+                            .skipped
+                            .push(format!("inline image on {}: {}", label, reason));
+                        return None;
+                    }
+                }
+            }
+            // named resources are converted with the other resources
+            None if matches!(space, Object::Name(_)) => return None,
+            None => {
+                let space = self.space(space)?;
+                converted.remove(b"ColorSpace");
+                converted.set("CS", space);
+                data.to_vec()
+            }
+        };
+        self.report.images += 1;
+        Some(Operation::new(
+            "BI",
+            vec![
+                Object::Dictionary(converted),
+                Object::String(data, StringFormat::Literal),
+            ],
+        ))
+    }
+    fn operation(
+        &mut self,
+        operation: Operation,
+        state: &State,
+        spaces: &HashMap<Vec<u8>, Object>,
+        label: &str,
+    ) -> Operation {
+        let stroke = operation.operator.chars().all(|c| c.is_ascii_uppercase());
+        let color = &state.graphics.color;
+        let (current, space) = if stroke {
+            (&color.stroke, &color.stroke_space)
+        } else {
+            (&color.non_stroke, &color.non_stroke_space)
+        };
+        match operation.operator.as_ref() {
+            "rg" | "RG" | "k" | "K" => {
+                self.report.operators += 1;
+                Color::Gray(gray(self.converter, Some(current.clone()))).into_operator(stroke)
+            }
+            "cs" | "CS" => match operation.operands.first().and_then(|o| o.as_name().ok()) {
+                Some(b"DeviceRGB") | Some(b"DeviceCMYK") => {
+                    self.report.operators += 1;
+                    Operation::new(&operation.operator, vec![device_gray()])
+                }
+                _ => operation,
+            },
+            "sc" | "scn" | "SC" | "SCN" => match space.family() {
+                Some(ColorFamily::Gray) => operation,
+                Some(_) => {
+                    self.report.operators += 1;
+                    let value = gray(self.converter, Some(current.clone()));
+                    Operation::new(&operation.operator, vec![Object::Real(value)])
+                }
+                None => {
+                    // an uncoloured pattern is painted in the pattern space's base
+                    let base = match space {
+                        ColorSpace::Resource(name, None) => spaces
+                            .get(name)
+                            .and_then(|o| o.as_array().ok())
+                            .filter(|a| {
+                                a.first().and_then(|o| o.as_name().ok()) == Some(b"Pattern")
+                            })
+                            .and_then(|a| a.get(1))
+                            .and_then(|base| self.family(base)),
+                        _ => None,
+                    };
+                    match (base, operation.operands.split_last()) {
+                        (Some(family), Some((pattern @ Object::Name(_), components)))
+                            if family != ColorFamily::Gray =>
+                        {
+                            let values: Vec<f32> =
+                                components.iter().filter_map(object_to_f32).collect();
+                            self.report.operators += 1;
+                            let value = gray(self.converter, family.color(&values));
+                            Operation::new(
+                                &operation.operator,
+                                vec![Object::Real(value), pattern.clone()],
+                            )
+                        }
+                        _ => operation,
+                    }
+                }
+            },
+            "BI" => match &operation.operands[..] {
+                [Object::Dictionary(dict), Object::String(data, _)] => self
+                    .inline_image(dict, data, spaces, label)
+                    .unwrap_or(operation),
+                _ => operation,
+            },
+            _ => operation,
+        }
+    }
+    fn content(
+        &mut self,
+        operations: Vec<Operation>,
+        mut state: State,
+        spaces: &HashMap<Vec<u8>, Object>,
+        label: &str,
+    ) -> Vec<Operation> {
+        operations
+            .into_iter()
+            .map(|operation| {
+                state.handle_operation(&operation);
+                self.operation(operation, &state, spaces, label)
+            })
+            .collect()
+    }
+    // Converts every entry of a `/ColorSpace` resource dictionary.
+    fn category(&mut self, dict: &mut Dictionary) -> bool {
+        let mut changed = false;
+        for (_, space) in dict.iter_mut() {
+            if let Some(converted) = self.space(space) {
+                *space = converted;
+                changed = true;
+            }
+        }
+        changed
+    }
+    fn dictionary(&mut self, dict: &mut Dictionary) -> bool {
+        if dict.has(b"ShadingType") {
+            return self.shading(dict);
+        }
+        let mut changed = false;
+        for (key, value) in dict.iter_mut() {
+            changed |= match (key.as_slice(), &mut *value) {
+                (b"ColorSpace", Object::Dictionary(category)) => self.category(category),
+                // the blending space of a transparency group
+                (b"CS", value) => match self.space(value) {
+                    Some(space) => {
+                        *value = space;
+                        true
+                    }
+                    None => false,
+                },
+                (_, value) => self.walk((0, 0), value),
+            };
+        }
+        changed
+    }
+    fn walk(&mut self, id: ObjectId, object: &mut Object) -> bool {
+        match object {
+            Object::Dictionary(dict) => self.dictionary(dict),
+            Object::Stream(stream) => {
+                if stream.dict.get(b"Subtype").and_then(|o| o.as_name()).ok() == Some(b"Image") {
+                    self.image(id, stream)
+                } else {
+                    self.dictionary(&mut stream.dict)
+                }
+            }
+            Object::Array(array) => array
+                .iter_mut()
+                .fold(false, |changed, o| self.walk(id, o) | changed),
+            _ => false,
+        }
+    }
+}
+
+// Colour space resources by name, dereferenced.
+fn space_map(doc: &Document, category: Option<&Dictionary>) -> HashMap<Vec<u8>, Object> {
+    category
+        .map(|dict| {
+            dict.iter()
+                .filter_map(|(name, o)| Some((name.clone(), doc.dereference(o).ok()?.1.clone())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Objects that are `/ColorSpace` resource dictionaries referenced indirectly.
+fn collect_categories(doc: &Document, object: &Object, categories: &mut HashSet<ObjectId>) {
+    let dict = match object {
+        Object::Dictionary(dict) => dict,
+        Object::Stream(stream) => &stream.dict,
+        Object::Array(array) => {
+            for o in array {
+                collect_categories(doc, o, categories);
+            }
+            return;
+        }
+        _ => return,
+    };
+    for (key, value) in dict.iter() {
+        match value {
+            Object::Reference(id) if key == b"ColorSpace" => {
+                if let Ok(Object::Dictionary(_)) = doc.get_object(*id) {
+                    categories.insert(*id);
+                }
+            }
+            value => collect_categories(doc, value, categories),
+        }
+    }
+}
+
+fn is_content_stream(stream: &Stream) -> bool {
+    stream.dict.get(b"Subtype").and_then(|o| o.as_name()).ok() == Some(b"Form")
+        || stream
+            .dict
+            .get(b"PatternType")
+            .and_then(|o| o.as_i64())
+            .ok()
+            == Some(1)
+}
+
+// The glyph procedures of Type3 fonts, with the resources of their font.
+fn char_procs(doc: &Document) -> HashMap<ObjectId, Option<&Dictionary>> {
+    let mut procs = HashMap::new();
+    for object in doc.objects.values() {
+        let Ok(font) = object.as_dict() else {
+            continue;
+        };
+        if font.get(b"Subtype").and_then(|o| o.as_name()).ok() != Some(b"Type3") {
+            continue;
+        }
+        let resources = font
+            .get(b"Resources")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+            .ok();
+        let Ok((_, Object::Dictionary(glyphs))) =
+            font.get(b"CharProcs").and_then(|o| doc.dereference(o))
+        else {
+            continue;
+        };
+        for (_, glyph) in glyphs.iter() {
+            if let Ok(id) = glyph.as_reference() {
+                procs.insert(id, resources);
+            }
+        }
+    }
+    procs
+}
+
+// Converts pages, form XObjects, tiling patterns, Type3 glyphs and all
+// colour spaces, shadings and images of the document to DeviceGray.
+pub fn convert_to_grayscale(
+    modifier: &mut PdfModifier,
+    converter: &ColorConverter,
+) -> GrayscaleReport {
+    let doc = modifier.document();
+    let mut grayscale = Grayscale {
+        doc,
+        converter,
+        next_id: doc.max_id + 1,
+        new_objects: Vec::new(),
+        report: GrayscaleReport::default(),
+    };
+
+    let mut pages = Vec::new();
+    for (number, page_id) in doc.get_pages() {
+        let Ok(operations) = doc
+            .get_page_content(page_id)
+            .and_then(|content| decode_operations(&content))
+        else {
+            grayscale
+                .report
+                .skipped
+                .push(format!("page {}: unreadable content", number));
+            continue;
+        };
+        let spaces = space_map(doc, page_resource(doc, page_id, b"ColorSpace"));
+        let operations = grayscale.content(
+            operations,
+            State::new(doc, page_id),
+            &spaces,
+            &format!("page {}", number),
+        );
+        pages.push((page_id, encode_operations(&operations).unwrap()));
+    }
+
+    let procs = char_procs(doc);
+    let mut categories = HashSet::new();
+    for object in doc.objects.values() {
+        collect_categories(doc, object, &mut categories);
+    }
+    let mut streams = Vec::new();
+    let mut objects = Vec::new();
+    for (id, object) in doc.objects.iter() {
+        if let Object::Stream(stream) = object {
+            let resources = if is_content_stream(stream) {
+                Some(
+                    stream
+                        .dict
+                        .get(b"Resources")
+                        .and_then(|o| doc.dereference(o))
+                        .and_then(|(_, o)| o.as_dict())
+                        .ok(),
+                )
+            } else {
+                procs.get(id).copied()
+            };
+            if let Some(resources) = resources {
+                let spaces = space_map(
+                    doc,
+                    resources
+                        .and_then(|r| r.get(b"ColorSpace").ok())
+                        .and_then(|o| doc.dereference(o).ok())
+                        .and_then(|(_, o)| o.as_dict().ok()),
+                );
+                let label = format!("object {} {} R", id.0, id.1);
+                match stream_content(stream).map(|content| decode_operations(&content)) {
+                    Some(Ok(operations)) => {
+                        let state = State::from_resources(doc, resources);
+                        let operations = grayscale.content(operations, state, &spaces, &label);
+                        streams.push((*id, encode_operations(&operations).unwrap()));
+                    }
+                    _ => grayscale
+                        .report
+                        .skipped
+                        .push(format!("{}: unreadable content", label)),
+                }
+            }
+        }
+        let mut converted = object.clone();
+        let changed = match &mut converted {
+            Object::Dictionary(dict) if categories.contains(id) => grayscale.category(dict),
+            converted => grayscale.walk(*id, converted),
+        };
+        if changed {
+            objects.push((*id, converted));
+        }
+    }
+
+    let Grayscale {
+        next_id,
+        new_objects,
+        report,
+        ..
+    } = grayscale;
+    let doc = modifier.document_mut();
+    for (id, object) in objects.into_iter().chain(new_objects) {
+        doc.objects.insert(id, object);
+    }
+    doc.max_id = next_id - 1;
+    for (id, content) in streams {
+        if let Ok(Object::Stream(stream)) = doc.get_object_mut(id) {
+            stream.set_plain_content(content);
+        }
+    }
+    for (page_id, content) in pages {
+        doc.change_page_content(page_id, content).unwrap();
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use lopdf::dictionary;
+
+    fn convert(content: &str, resources: Dictionary) -> (Document, GrayscaleReport) {
+        let mut modifier = PdfModifier::from_document(testing::page(content, resources));
+        let report = convert_to_grayscale(&mut modifier, &ColorConverter::naive());
+        (modifier.document().clone(), report)
+    }
+
+    fn page_operations(doc: &Document) -> Vec<Operation> {
+        let page_id = doc.page_iter().next().unwrap();
+        decode_operations(&doc.get_page_content(page_id).unwrap()).unwrap()
+    }
+
+    fn resource<'a>(doc: &'a Document, category: &[u8], name: &[u8]) -> &'a Object {
+        let page_id = doc.page_iter().next().unwrap();
+        let dict = page_resource(doc, page_id, category).unwrap();
+        doc.dereference(dict.get(name).unwrap()).unwrap().1
+    }
+
+    #[test]
+    fn rgb_fill() {
+        let (doc, report) = convert("1 0 0 rg 0 0 10 10 re f 0 0 1 RG", Dictionary::new());
+        let operations = page_operations(&doc);
+        let red = gray(&ColorConverter::naive(), Some(Color::RGB(1., 0., 0.)));
+        assert_eq!(operations[0].operator, "g");
+        assert_eq!(object_to_f32(&operations[0].operands[0]), Some(red));
+        assert_eq!(operations[3].operator, "G");
+        assert_eq!(report.operators, 2);
+    }
+
+    #[test]
+    fn indexed_image() {
+        let mut doc = testing::page("q 10 0 0 10 0 0 cm /Im0 Do Q", Dictionary::new());
+        let image = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 2,
+                "Height" => 1,
+                "BitsPerComponent" => 8,
+                "ColorSpace" => vec![
+                    "Indexed".into(),
+                    "DeviceRGB".into(),
+                    1.into(),
+                    Object::String(vec![255, 0, 0, 255, 255, 255], StringFormat::Hexadecimal),
+                ],
+            },
+            vec![0, 1],
+        ));
+        let page_id = doc.page_iter().next().unwrap();
+        doc.get_dictionary_mut(page_id)
+            .unwrap()
+            .get_mut(b"Resources")
+            .and_then(|o| o.as_dict_mut())
+            .unwrap()
+            .set("XObject", dictionary! { "Im0" => image });
+        let mut modifier = PdfModifier::from_document(doc);
+        convert_to_grayscale(&mut modifier, &ColorConverter::naive());
+        let Object::Stream(image) = resource(modifier.document(), b"XObject", b"Im0") else {
+            panic!("image is not a stream");
+        };
+        let space = image.dict.get(b"ColorSpace").unwrap().as_array().unwrap();
+        assert_eq!(space[1].as_name().unwrap(), b"DeviceGray");
+        let red = (gray(&ColorConverter::naive(), Some(Color::RGB(1., 0., 0.))) * 255.).round();
+        assert_eq!(space[3].as_str().unwrap(), [red as u8, 255]);
+        // the indices are kept
+        assert_eq!(image.content, [0, 1]);
+    }
+
+    #[test]
+    fn separation_space() {
+        let tint = dictionary! {
+            "FunctionType" => 2,
+            "Domain" => vec![0.into(), 1.into()],
+            "C0" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+            "C1" => vec![1.into(), 0.into(), 0.into(), 0.into()],
+            "N" => 1,
+        };
+        let (doc, _) = convert(
+            "/CS0 cs 1 scn 0 0 10 10 re f",
+            dictionary! {
+                "ColorSpace" => dictionary! {
+                    "CS0" => vec![
+                        "Separation".into(),
+                        "Blue".into(),
+                        "DeviceCMYK".into(),
+                        tint.into(),
+                    ],
+                },
+            },
+        );
+        let space = resource(&doc, b"ColorSpace", b"CS0").as_array().unwrap();
+        assert_eq!(space[0].as_name().unwrap(), b"Separation");
+        assert_eq!(space[2].as_name().unwrap(), b"DeviceGray");
+        let cyan = gray(&ColorConverter::naive(), Some(Color::CMYK(1., 0., 0., 0.)));
+        let function = Function::from_object(&doc, &space[3]).unwrap();
+        assert!((function.evaluate(&[1.])[0] - cyan).abs() < 0.01);
+        // the tint itself is left alone
+        assert_eq!(page_operations(&doc)[1].operator, "scn");
+    }
+
+    #[test]
+    fn type3_glyphs() {
+        let mut doc = testing::page("BT /T3 12 Tf (a) Tj ET", Dictionary::new());
+        let glyph = doc.add_object(Stream::new(
+            Dictionary::new(),
+            b"1000 0 d0 1 0 0 rg 0 0 1000 1000 re f".to_vec(),
+        ));
+        let font = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type3",
+            "FontBBox" => vec![0.into(), 0.into(), 1000.into(), 1000.into()],
+            "FontMatrix" => vec![0.001.into(), 0.into(), 0.into(), 0.001.into(), 0.into(), 0.into()],
+            "CharProcs" => dictionary! { "a" => glyph },
+            "Encoding" => dictionary! { "Differences" => vec![97.into(), "a".into()] },
+            "FirstChar" => 97,
+            "LastChar" => 97,
+            "Widths" => vec![1000.into()],
+        });
+        let page_id = doc.page_iter().next().unwrap();
+        doc.get_dictionary_mut(page_id)
+            .unwrap()
+            .get_mut(b"Resources")
+            .and_then(|o| o.as_dict_mut())
+            .unwrap()
+            .set("Font", dictionary! { "T3" => font });
+        let mut modifier = PdfModifier::from_document(doc);
+        convert_to_grayscale(&mut modifier, &ColorConverter::naive());
+        let Ok(Object::Stream(glyph)) = modifier.document().get_object(glyph) else {
+            panic!("glyph is not a stream");
+        };
+        let operations = decode_operations(&glyph.content).unwrap();
+        assert_eq!(operations[1].operator, "g");
+    }
+
+    #[test]
+    fn shading_background() {
+        let (doc, report) = convert(
+            "/Sh0 sh",
+            dictionary! {
+                "Shading" => dictionary! {
+                    "Sh0" => dictionary! {
+                        "ShadingType" => 2,
+                        "ColorSpace" => "DeviceRGB",
+                        "Coords" => vec![0.into(), 0.into(), 1.into(), 0.into()],
+                        "Function" => dictionary! {
+                            "FunctionType" => 2,
+                            "Domain" => vec![0.into(), 1.into()],
+                            "C0" => vec![0.into(), 0.into(), 0.into()],
+                            "C1" => vec![1.into(), 1.into(), 1.into()],
+                            "N" => 1,
+                        },
+                        "Background" => vec![1.into(), 0.into(), 0.into()],
+                    },
+                },
+            },
+        );
+        assert_eq!(report.shadings, 1);
+        let shading = resource(&doc, b"Shading", b"Sh0").as_dict().unwrap();
+        let background = shading.get(b"Background").unwrap().as_array().unwrap();
+        let red = gray(&ColorConverter::naive(), Some(Color::RGB(1., 0., 0.)));
+        assert_eq!(object_to_f32(&background[0]), Some(red));
+    }
+}
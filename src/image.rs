@@ -0,0 +1,142 @@
+use std::io::{Read, Write};
+
+use lopdf::{Dictionary, Object, Stream};
+
+pub fn stream_content(stream: &Stream) -> Option<Vec<u8>> {
+    if stream.dict.has(b"Filter") {
+        stream.decompressed_content().ok()
+    } else {
+        Some(stream.content.clone())
+    }
+}
+
+pub fn filter_names(object: Option<&Object>) -> Vec<Vec<u8>> {
+    match object {
+        Some(Object::Name(name)) => vec![name.clone()],
+        Some(Object::Array(array)) => array
+            .iter()
+            .filter_map(|o| o.as_name().ok().map(|n| n.to_vec()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut result = Vec::new();
+    flate2::read::ZlibDecoder::new(data)
+        .read_to_end(&mut result)
+        .ok()?;
+    Some(result)
+}
+
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+// Reverses PNG predictors (/Predictor 10 to 15).
+pub fn unpredict(data: &[u8], parms: Option<&Dictionary>) -> Result<Vec<u8>, String> {
+    let get = |key: &[u8], default: i64| {
+        parms
+            .and_then(|p| p.get(key).and_then(|o| o.as_i64()).ok())
+            .unwrap_or(default)
+    };
+    let predictor = get(b"Predictor", 1);
+    if predictor == 1 {
+        return Ok(data.to_vec());
+    }
+    if predictor < 10 {
+        return Err("TIFF predictor".to_string());
+    }
+    let bits = (get(b"Colors", 1) * get(b"BitsPerComponent", 8)) as usize;
+    let pixel = bits.div_ceil(8);
+    let row = (bits * get(b"Columns", 1) as usize).div_ceil(8);
+    let mut result: Vec<u8> = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row];
+    for chunk in data.chunks(row + 1) {
+        let (kind, line) = (chunk[0], &chunk[1..]);
+        let mut current = vec![0u8; row];
+        for (i, byte) in line.iter().enumerate() {
+            let left = if i >= pixel { current[i - pixel] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= pixel { previous[i - pixel] } else { 0 };
+            let prediction = match kind {
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => {
+                    let p = left as i16 + up as i16 - up_left as i16;
+                    let (pa, pb, pc) = (
+                        (p - left as i16).abs(),
+                        (p - up as i16).abs(),
+                        (p - up_left as i16).abs(),
+                    );
+                    if pa <= pb && pa <= pc {
+                        left
+                    } else if pb <= pc {
+                        up
+                    } else {
+                        up_left
+                    }
+                }
+                _ => 0,
+            };
+            current[i] = byte.wrapping_add(prediction);
+        }
+        result.extend_from_slice(&current[..line.len()]);
+        previous = current;
+    }
+    Ok(result)
+}
+
+// Samples of image data as unfiltered bytes.
+pub struct Samples {
+    pub data: Vec<u8>,
+    pub bits: usize,
+    pub components: usize,
+}
+
+// Undoes the filters of image data. Only Flate and DCT data can be decoded;
+// DCT data comes out with 8 bits per component and as many components as
+// the JPEG has.
+pub fn decode_samples(
+    content: &[u8],
+    filters: &[Vec<u8>],
+    parms: Option<&Dictionary>,
+    bits: usize,
+    components: usize,
+) -> Result<Samples, String> {
+    let samples = |data, bits, components| Samples {
+        data,
+        bits,
+        components,
+    };
+    match filters.iter().map(|f| f.as_slice()).collect::<Vec<_>>()[..] {
+        [] => Ok(samples(content.to_vec(), bits, components)),
+        [b"FlateDecode"] | [b"Fl"] => Ok(samples(
+            unpredict(&inflate(content).ok_or("corrupt Flate data")?, parms)?,
+            bits,
+            components,
+        )),
+        [b"DCTDecode"] | [b"DCT"] => {
+            let mut decoder = jpeg_decoder::Decoder::new(content);
+            let pixels = decoder.decode().map_err(|e| e.to_string())?;
+            let info = decoder.info().ok_or("corrupt JPEG data")?;
+            let (bits, components) = match info.pixel_format {
+                jpeg_decoder::PixelFormat::L8 => (8, 1),
+                jpeg_decoder::PixelFormat::L16 => (16, 1),
+                jpeg_decoder::PixelFormat::RGB24 => (8, 3),
+                jpeg_decoder::PixelFormat::CMYK32 => (8, 4),
+            };
+            Ok(samples(pixels, bits, components))
+        }
+        _ => {
+            let filters: Vec<String> = filters
+                .iter()
+                .map(|f| String::from_utf8_lossy(f).to_string())
+                .collect();
+            Err(format!("{} filter", filters.join(" ")))
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use lopdf::Object;
+
+use crate::graphics::color::Color;
+use crate::{page_resource, Paint, PdfModifier};
+
+#[derive(Debug, Clone)]
+pub struct ColorUsage {
+    pub color: Color,
+    pub text: usize,
+    pub paths: usize,
+    // stencil masks, which are painted with the fill colour
+    pub images: usize,
+    pub fill: usize,
+    pub stroke: usize,
+    pub pages: BTreeSet<usize>,
+}
+
+impl ColorUsage {
+    fn new(color: Color) -> Self {
+        Self {
+            color,
+            text: 0,
+            paths: 0,
+            images: 0,
+            fill: 0,
+            stroke: 0,
+            pages: BTreeSet::new(),
+        }
+    }
+    pub fn total(&self) -> usize {
+        self.text + self.paths + self.images
+    }
+    // Pages as ranges such as `1-3,5`.
+    pub fn page_ranges(&self) -> String {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for page in &self.pages {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == *page => *end = *page,
+                _ => ranges.push((*page, *page)),
+            }
+        }
+        ranges
+            .iter()
+            .map(|(start, end)| {
+                if start == end {
+                    start.to_string()
+                } else {
+                    format!("{}-{}", start, end)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ColorInventory {
+    pub colors: Vec<ColorUsage>,
+}
+
+// Names of the page's image XObjects that are stencil masks.
+fn image_masks(modifier: &PdfModifier, page_id: (u32, u16)) -> Vec<Vec<u8>> {
+    let doc = modifier.document();
+    page_resource(doc, page_id, b"XObject")
+        .map(|xobjects| {
+            xobjects
+                .iter()
+                .filter(|(_, xobject)| match doc.dereference(xobject) {
+                    Ok((_, Object::Stream(stream))) => stream
+                        .dict
+                        .get(b"ImageMask")
+                        .and_then(|o| o.as_bool())
+                        .unwrap_or(false),
+                    _ => false,
+                })
+                .map(|(name, _)| name.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl ColorInventory {
+    pub fn new() -> Self {
+        Self { colors: Vec::new() }
+    }
+    fn usage(&mut self, color: &Color) -> &mut ColorUsage {
+        let index = match self.colors.iter().position(|u| &u.color == color) {
+            Some(index) => index,
+            None => {
+                self.colors.push(ColorUsage::new(color.clone()));
+                self.colors.len() - 1
+            }
+        };
+        &mut self.colors[index]
+    }
+    pub fn add_page(
+        &mut self,
+        modifier: &mut PdfModifier,
+        page_id: (u32, u16),
+        page_number: usize,
+    ) {
+        let masks = image_masks(modifier, page_id);
+        modifier.for_each(page_id, &mut |operation, state| {
+            let color = &state.graphics.color;
+            let is_mask = match (operation.operator.as_ref(), &operation.operands[..]) {
+                ("Do", [Object::Name(name), ..]) => masks.contains(name),
+                ("BI", [Object::Dictionary(dict), ..]) => dict
+                    .get(b"IM")
+                    .or(dict.get(b"ImageMask"))
+                    .and_then(|o| o.as_bool())
+                    .unwrap_or(false),
+                _ => false,
+            };
+            if is_mask && color.is_tracked(false) {
+                let usage = self.usage(&color.non_stroke);
+                usage.images += 1;
+                usage.fill += 1;
+                usage.pages.insert(page_number);
+            }
+            for paint in Paint::of(&operation, state) {
+                let stroke = paint.is_stroke();
+                if !color.is_tracked(stroke) {
+                    continue;
+                }
+                let usage = self.usage(if stroke {
+                    &color.stroke
+                } else {
+                    &color.non_stroke
+                });
+                match paint {
+                    Paint::TextFill | Paint::TextStroke => usage.text += 1,
+                    Paint::PathFill | Paint::PathStroke => usage.paths += 1,
+                }
+                if stroke {
+                    usage.stroke += 1;
+                } else {
+                    usage.fill += 1;
+                }
+                usage.pages.insert(page_number);
+            }
+        });
+    }
+    // Most used colours first.
+    pub fn sort(&mut self) {
+        self.colors.sort_by_key(|u| std::cmp::Reverse(u.total()));
+    }
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!(self
+            .colors
+            .iter()
+            .map(|u| serde_json::json!({
+                "color": u.color.to_string(),
+                "text": u.text,
+                "paths": u.paths,
+                "images": u.images,
+                "fill": u.fill,
+                "stroke": u.stroke,
+                "pages": u.pages,
+            }))
+            .collect::<Vec<_>>())
+    }
+}
+
+impl Display for ColorInventory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .colors
+            .iter()
+            .map(|u| u.color.to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max("color".len());
+        writeln!(
+            f,
+            "{:width$}  {:>6} {:>6} {:>6} {:>6} {:>6}  pages",
+            "color", "text", "paths", "images", "fill", "stroke"
+        )?;
+        for u in &self.colors {
+            writeln!(
+                f,
+                "{:width$}  {:>6} {:>6} {:>6} {:>6} {:>6}  {}",
+                u.color.to_string(),
+                u.text,
+                u.paths,
+                u.images,
+                u.fill,
+                u.stroke,
+                u.page_ranges()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,595 @@
+use kurbo::Rect;
+
+use crate::graphics::color::Color;
+use crate::graphics::text::RenderingMode;
+use crate::{PdfModifier, ShownGlyph};
+
+// The text shown by one text showing operation.
+#[derive(Debug, Clone)]
+pub struct PositionedText {
+    pub text: String,
+    pub font: String,
+    // font size scaled by the text and transformation matrices, in points
+    pub size: f64,
+    pub bold: bool,
+    pub color: Color,
+    pub rendering_mode: RenderingMode,
+    // in default user space
+    pub bbox: Rect,
+    pub glyphs: Vec<ShownGlyph>,
+}
+
+impl PositionedText {
+    // Runs of glyphs between white space, with their boxes.
+    pub fn words(&self) -> Vec<(String, Rect)> {
+        let mut words: Vec<(String, Rect)> = Vec::new();
+        let mut split = true;
+        for glyph in &self.glyphs {
+            if glyph.text.trim().is_empty() {
+                split = true;
+                continue;
+            }
+            match words.last_mut() {
+                Some((text, bbox)) if !split => {
+                    text.push_str(&glyph.text);
+                    *bbox = bbox.union(glyph.bbox);
+                }
+                _ => words.push((glyph.text.clone(), glyph.bbox)),
+            }
+            split = false;
+        }
+        words
+    }
+}
+
+// The text of the page, including the text of the forms it draws.
+pub fn text_runs(modifier: &mut PdfModifier, page_id: (u32, u16)) -> Vec<PositionedText> {
+    let mut runs = Vec::new();
+    modifier.for_each_nested(page_id, &mut |operation, state| {
+        let glyphs = state.shown_glyphs(&operation);
+        let Some(bbox) = glyphs.iter().map(|g| g.bbox).reduce(|a, b| a.union(b)) else {
+            return;
+        };
+        let text = &state.graphics.text;
+        let [_, _, c, d, _, _] = (state.graphics.ctm * text.matrix).as_coeffs();
+        let font = match state.font().base_font.as_str() {
+            "" => text
+                .font
+                .as_ref()
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+                .unwrap_or_default(),
+            name => name.to_string(),
+        };
+        runs.push(PositionedText {
+            text: glyphs.iter().map(|g| g.text.as_str()).collect(),
+            font,
+            size: text.font_size.unwrap_or(0.) as f64 * c.hypot(d),
+            bold: state.font().is_bold(),
+            color: state.graphics.color.non_stroke.clone(),
+            rendering_mode: text.rendering_mode.clone(),
+            bbox,
+            glyphs,
+        });
+    });
+    runs
+}
+
+#[derive(Debug, Clone)]
+pub struct PageText {
+    pub number: usize,
+    // the visible area of the page
+    pub bbox: Rect,
+    pub runs: Vec<PositionedText>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextExport {
+    pub pages: Vec<PageText>,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Default for TextExport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextExport {
+    pub fn new() -> Self {
+        Self { pages: Vec::new() }
+    }
+    pub fn add_page(
+        &mut self,
+        modifier: &mut PdfModifier,
+        page_id: (u32, u16),
+        page_number: usize,
+    ) {
+        let runs = text_runs(modifier, page_id);
+        let bbox = modifier
+            .visible_box(page_id)
+            .or_else(|| runs.iter().map(|r| r.bbox).reduce(|a, b| a.union(b)))
+            .unwrap_or(Rect::ZERO);
+        self.pages.push(PageText {
+            number: page_number,
+            bbox,
+            runs,
+        });
+    }
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!(self
+            .pages
+            .iter()
+            .map(|page| serde_json::json!({
+                "page": page.number,
+                "bbox": [page.bbox.x0, page.bbox.y0, page.bbox.x1, page.bbox.y1],
+                "runs": page
+                    .runs
+                    .iter()
+                    .map(|run| serde_json::json!({
+                        "text": run.text,
+                        "font": run.font,
+                        "size": run.size,
+                        "color": run.color.to_string(),
+                        "rendering_mode": run.rendering_mode.to_i64(),
+                        "bbox": [run.bbox.x0, run.bbox.y0, run.bbox.x1, run.bbox.y1],
+                    }))
+                    .collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>())
+    }
+    // One row per run with a header line. Tabs and line breaks in the text
+    // are written as spaces.
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = "page\tx0\ty0\tx1\ty1\tfont\tsize\tcolor\trendering_mode\ttext\n".to_string();
+        for page in &self.pages {
+            for run in &page.runs {
+                tsv.push_str(&format!(
+                    "{}\t{:.2}\t{:.2}\t{:.2}\t{:.2}\t{}\t{:.2}\t{}\t{}\t{}\n",
+                    page.number,
+                    run.bbox.x0,
+                    run.bbox.y0,
+                    run.bbox.x1,
+                    run.bbox.y1,
+                    run.font,
+                    run.size,
+                    run.color,
+                    run.rendering_mode.to_i64(),
+                    run.text.replace(['\t', '\n', '\r'], " ")
+                ));
+            }
+        }
+        tsv
+    }
+    // hOCR with a line per run and its words. Boxes are in points from the
+    // top left corner of the visible area, as hOCR expects.
+    pub fn to_hocr(&self) -> String {
+        let mut hocr = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" ",
+            "\"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">\n",
+            "<html xmlns=\"http://www.w3.org/1999/xhtml\">\n<head>\n",
+            "<meta http-equiv=\"Content-Type\" content=\"text/html; charset=utf-8\" />\n",
+            "<meta name=\"ocr-system\" content=\"pdf-console-editor\" />\n",
+            "<meta name=\"ocr-capabilities\" content=\"ocr_page ocr_line ocrx_word\" />\n",
+            "</head>\n<body>\n"
+        ));
+        for page in &self.pages {
+            let bbox = |rect: Rect| {
+                format!(
+                    "bbox {} {} {} {}",
+                    (rect.x0 - page.bbox.x0).round() as i64,
+                    (page.bbox.y1 - rect.y1).round() as i64,
+                    (rect.x1 - page.bbox.x0).round() as i64,
+                    (page.bbox.y1 - rect.y0).round() as i64
+                )
+            };
+            hocr.push_str(&format!(
+                "<div class=\"ocr_page\" id=\"page_{}\" title=\"{}; ppageno {}\">\n",
+                page.number,
+                bbox(page.bbox),
+                page.number - 1
+            ));
+            let mut word_number = 0;
+            for (line_number, run) in page.runs.iter().enumerate() {
+                hocr.push_str(&format!(
+                    "<span class=\"ocr_line\" id=\"line_{}_{}\" title=\"{}; x_size {:.2}\">",
+                    page.number,
+                    line_number + 1,
+                    bbox(run.bbox),
+                    run.size
+                ));
+                for (text, rect) in run.words() {
+                    word_number += 1;
+                    hocr.push_str(&format!(
+                        "<span class=\"ocrx_word\" id=\"word_{}_{}\" title=\"{}; x_font {}; x_fsize {:.2}\">{}</span> ",
+                        page.number,
+                        word_number,
+                        bbox(rect),
+                        escape_html(&run.font).replace(';', ""),
+                        run.size,
+                        escape_html(&text)
+                    ));
+                }
+                hocr.push_str("</span>\n");
+            }
+            hocr.push_str("</div>\n");
+        }
+        hocr.push_str("</body>\n</html>\n");
+        hocr
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Word {
+    pub text: String,
+    pub bbox: Rect,
+    pub size: f64,
+    pub bold: bool,
+    pub baseline: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub words: Vec<Word>,
+    pub bbox: Rect,
+    pub size: f64,
+    pub baseline: f64,
+}
+
+impl Line {
+    fn new(words: Vec<Word>) -> Self {
+        let bbox = words
+            .iter()
+            .map(|w| w.bbox)
+            .reduce(|a, b| a.union(b))
+            .unwrap();
+        let size = words.iter().map(|w| w.size).fold(0., f64::max);
+        let baseline = words[0].baseline;
+        Self {
+            words,
+            bbox,
+            size,
+            baseline,
+        }
+    }
+    pub fn text(&self) -> String {
+        self.words
+            .iter()
+            .map(|w| w.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Paragraph {
+    pub lines: Vec<Line>,
+    pub bbox: Rect,
+}
+
+impl Paragraph {
+    // The size, to half a point, and weight most of the text is set in.
+    pub fn style(&self) -> (f64, bool) {
+        let mut styles: Vec<((f64, bool), usize)> = Vec::new();
+        for word in self.lines.iter().flat_map(|l| &l.words) {
+            let style = ((word.size * 2.).round() / 2., word.bold);
+            match styles.iter_mut().find(|(s, _)| *s == style) {
+                Some((_, count)) => *count += word.text.chars().count(),
+                None => styles.push((style, word.text.chars().count())),
+            }
+        }
+        styles
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map_or((0., false), |(style, _)| style)
+    }
+    // The lines joined with spaces, rejoining words hyphenated across
+    // lines.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for line in &self.lines {
+            let line = line.text();
+            let hyphenated = text.strip_suffix('-').filter(|rest| {
+                rest.ends_with(char::is_alphabetic) && line.starts_with(char::is_lowercase)
+            });
+            match hyphenated {
+                Some(rest) => text.truncate(rest.len()),
+                None if !text.is_empty() => text.push(' '),
+                None => (),
+            }
+            text.push_str(&line);
+        }
+        text
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub bbox: Rect,
+    // in reading order
+    pub paragraphs: Vec<Paragraph>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PageLayout {
+    // in reading order
+    pub columns: Vec<Column>,
+}
+
+impl PageLayout {
+    pub fn paragraphs(&self) -> impl Iterator<Item = &Paragraph> {
+        self.columns.iter().flat_map(|c| &c.paragraphs)
+    }
+    pub fn to_text(&self) -> String {
+        self.paragraphs()
+            .map(|p| p.text() + "\n")
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+    // Headings are marked with their level, the largest being level 1.
+    pub fn to_markdown(&self, heading_styles: &[(f64, bool)]) -> String {
+        self.paragraphs()
+            .map(|p| match heading_level(heading_styles, p) {
+                Some(level) => format!("{} {}\n", "#".repeat(level), p.text()),
+                None => p.text() + "\n",
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// The level from 1 of a paragraph of at most three lines set in one of the
+// heading styles.
+pub fn heading_level(heading_styles: &[(f64, bool)], paragraph: &Paragraph) -> Option<usize> {
+    if paragraph.lines.len() > 3 {
+        return None;
+    }
+    let style = paragraph.style();
+    heading_styles
+        .iter()
+        .position(|s| *s == style)
+        .map(|i| i + 1)
+}
+
+// Sizes and weights that set text apart from the body text of the pages:
+// larger sizes, and bold at the body size when the body is not bold.
+// Larger sizes come first, then bold before regular, at most six as
+// Markdown has heading levels.
+pub fn heading_styles(pages: &[PageLayout]) -> Vec<(f64, bool)> {
+    let mut styles: Vec<((f64, bool), usize)> = Vec::new();
+    for paragraph in pages.iter().flat_map(|p| p.paragraphs()) {
+        let style = paragraph.style();
+        let count = paragraph.text().chars().count();
+        match styles.iter_mut().find(|(s, _)| *s == style) {
+            Some((_, c)) => *c += count,
+            None => styles.push((style, count)),
+        }
+    }
+    let Some((body, body_bold)) = styles.iter().max_by_key(|(_, c)| *c).map(|(s, _)| *s) else {
+        return Vec::new();
+    };
+    let mut headings: Vec<(f64, bool)> = styles
+        .into_iter()
+        .map(|(s, _)| s)
+        .filter(|(size, bold)| *size > body * 1.15 || (*size >= body && *bold && !body_bold))
+        .collect();
+    headings.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.cmp(&a.1)));
+    headings.truncate(6);
+    headings
+}
+
+fn overlap(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.1.min(b.1) - a.0.max(b.0)
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutAnalyzer {
+    // gaps between glyphs wider than this many ems split words
+    pub word_gap: f64,
+    // gaps between words wider than this many ems split columns
+    pub column_gap: f64,
+    // lines further apart than this many ems are in different paragraphs
+    pub max_line_spacing: f64,
+}
+
+impl Default for LayoutAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LayoutAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            word_gap: 0.15,
+            column_gap: 1.2,
+            max_line_spacing: 1.6,
+        }
+    }
+    pub fn analyze(&self, texts: &[PositionedText]) -> PageLayout {
+        let lines = self.lines(self.words(texts));
+        let paragraphs = self.paragraphs(lines);
+        PageLayout {
+            columns: self.columns(paragraphs),
+        }
+    }
+    // Glyphs in content order are joined into words until white space, a
+    // gap or a change of baseline.
+    fn words(&self, texts: &[PositionedText]) -> Vec<Word> {
+        let mut words: Vec<Word> = Vec::new();
+        let mut split = true;
+        for text in texts {
+            if let RenderingMode::AddClippingPath = text.rendering_mode {
+                continue;
+            }
+            for glyph in &text.glyphs {
+                if glyph.text.trim().is_empty() {
+                    split = true;
+                    continue;
+                }
+                match words.last_mut() {
+                    Some(word)
+                        if !split
+                            && (word.baseline - glyph.origin.y).abs() <= 0.2 * text.size
+                            && glyph.bbox.x0 - word.bbox.x1 <= self.word_gap * text.size
+                            && glyph.bbox.x0 - word.bbox.x1 >= -text.size =>
+                    {
+                        word.text.push_str(&glyph.text);
+                        word.bbox = word.bbox.union(glyph.bbox);
+                        word.size = word.size.max(text.size);
+                        word.bold &= text.bold;
+                    }
+                    _ => words.push(Word {
+                        text: glyph.text.clone(),
+                        bbox: glyph.bbox,
+                        size: text.size,
+                        bold: text.bold,
+                        baseline: glyph.origin.y,
+                    }),
+                }
+                split = false;
+            }
+        }
+        words
+    }
+    // Words on a baseline, split where the gap between them is wide enough
+    // to be a column gutter, from the top of the page.
+    fn lines(&self, mut words: Vec<Word>) -> Vec<Line> {
+        words.sort_by(|a, b| b.baseline.total_cmp(&a.baseline));
+        let mut rows: Vec<Vec<Word>> = Vec::new();
+        for word in words {
+            match rows.last_mut() {
+                Some(row)
+                    if (row[0].baseline - word.baseline).abs()
+                        <= 0.3 * row[0].size.max(word.size) =>
+                {
+                    row.push(word)
+                }
+                _ => rows.push(vec![word]),
+            }
+        }
+        let mut lines = Vec::new();
+        for mut row in rows {
+            row.sort_by(|a, b| a.bbox.x0.total_cmp(&b.bbox.x0));
+            let mut segment: Vec<Word> = Vec::new();
+            for word in row {
+                if let Some(last) = segment.last() {
+                    if word.bbox.x0 - last.bbox.x1 > self.column_gap * last.size.max(word.size) {
+                        lines.push(Line::new(std::mem::take(&mut segment)));
+                    }
+                }
+                segment.push(word);
+            }
+            lines.push(Line::new(segment));
+        }
+        lines
+    }
+    // Each line continues the paragraph whose last line is just above it,
+    // in the same size, unless it is further away than the paragraph's
+    // spacing so far or starts with an indent.
+    fn paragraphs(&self, lines: Vec<Line>) -> Vec<Paragraph> {
+        let mut paragraphs: Vec<Paragraph> = Vec::new();
+        for line in lines {
+            let continued = paragraphs.iter().rposition(|p| {
+                let last = p.lines.last().unwrap();
+                let distance = last.baseline - line.baseline;
+                let size = last.size.max(line.size);
+                let spacing = match &p.lines[..] {
+                    [.., before, last] => before.baseline - last.baseline,
+                    _ => self.max_line_spacing * size,
+                };
+                overlap((last.bbox.x0, last.bbox.x1), (line.bbox.x0, line.bbox.x1)) > 0.
+                    && distance > 0.3 * size
+                    && distance <= (1.2 * spacing).min(self.max_line_spacing * size)
+                    && (last.size - line.size).abs() <= 0.1 * size
+                    && line.bbox.x0 - p.bbox.x0 <= size
+            });
+            match continued {
+                Some(i) => {
+                    let paragraph = &mut paragraphs[i];
+                    paragraph.bbox = paragraph.bbox.union(line.bbox);
+                    paragraph.lines.push(line);
+                }
+                None => paragraphs.push(Paragraph {
+                    bbox: line.bbox,
+                    lines: vec![line],
+                }),
+            }
+        }
+        paragraphs
+    }
+    // Orders paragraphs by recursive cuts along the widest gap of the
+    // region: gaps running down it separate columns read left to right,
+    // gaps across it parts read top to bottom. Gaps between columns are
+    // preferred unless a gap across is more than twice as wide.
+    fn columns(&self, paragraphs: Vec<Paragraph>) -> Vec<Column> {
+        if paragraphs.is_empty() {
+            return Vec::new();
+        }
+        let span = |vertical: bool, p: &Paragraph| {
+            if vertical {
+                (p.bbox.x0, p.bbox.x1)
+            } else {
+                (-p.bbox.y1, -p.bbox.y0)
+            }
+        };
+        // the middle and width of the widest gap
+        let widest = |vertical: bool| {
+            let mut spans: Vec<(f64, f64)> = paragraphs.iter().map(|p| span(vertical, p)).collect();
+            spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut end = spans[0].1;
+            let mut widest: Option<(f64, f64)> = None;
+            for s in &spans[1..] {
+                if s.0 > end && widest.is_none_or(|(_, width)| s.0 - end > width) {
+                    widest = Some(((end + s.0) / 2., s.0 - end));
+                }
+                end = end.max(s.1);
+            }
+            widest
+        };
+        let cut = match (widest(true), widest(false)) {
+            (Some(down), Some(across)) if across.1 > 2. * down.1 => Some((false, across.0)),
+            (Some(down), _) => Some((true, down.0)),
+            (None, Some(across)) => Some((false, across.0)),
+            (None, None) => None,
+        };
+        if let Some((vertical, cut)) = cut {
+            let (first, rest): (Vec<Paragraph>, Vec<Paragraph>) = paragraphs
+                .into_iter()
+                .partition(|p| span(vertical, p).1 < cut);
+            let mut columns = self.columns(first);
+            let rest = self.columns(rest);
+            // the parts of a column split by a gap across it stay one column
+            match (vertical, columns.last_mut(), rest.first()) {
+                (false, Some(last), Some(next))
+                    if overlap((last.bbox.x0, last.bbox.x1), (next.bbox.x0, next.bbox.x1))
+                        > 0.5 * last.bbox.width().max(next.bbox.width()) =>
+                {
+                    let mut rest = rest.into_iter();
+                    let next = rest.next().unwrap();
+                    last.bbox = last.bbox.union(next.bbox);
+                    last.paragraphs.extend(next.paragraphs);
+                    columns.extend(rest);
+                }
+                _ => columns.extend(rest),
+            }
+            return columns;
+        }
+        let mut paragraphs = paragraphs;
+        paragraphs.sort_by(|a, b| b.bbox.y1.total_cmp(&a.bbox.y1));
+        vec![Column {
+            bbox: paragraphs
+                .iter()
+                .map(|p| p.bbox)
+                .reduce(|a, b| a.union(b))
+                .unwrap(),
+            paragraphs,
+        }]
+    }
+}
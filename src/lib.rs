@@ -1,7 +1,61 @@
+mod annotation;
+mod cleanup;
+mod content;
+mod contrast;
+mod crop;
+mod darkmode;
+mod decoration;
+mod font;
+mod function;
+mod furniture;
+mod glyph;
+mod grayscale;
+mod image;
+mod inventory;
+mod layout;
+mod numbering;
+mod outline;
+mod recolor;
+mod redact;
+mod replace;
+mod search;
+mod serialize;
+mod stamp;
 mod state;
+mod table;
+#[cfg(test)]
+mod testing;
 mod transform;
 mod util;
+mod vectorize;
+mod vision;
 
+pub use crate::annotation::*;
+pub use crate::cleanup::*;
+pub use crate::content::*;
+pub use crate::contrast::*;
+pub use crate::crop::*;
+pub use crate::darkmode::*;
+pub use crate::decoration::*;
+pub use crate::font::*;
+pub use crate::function::*;
+pub use crate::furniture::*;
+pub use crate::glyph::*;
+pub use crate::grayscale::*;
+pub use crate::image::*;
+pub use crate::inventory::*;
+pub use crate::layout::*;
+pub use crate::numbering::*;
+pub use crate::outline::*;
+pub use crate::recolor::*;
+pub use crate::redact::*;
+pub use crate::replace::*;
+pub use crate::search::*;
+pub use crate::serialize::*;
+pub use crate::stamp::*;
 pub use crate::state::*;
+pub use crate::table::*;
 pub use crate::transform::*;
 pub use crate::util::*;
+pub use crate::vectorize::*;
+pub use crate::vision::*;
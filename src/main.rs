@@ -3,14 +3,19 @@ use lopdf::content::Operation;
 use lopdf::Object;
 use pdf_console_editor::*;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 mod argparse;
 use argparse::*;
+use pdf_console_editor::graphics::color::convert::ColorConverter;
 use pdf_console_editor::graphics::color::Color;
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(short, long)]
     rectangle: bool,
     #[arg(short, long, value_parser=ArgRange::parser, default_value_t=ArgRange::default())]
@@ -22,14 +27,691 @@ struct Cli {
     #[arg(short, long)]
     background_color: bool,
 
-    input: std::path::PathBuf,
-    output: std::path::PathBuf,
+    /// Replace a color: FROM=TO[:target] where target is text-fill, text-stroke,
+    /// path-fill, path-stroke, text, path, fill, stroke or all (the default)
+    #[arg(short, long, value_parser=ArgColorRule::parser)]
+    map_color: Vec<ArgColorRule>,
+
+    /// How close colors must be to match: a CIEDE2000 difference (2.0 or de:2.0)
+    /// or a per-channel RGB difference (channel:0.02)
+    #[arg(short, long, value_parser=ArgTolerance::parser, default_value_t=ArgTolerance::default())]
+    tolerance: ArgTolerance,
+    /// How colors are converted for matching within a tolerance: naive,
+    /// document (the profile of its output intent) or the path of an ICC
+    /// profile. Exact matching compares the colors as written
+    #[arg(long, value_parser=ArgColorProfile::parser, default_value_t=ArgColorProfile::Document)]
+    color_profile: ArgColorProfile,
+    /// Replace spot colors not matched by a rule with the process color
+    /// their ink prints as
+    #[arg(long)]
+    spot_to_process: bool,
+
+    /// Replace text wherever it is shown, in the font it is shown with
+    #[arg(long, num_args = 2, value_names = ["FIND", "REPLACEMENT"])]
+    replace_text: Vec<String>,
+
+    /// Add a highlight annotation over every match of a regular expression
+    #[arg(long, value_parser=regex::Regex::new)]
+    highlight: Vec<regex::Regex>,
+    /// Color of the highlight annotations
+    #[arg(long, value_parser=ArgColor::parser, default_value = "yellow", requires = "highlight")]
+    highlight_color: ArgColor,
+    /// Author recorded in the highlight annotations
+    #[arg(long, requires = "highlight")]
+    highlight_author: Option<String>,
+
+    /// Remove, recolor or convert to annotations underlines, strike-throughs and highlights
+    #[arg(long, value_enum)]
+    decorations: Option<ArgDecorationAction>,
+    /// Decoration kinds to handle (all when omitted)
+    #[arg(long, value_enum, value_delimiter = ',', requires = "decorations")]
+    decoration_kinds: Vec<ArgDecorationKind>,
+    /// Color used by recolor and annotate (annotations keep the original color when omitted)
+    #[arg(long, value_parser=ArgColor::parser, requires = "decorations",
+          required_if_eq("decorations", "recolor"))]
+    decoration_color: Option<ArgColor>,
+
+    /// Remove headers, footers and watermarks repeated across pages, or mark
+    /// them as artifacts
+    #[arg(long, value_enum)]
+    furniture: Option<ArgFurnitureAction>,
+    /// Kinds of repeated content to handle (all when omitted)
+    #[arg(long, value_enum, value_delimiter = ',', requires = "furniture")]
+    furniture_kinds: Vec<ArgFurnitureKind>,
+
+    /// Remove content that cannot be seen and report it per page
+    #[arg(long)]
+    remove_invisible: bool,
+    /// Replace text with the outlines of its glyphs, read from the embedded fonts
+    #[arg(long)]
+    outline_text: bool,
+
+    /// Produce a dark theme: remap keeps hues and reverses lightness, invert
+    /// inverts every component
+    #[arg(long, value_enum)]
+    dark_mode: Option<ArgDarkStyle>,
+    /// Page background painted beneath the content in dark mode
+    #[arg(long, value_parser=ArgColor::parser, requires = "dark_mode")]
+    dark_background: Option<ArgColor>,
+    /// What black becomes with the remap style
+    #[arg(long, value_parser=ArgColor::parser, requires = "dark_mode")]
+    dark_foreground: Option<ArgColor>,
+    /// Brightness left to images in dark mode, from 0 to 1 (untouched)
+    #[arg(long, value_parser=unit_parser, default_value_t = 1., requires = "dark_mode")]
+    dim_images: f32,
+
+    /// Show vector and text colors as they look with a color vision deficiency
+    #[arg(long, value_enum)]
+    simulate_cvd: Option<ArgDeficiency>,
+    /// Replace colors that look alike with a color vision deficiency by
+    /// colors from a color-blind-safe palette
+    #[arg(long, value_enum)]
+    cvd_safe: Option<ArgDeficiency>,
+
+    /// Convert all colours, shadings and images to DeviceGray
+    #[arg(long)]
+    grayscale: bool,
+
+    /// Set the crop box to the bounding box of the painted content
+    #[arg(long)]
+    auto_crop: bool,
+    /// Space left around the content when cropping
+    #[arg(long, default_value_t = 0., requires = "auto_crop")]
+    crop_margin: f64,
+
+    /// Stamp text such as DRAFT onto the pages
+    #[arg(long, group = "stamp")]
+    stamp_text: Option<String>,
+    /// Stamp a JPEG image, or a page of a PDF, onto the pages
+    #[arg(long, group = "stamp")]
+    stamp_file: Option<std::path::PathBuf>,
+    /// Page of the PDF given with --stamp-file
+    #[arg(long, default_value_t = 1, requires = "stamp_file")]
+    stamp_file_page: usize,
+    /// Standard font of the stamp text
+    #[arg(long, default_value = "Helvetica", requires = "stamp_text",
+          value_parser = clap::builder::PossibleValuesParser::new(STANDARD_FONTS))]
+    stamp_font: String,
+    /// Font size of the stamp text
+    #[arg(long, default_value_t = 48., requires = "stamp_text")]
+    stamp_size: f64,
+    #[arg(long, value_parser=ArgColor::parser, default_value = "gray", requires = "stamp_text")]
+    stamp_color: ArgColor,
+    /// Opacity of the stamp, from 0 to 1
+    #[arg(long, value_parser=unit_parser, default_value_t = 1., requires = "stamp")]
+    stamp_opacity: f32,
+    /// Counterclockwise rotation of the stamp in degrees
+    #[arg(long, default_value_t = 0., requires = "stamp")]
+    stamp_rotation: f64,
+    /// center, top, bottom, left, right, top-left, top-right, bottom-left,
+    /// bottom-right, or the X,Y of the stamp's centre in points
+    #[arg(long, value_parser=ArgStampPosition::parser, default_value = "center", requires = "stamp")]
+    stamp_position: ArgStampPosition,
+    /// Space kept between the stamp and the page edges
+    #[arg(long, default_value_t = 36., requires = "stamp")]
+    stamp_margin: f64,
+    /// Width the stamp is scaled to, in points
+    #[arg(long, requires = "stamp")]
+    stamp_width: Option<f64>,
+    /// Paint the stamp beneath the page content
+    #[arg(long, requires = "stamp")]
+    stamp_underlay: bool,
+    /// Pages to stamp, such as 1,3-5,8- (all when omitted)
+    #[arg(long, value_parser=ArgPages::parser, requires = "stamp")]
+    stamp_pages: Option<ArgPages>,
+
+    /// Write an outline of the headings found by font size and weight,
+    /// unless the document has one
+    #[arg(long)]
+    add_outline: bool,
+
+    #[arg(required = true)]
+    input: Option<std::path::PathBuf>,
+    #[arg(required = true)]
+    output: Option<std::path::PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Extract tables drawn with ruling lines
+    Tables {
+        #[arg(short, long, value_enum, default_value_t=ArgTableFormat::Csv)]
+        format: ArgTableFormat,
+        /// Filled rectangles thinner than this are taken as ruling lines
+        #[arg(long, default_value_t = 3.)]
+        max_thickness: f64,
+
+        input: std::path::PathBuf,
+        /// Written to stdout when omitted
+        output: Option<std::path::PathBuf>,
+    },
+    /// Export the text of each page with its position, font and color, or
+    /// as paragraphs in reading order
+    Text {
+        #[arg(short, long, value_enum, default_value_t=ArgTextFormat::Json)]
+        format: ArgTextFormat,
+
+        input: std::path::PathBuf,
+        /// Written to stdout when omitted
+        output: Option<std::path::PathBuf>,
+    },
+    /// Report what a document contains
+    Inspect {
+        #[command(subcommand)]
+        target: InspectTarget,
+    },
+    /// Check a document against accessibility guidelines
+    Audit {
+        #[command(subcommand)]
+        target: AuditTarget,
+    },
+    /// Number the pages of one or more documents, with a counter running
+    /// across them as for Bates numbering
+    Number {
+        /// Text of each number, with fields in braces: n is the counter, page
+        /// and total count the pages of the document, label is the page
+        /// label; a width such as {n:6} pads with zeros
+        #[arg(long, default_value = "{page}", value_parser=NumberTemplate::parse)]
+        format: NumberTemplate,
+        /// First value of the counter
+        #[arg(long, default_value_t = 1)]
+        start: u64,
+        #[arg(long, default_value = "Helvetica",
+              value_parser = clap::builder::PossibleValuesParser::new(STANDARD_FONTS))]
+        font: String,
+        #[arg(long, default_value_t = 10.)]
+        size: f64,
+        #[arg(long, value_parser=ArgColor::parser, default_value = "black")]
+        color: ArgColor,
+        /// center, top, bottom, left, right, top-left, top-right, bottom-left,
+        /// bottom-right, or the X,Y of the number's centre in points
+        #[arg(long, value_parser=ArgStampPosition::parser, default_value = "bottom-right")]
+        position: ArgStampPosition,
+        /// Space kept between the number and the page edges
+        #[arg(long, default_value_t = 36.)]
+        margin: f64,
+        /// Directory the numbered documents are written to, under their own
+        /// names
+        #[arg(long, required = true)]
+        output_dir: std::path::PathBuf,
+
+        #[arg(required = true)]
+        inputs: Vec<std::path::PathBuf>,
+    },
+    /// Remove the text, paths and image pixels under areas and cover them
+    Redact {
+        /// Area to redact, in points: [PAGE:]X0,Y0,X1,Y1, on every page when
+        /// PAGE is omitted
+        #[arg(long = "rect", value_parser=ArgPageRect::parser)]
+        rects: Vec<ArgPageRect>,
+        /// Redact wherever this text is shown
+        #[arg(long = "text")]
+        texts: Vec<String>,
+        /// Colour painted over the redacted areas
+        #[arg(long, value_parser=ArgColor::parser, default_value = "black")]
+        fill: ArgColor,
+        /// Leave the redacted areas unpainted
+        #[arg(long, conflicts_with = "fill")]
+        no_fill: bool,
+
+        input: std::path::PathBuf,
+        output: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum InspectTarget {
+    /// List the colours used for text, paths and stencil masks
+    Colors {
+        #[arg(short, long, value_enum, default_value_t=ArgReportFormat::Text)]
+        format: ArgReportFormat,
+
+        input: std::path::PathBuf,
+        /// Written to stdout when omitted
+        output: Option<std::path::PathBuf>,
+    },
+    /// List headers, footers and watermarks repeated across pages
+    Furniture {
+        input: std::path::PathBuf,
+        /// Written to stdout when omitted
+        output: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AuditTarget {
+    /// Report text whose contrast with its background is below the WCAG level
+    Contrast {
+        #[arg(short, long, value_enum, default_value_t=ArgContrastLevel::Aa)]
+        level: ArgContrastLevel,
+        #[arg(short, long, value_enum, default_value_t=ArgReportFormat::Text)]
+        format: ArgReportFormat,
+        /// Write a copy with the failing text darkened, or lightened on dark
+        /// backgrounds, until it passes
+        #[arg(long)]
+        fix: Option<std::path::PathBuf>,
+
+        input: std::path::PathBuf,
+        /// Written to stdout when omitted
+        output: Option<std::path::PathBuf>,
+    },
 }
 
 fn main() {
     let args = Cli::parse();
-    let mut modifier = PdfModifier::new(&args.input).unwrap();
-    for page_id in modifier.pages() {
+    match &args.command {
+        Some(Command::Tables {
+            format,
+            max_thickness,
+            input,
+            output,
+        }) => tables(format, *max_thickness, input, output.as_ref()),
+        Some(Command::Text {
+            format,
+            input,
+            output,
+        }) => text(format, input, output.as_ref()),
+        Some(Command::Inspect {
+            target:
+                InspectTarget::Colors {
+                    format,
+                    input,
+                    output,
+                },
+        }) => inspect_colors(format, input, output.as_ref()),
+        Some(Command::Inspect {
+            target: InspectTarget::Furniture { input, output },
+        }) => inspect_furniture(input, output.as_ref()),
+        Some(Command::Audit {
+            target:
+                AuditTarget::Contrast {
+                    level,
+                    format,
+                    fix,
+                    input,
+                    output,
+                },
+        }) => audit_contrast(level, format, fix.as_ref(), input, output.as_ref()),
+        Some(Command::Number {
+            format,
+            start,
+            font,
+            size,
+            color,
+            position,
+            margin,
+            output_dir,
+            inputs,
+        }) => {
+            let numbering = PageNumbering {
+                font: font.clone(),
+                size: *size,
+                color: color.0.clone(),
+                position: position.0.clone(),
+                margin: *margin,
+                ..PageNumbering::new(format.clone())
+            };
+            number(&numbering, *start, output_dir, inputs)
+        }
+        Some(Command::Redact {
+            rects,
+            texts,
+            fill,
+            no_fill,
+            input,
+            output,
+        }) => redact(rects, texts, fill, *no_fill, input, output),
+        None => edit(&args),
+    }
+}
+
+fn write_output(output: Option<&std::path::PathBuf>, content: &str) {
+    match output {
+        Some(path) => std::fs::write(path, content).unwrap(),
+        None => print!("{}", content),
+    }
+}
+
+fn tables(
+    format: &ArgTableFormat,
+    max_thickness: f64,
+    input: &std::path::PathBuf,
+    output: Option<&std::path::PathBuf>,
+) {
+    let mut modifier = PdfModifier::new(input).unwrap();
+    let detector = TableDetector {
+        max_thickness,
+        ..TableDetector::new()
+    };
+    let mut found: Vec<(usize, Table)> = Vec::new();
+    for (page_number, page_id) in modifier.pages().into_iter().enumerate() {
+        found.extend(
+            detector
+                .detect(&mut modifier, page_id)
+                .into_iter()
+                .map(|table| (page_number + 1, table)),
+        );
+    }
+    let content = match format {
+        ArgTableFormat::Csv => found
+            .iter()
+            .map(|(_, table)| table.to_csv())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ArgTableFormat::Json => {
+            let tables: Vec<serde_json::Value> = found
+                .iter()
+                .map(|(page, table)| {
+                    let mut value = table.to_json();
+                    value["page"] = serde_json::json!(page);
+                    value
+                })
+                .collect();
+            serde_json::to_string_pretty(&tables).unwrap() + "\n"
+        }
+    };
+    write_output(output, &content);
+}
+
+fn text(format: &ArgTextFormat, input: &std::path::PathBuf, output: Option<&std::path::PathBuf>) {
+    let mut modifier = PdfModifier::new(input).unwrap();
+    let mut export = TextExport::new();
+    for (page_number, page_id) in modifier.pages().into_iter().enumerate() {
+        export.add_page(&mut modifier, page_id, page_number + 1);
+    }
+    let content = match format {
+        ArgTextFormat::Json => serde_json::to_string_pretty(&export.to_json()).unwrap() + "\n",
+        ArgTextFormat::Hocr => export.to_hocr(),
+        ArgTextFormat::Tsv => export.to_tsv(),
+        ArgTextFormat::Plain | ArgTextFormat::Markdown => {
+            let analyzer = LayoutAnalyzer::new();
+            let layouts: Vec<PageLayout> = export
+                .pages
+                .iter()
+                .map(|page| analyzer.analyze(&page.runs))
+                .collect();
+            let heading_styles = heading_styles(&layouts);
+            let pages: Vec<String> = layouts
+                .iter()
+                .map(|layout| match format {
+                    ArgTextFormat::Markdown => layout.to_markdown(&heading_styles),
+                    _ => layout.to_text(),
+                })
+                .collect();
+            // pages are separated by form feeds in plain text
+            match format {
+                ArgTextFormat::Markdown => pages.join("\n"),
+                _ => pages.join("\x0c"),
+            }
+        }
+    };
+    write_output(output, &content);
+}
+
+fn inspect_colors(
+    format: &ArgReportFormat,
+    input: &std::path::PathBuf,
+    output: Option<&std::path::PathBuf>,
+) {
+    let mut modifier = PdfModifier::new(input).unwrap();
+    let mut inventory = ColorInventory::new();
+    for (page_number, page_id) in modifier.pages().into_iter().enumerate() {
+        inventory.add_page(&mut modifier, page_id, page_number + 1);
+    }
+    inventory.sort();
+    let content = match format {
+        ArgReportFormat::Text => inventory.to_string(),
+        ArgReportFormat::Json => serde_json::to_string_pretty(&inventory.to_json()).unwrap() + "\n",
+    };
+    write_output(output, &content);
+}
+
+fn inspect_furniture(input: &std::path::PathBuf, output: Option<&std::path::PathBuf>) {
+    let mut modifier = PdfModifier::new(input).unwrap();
+    let report = FurnitureDetector::new().detect(&mut modifier);
+    write_output(output, &report.to_string());
+}
+
+fn audit_contrast(
+    level: &ArgContrastLevel,
+    format: &ArgReportFormat,
+    fix: Option<&std::path::PathBuf>,
+    input: &std::path::PathBuf,
+    output: Option<&std::path::PathBuf>,
+) {
+    let mut modifier = PdfModifier::new(input).unwrap();
+    let converter = ColorConverter::from_document(modifier.document());
+    let checker = ContrastChecker {
+        level: level.to_level(),
+        fix: fix.is_some(),
+    };
+    let mut report = ContrastReport::default();
+    for (page_number, page_id) in modifier.pages().into_iter().enumerate() {
+        checker.check(
+            &mut modifier,
+            page_id,
+            page_number + 1,
+            &converter,
+            &mut report,
+        );
+    }
+    let content = match format {
+        ArgReportFormat::Text => report.to_string(),
+        ArgReportFormat::Json => serde_json::to_string_pretty(&report.to_json()).unwrap() + "\n",
+    };
+    write_output(output, &content);
+    if let Some(path) = fix {
+        modifier.save(path);
+    }
+}
+
+fn number(
+    numbering: &PageNumbering,
+    start: u64,
+    output_dir: &std::path::Path,
+    inputs: &[std::path::PathBuf],
+) {
+    // the outputs take the file names of the inputs
+    let mut names = std::collections::HashSet::new();
+    for input in inputs {
+        if !input.file_name().is_some_and(|name| names.insert(name)) {
+            eprintln!(
+                "{}: another input has the same file name in {}",
+                input.display(),
+                output_dir.display()
+            );
+            std::process::exit(2);
+        }
+    }
+    let mut counter = start;
+    for input in inputs {
+        let mut modifier = PdfModifier::new(input).unwrap();
+        let texts = numbering
+            .apply(&mut modifier, &mut counter)
+            .unwrap_or_else(|e| {
+                eprintln!("{}: {}", input.display(), e);
+                std::process::exit(2);
+            });
+        let output = output_dir.join(input.file_name().unwrap());
+        if output.canonicalize().ok() == input.canonicalize().ok() {
+            eprintln!("{}: would overwrite the input", output.display());
+            std::process::exit(2);
+        }
+        modifier.save(&output);
+        match (texts.first(), texts.last()) {
+            (Some(first), Some(last)) => println!("{}: {} - {}", output.display(), first, last),
+            _ => println!("{}: no pages", output.display()),
+        }
+    }
+}
+
+fn redact(
+    rects: &[ArgPageRect],
+    texts: &[String],
+    fill: &ArgColor,
+    no_fill: bool,
+    input: &std::path::PathBuf,
+    output: &std::path::PathBuf,
+) {
+    let mut modifier = PdfModifier::new(input).unwrap();
+    let redactor = Redactor {
+        fill: (!no_fill).then(|| fill.0.clone()),
+    };
+    for (page_number, page_id) in modifier.pages().into_iter().enumerate() {
+        let mut areas: Vec<kurbo::Rect> = rects
+            .iter()
+            .filter(|r| r.page.is_none_or(|p| p == page_number + 1))
+            .map(|r| r.rect)
+            .collect();
+        for text in texts.iter().filter(|t| !t.is_empty()) {
+            let matches = text_matches(&mut modifier, page_id, |page_text| {
+                page_text
+                    .match_indices(text.as_str())
+                    .map(|(i, m)| i..i + m.len())
+                    .collect()
+            });
+            areas.extend(matches.into_iter().flat_map(|m| m.rects));
+        }
+        if areas.is_empty() {
+            continue;
+        }
+        let report = redactor.redact(&mut modifier, page_id, &areas);
+        println!("page {}: redacted {}", page_number + 1, report);
+        for removed in report.removed {
+            println!("  removed {}", removed);
+        }
+    }
+    // the original content and XObjects are no longer referenced
+    modifier.document_mut().prune_objects();
+    modifier.save(output);
+}
+
+fn edit(args: &Cli) {
+    let mut modifier = PdfModifier::new(args.input.as_ref().unwrap()).unwrap();
+    let converter = args
+        .color_profile
+        .converter(modifier.document())
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+    let dark_mode = args.dark_mode.as_ref().map(|style| {
+        let default = DarkMode::new();
+        DarkMode {
+            style: style.to_style(),
+            background: args
+                .dark_background
+                .as_ref()
+                .map_or(default.background, |c| c.0.clone()),
+            foreground: args
+                .dark_foreground
+                .as_ref()
+                .map_or(default.foreground, |c| c.0.clone()),
+            image_brightness: args.dim_images,
+        }
+    });
+    let mut rules = args.map_color.clone();
+    if !args.background_color {
+        // text given with --colored-text is made blue
+        rules.extend(args.colored_text.iter().map(|c| ArgColorRule {
+            from: c.0.clone(),
+            to: Color::RGB(0., 0., 1.),
+            targets: vec![Paint::TextFill],
+        }));
+    }
+    if let Some(deficiency) = &args.cvd_safe {
+        let mut inventory = ColorInventory::new();
+        for (page_number, page_id) in modifier.pages().into_iter().enumerate() {
+            inventory.add_page(&mut modifier, page_id, page_number + 1);
+        }
+        inventory.sort();
+        let vision = ColorVision::new(deficiency.to_deficiency());
+        for (from, to) in vision.safe_mapping(&converter, &inventory) {
+            println!("cvd-safe: {} -> {}", from, to);
+            rules.push(ArgColorRule {
+                from,
+                to,
+                targets: Paint::ALL.to_vec(),
+            });
+        }
+    }
+    let map_colors = !rules.is_empty() || args.spot_to_process;
+    let mut map_color = |color: &Color, paint: Paint| {
+        rules
+            .iter()
+            .find(|rule| {
+                rule.targets.contains(&paint)
+                    && converter.matches(&rule.from, color, &args.tolerance.0)
+            })
+            .map(|rule| rule.to.clone())
+            .or_else(|| match color {
+                Color::Spot(..) if args.spot_to_process => Some(converter.process(color)),
+                _ => None,
+            })
+    };
+    let simulation = args
+        .simulate_cvd
+        .as_ref()
+        .map(|deficiency| ColorVision::new(deficiency.to_deficiency()));
+    let replacements: Vec<TextReplacement> = args
+        .replace_text
+        .chunks(2)
+        .map(|pair| TextReplacement {
+            find: pair[0].clone(),
+            replace: pair[1].clone(),
+        })
+        .collect();
+    let mut replace_report = ReplaceReport::default();
+    let highlighters: Vec<Highlighter> = args
+        .highlight
+        .iter()
+        .map(|pattern| Highlighter {
+            color: args.highlight_color.0.clone(),
+            author: args.highlight_author.clone(),
+            ..Highlighter::new(pattern.clone())
+        })
+        .collect();
+    let mut highlighted = 0;
+    let mut outline_report = VectorizeReport::default();
+    let furniture = args.furniture.as_ref().map(|action| {
+        let report = FurnitureDetector::new().detect(&mut modifier);
+        let kinds: Vec<FurnitureKind> = if args.furniture_kinds.is_empty() {
+            vec![
+                FurnitureKind::Header,
+                FurnitureKind::Footer,
+                FurnitureKind::Watermark,
+            ]
+        } else {
+            args.furniture_kinds.iter().map(|k| k.to_kind()).collect()
+        };
+        for line in report.to_string().lines() {
+            println!("furniture: {}", line);
+        }
+        (action, report, kinds)
+    });
+    for (page_number, page_id) in modifier.pages().into_iter().enumerate() {
+        if let Some((action, report, kinds)) = &furniture {
+            match action {
+                ArgFurnitureAction::Remove => {
+                    report.remove(&mut modifier, page_id, page_number, kinds)
+                }
+                ArgFurnitureAction::Artifact => {
+                    report.mark_artifacts(&mut modifier, page_id, page_number, kinds)
+                }
+            }
+        }
+        for replacement in &replacements {
+            replace_text(
+                &mut modifier,
+                page_id,
+                page_number + 1,
+                replacement,
+                &mut replace_report,
+            );
+        }
+        for highlighter in &highlighters {
+            highlighted += highlighter.highlight(&mut modifier, page_id);
+        }
         let mut paths: Vec<(usize, BezPath, Color)> = Vec::new();
         let mut objects: Vec<(usize, Point)> = Vec::new();
         if args.background_color {
@@ -84,14 +766,16 @@ fn main() {
                             let t = state.graphics.text.line_matrix.translation();
                             *i < state.id && path.contains(t.to_point())
                         }) {
-                            background.2.equals_to(&state.graphics.color.non_stroke)
+                            converter.matches(
+                                &background.2,
+                                &state.graphics.color.non_stroke,
+                                &args.tolerance.0,
+                            )
                         } else {
                             false
                         }
                     } else {
-                        args.colored_text
-                            .iter()
-                            .any(|c| c.0.equals_to(&state.graphics.color.non_stroke))
+                        false
                     };
                     if need_replace {
                         vec![
@@ -100,8 +784,10 @@ fn main() {
                                 vec![Object::from(0.), Object::from(0.), Object::from(1.)],
                             ),
                             operation,
-                            state.graphics.color.operator_non_stroke(),
                         ]
+                        .into_iter()
+                        .chain(state.graphics.color.operators(false))
+                        .collect()
                     } else {
                         vec![operation]
                     }
@@ -111,6 +797,202 @@ fn main() {
                 }
             },
         );
+        if map_colors {
+            rewrite_colors(&mut modifier, page_id, &mut map_color);
+        }
+        if let Some(action) = &args.decorations {
+            let kinds: Vec<DecorationKind> = args
+                .decoration_kinds
+                .iter()
+                .map(ArgDecorationKind::to_kind)
+                .collect();
+            let decorations: Vec<Decoration> = DecorationDetector::new()
+                .detect(&mut modifier, page_id)
+                .into_iter()
+                .filter(|d| kinds.is_empty() || kinds.contains(&d.kind))
+                .collect();
+            handle_decorations(
+                &mut modifier,
+                page_id,
+                &decorations,
+                action,
+                &args.decoration_color,
+            );
+        }
+        if let Some(dark_mode) = &dark_mode {
+            dark_mode.apply(&mut modifier, page_id, &converter);
+        }
+        if let Some(vision) = &simulation {
+            vision.apply(&mut modifier, page_id, &converter);
+        }
+        if args.remove_invisible {
+            let report = remove_invisible(&mut modifier, page_id);
+            println!("page {}: removed {}", page_number + 1, report);
+        }
+        if args.outline_text {
+            text_to_outlines(&mut modifier, page_id, &mut outline_report);
+        }
+        if args.auto_crop {
+            if let (Some(content), Some(page)) = (
+                content_bbox(&mut modifier, page_id),
+                modifier.visible_box(page_id),
+            ) {
+                let margin = args.crop_margin;
+                modifier.set_crop_box(page_id, content.inflate(margin, margin).intersect(page));
+            }
+        }
+    }
+    if !replacements.is_empty() {
+        println!("replace: {}", replace_report);
+        for failure in &replace_report.failures {
+            println!(
+                "  page {}: {:?} kept, {}",
+                failure.page, failure.text, failure.reason
+            );
+        }
+    }
+    if !highlighters.is_empty() {
+        println!("highlight: {} matches", highlighted);
+    }
+    if args.outline_text {
+        println!("outline-text: {}", outline_report);
+        for font in &outline_report.fonts {
+            println!("  kept text in {}", font);
+        }
+    }
+    if map_colors {
+        rewrite_stream_colors(&mut modifier, &mut map_color);
+    }
+    if let Some(dark_mode) = &dark_mode {
+        dark_mode.apply_to_forms(&mut modifier, &converter);
+        let pages = modifier.pages();
+        dark_mode.dim_images(&mut modifier, &pages);
+    }
+    if args.grayscale {
+        let report = convert_to_grayscale(&mut modifier, &converter);
+        println!("grayscale: {}", report);
+        for skipped in &report.skipped {
+            println!("  kept {}", skipped);
+        }
+    }
+    if let Some(content) = stamp_content(args) {
+        let stamp = Stamp {
+            opacity: args.stamp_opacity,
+            rotation: args.stamp_rotation,
+            position: args.stamp_position.0.clone(),
+            margin: args.stamp_margin,
+            width: args.stamp_width,
+            underlay: args.stamp_underlay,
+            ..Stamp::new(content)
+        };
+        let pages: Vec<(u32, u16)> = modifier
+            .pages()
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| args.stamp_pages.as_ref().is_none_or(|p| p.contains(i + 1)))
+            .map(|(_, page_id)| page_id)
+            .collect();
+        if let Err(e) = stamp.apply(&mut modifier, &pages) {
+            eprintln!("stamp: {}", e);
+            std::process::exit(2);
+        }
+    }
+    if args.add_outline {
+        if has_outline(modifier.document()) {
+            println!("outline: kept the outline the document has");
+        } else {
+            let headings = detect_headings(&mut modifier);
+            set_outline(modifier.document_mut(), &headings);
+            println!("outline: {} headings", headings.len());
+        }
+    }
+    modifier.save(args.output.as_ref().unwrap());
+}
+
+fn stamp_content(args: &Cli) -> Option<StampContent> {
+    if let Some(text) = &args.stamp_text {
+        return Some(StampContent::Text {
+            text: text.clone(),
+            font: args.stamp_font.clone(),
+            size: args.stamp_size,
+            color: args.stamp_color.0.clone(),
+        });
+    }
+    let path = args.stamp_file.as_ref()?;
+    let pdf = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+    let content = if pdf {
+        lopdf::Document::load(path)
+            .map(|doc| StampContent::Page(doc, args.stamp_file_page.saturating_sub(1)))
+            .map_err(|e| e.to_string())
+    } else {
+        std::fs::read(path)
+            .map(StampContent::Image)
+            .map_err(|e| e.to_string())
+    };
+    match content {
+        Ok(content) => Some(content),
+        Err(e) => {
+            eprintln!("stamp: {}: {}", path.display(), e);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn handle_decorations(
+    modifier: &mut PdfModifier,
+    page_id: (u32, u16),
+    decorations: &[Decoration],
+    action: &ArgDecorationAction,
+    color: &Option<ArgColor>,
+) {
+    match action {
+        ArgDecorationAction::Remove | ArgDecorationAction::Annotate => {
+            modifier.apply(page_id, &mut |operation, state| {
+                if decorations.iter().any(|d| d.id == state.id) {
+                    vec![Operation::new("n", vec![])]
+                } else {
+                    vec![operation]
+                }
+            })
+        }
+        ArgDecorationAction::Recolor => {
+            let color = &color.as_ref().unwrap().0;
+            recolor_operations(modifier, page_id, &mut |id, paint| {
+                decorations
+                    .iter()
+                    .any(|d| d.id == id && d.stroke == paint.is_stroke())
+                    .then(|| color.clone())
+            })
+        }
+    }
+    if let ArgDecorationAction::Annotate = action {
+        for decoration in decorations {
+            // markup annotations cover the decorated text, not the line itself
+            let quads: Vec<Quad> = match decoration.kind {
+                DecorationKind::Highlight => vec![rect_quad(decoration.rect)],
+                _ => decoration
+                    .text_rects
+                    .iter()
+                    .map(|t| {
+                        rect_quad(kurbo::Rect::new(
+                            decoration.rect.x0,
+                            t.y0,
+                            decoration.rect.x1,
+                            t.y1,
+                        ))
+                    })
+                    .collect(),
+            };
+            let color = color.as_ref().map_or(&decoration.color, |c| &c.0);
+            let annotation = markup_annotation(
+                modifier.document_mut(),
+                decoration.kind.annotation_subtype(),
+                &quads,
+                color,
+            );
+            modifier.add_annotation(page_id, annotation);
+        }
     }
-    modifier.save(&args.output);
 }
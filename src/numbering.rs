@@ -0,0 +1,303 @@
+use lopdf::{Dictionary, Document, Object};
+
+use crate::graphics::color::Color;
+use crate::{decode_text_string, PdfModifier, Stamp, StampContent, StampPosition};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    // the counter running across documents, zero padded to a width
+    Counter(usize),
+    Page(usize),
+    Total,
+    Label,
+}
+
+// A template such as `ACME{n:6}` or `Page {page} of {total}`. `{n}` is the
+// counter running across documents, `{page}` and `{total}` count the pages
+// of the document, `{label}` is the page label. A width after a colon pads
+// numbers with zeros; `{{` and `}}` stand for braces.
+#[derive(Debug, Clone)]
+pub struct NumberTemplate(Vec<Token>);
+
+impl NumberTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err("unclosed { in template".to_string()),
+                        }
+                    }
+                    let (name, width) = match field.split_once(':') {
+                        Some((name, width)) => (
+                            name,
+                            width
+                                .parse::<usize>()
+                                .map_err(|_| format!("invalid width in {{{}}}", field))?,
+                        ),
+                        None => (field.as_str(), 0),
+                    };
+                    let token = match name {
+                        "n" => Token::Counter(width),
+                        "page" => Token::Page(width),
+                        "total" => Token::Total,
+                        "label" => Token::Label,
+                        _ => {
+                            return Err(format!(
+                                "unknown field {{{}}}; use n, page, total or label",
+                                field
+                            ))
+                        }
+                    };
+                    if !literal.is_empty() {
+                        tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                    }
+                    tokens.push(token);
+                }
+                '}' => return Err("unmatched } in template; write }} for a brace".to_string()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+        Ok(Self(tokens))
+    }
+    pub fn format(&self, counter: u64, page: usize, total: usize, label: &str) -> String {
+        self.0
+            .iter()
+            .map(|token| match token {
+                Token::Literal(text) => text.clone(),
+                Token::Counter(width) => format!("{:0width$}", counter, width = width),
+                Token::Page(width) => format!("{:0width$}", page, width = width),
+                Token::Total => total.to_string(),
+                Token::Label => label.to_string(),
+            })
+            .collect()
+    }
+}
+
+fn roman(mut n: i64) -> String {
+    let numerals = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut text = String::new();
+    for (value, numeral) in numerals {
+        while n >= value {
+            text.push_str(numeral);
+            n -= value;
+        }
+    }
+    text
+}
+
+// The ranges of a `/PageLabels` number tree, by first page index.
+fn label_ranges(doc: &Document, node: &Dictionary, ranges: &mut Vec<(i64, Dictionary)>) {
+    if let Ok(nums) = node.get(b"Nums").and_then(|o| o.as_array()) {
+        for pair in nums.chunks_exact(2) {
+            let style = doc
+                .dereference(&pair[1])
+                .and_then(|(_, o)| o.as_dict())
+                .cloned();
+            if let (Ok(index), Ok(style)) = (pair[0].as_i64(), style) {
+                ranges.push((index, style));
+            }
+        }
+    }
+    if let Ok(kids) = node.get(b"Kids").and_then(|o| o.as_array()) {
+        for kid in kids {
+            if let Ok((_, Object::Dictionary(kid))) = doc.dereference(kid) {
+                label_ranges(doc, kid, ranges);
+            }
+        }
+    }
+}
+
+// The label of each page: its number unless the document has page labels.
+pub fn page_labels(doc: &Document) -> Vec<String> {
+    let pages = doc.get_pages().len();
+    let mut ranges = Vec::new();
+    if let Ok((_, Object::Dictionary(tree))) = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"PageLabels"))
+        .and_then(|o| doc.dereference(o))
+    {
+        label_ranges(doc, tree, &mut ranges);
+    }
+    ranges.sort_by_key(|(index, _)| *index);
+    (0..pages as i64)
+        .map(|index| {
+            let Some((first, style)) = ranges.iter().rev().find(|(first, _)| *first <= index)
+            else {
+                return (index + 1).to_string();
+            };
+            let prefix = match style.get(b"P") {
+                Ok(Object::String(bytes, _)) => decode_text_string(bytes),
+                _ => String::new(),
+            };
+            // a start value below 1 starts from 1
+            let start = style.get(b"St").and_then(|o| o.as_i64()).unwrap_or(1);
+            let n = start.max(1).saturating_add(index - first);
+            let letters = |upper: bool| {
+                // A to Z, then AA to ZZ and so on
+                let letter = (b'a' + ((n - 1) % 26) as u8) as char;
+                let letter = if upper {
+                    letter.to_ascii_uppercase()
+                } else {
+                    letter
+                };
+                letter.to_string().repeat(((n - 1) / 26 + 1) as usize)
+            };
+            let number = match style.get(b"S").and_then(|o| o.as_name()) {
+                Ok(b"D") => n.to_string(),
+                Ok(b"R") => roman(n).to_uppercase(),
+                Ok(b"r") => roman(n),
+                Ok(b"A") => letters(true),
+                Ok(b"a") => letters(false),
+                _ => String::new(),
+            };
+            prefix + &number
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct PageNumbering {
+    pub template: NumberTemplate,
+    pub font: String,
+    pub size: f64,
+    pub color: Color,
+    pub position: StampPosition,
+    pub margin: f64,
+}
+
+impl PageNumbering {
+    pub fn new(template: NumberTemplate) -> Self {
+        Self {
+            template,
+            font: "Helvetica".to_string(),
+            size: 10.,
+            color: Color::Gray(0.),
+            position: StampPosition::Relative(1., 0.),
+            margin: 36.,
+        }
+    }
+    // Numbers every page, the counter starting at `counter` and moved on
+    // past the last page. Returns the text stamped on each page.
+    pub fn apply(
+        &self,
+        modifier: &mut PdfModifier,
+        counter: &mut u64,
+    ) -> Result<Vec<String>, String> {
+        let labels = page_labels(modifier.document());
+        let pages = modifier.pages();
+        let mut texts = Vec::new();
+        for (index, page_id) in pages.iter().enumerate() {
+            let text = self.template.format(
+                *counter + index as u64,
+                index + 1,
+                pages.len(),
+                labels.get(index).map_or("", |l| l.as_str()),
+            );
+            texts.push((*page_id, text));
+        }
+        let stamp = Stamp {
+            position: self.position.clone(),
+            margin: self.margin,
+            ..Stamp::new(StampContent::Text {
+                text: String::new(),
+                font: self.font.clone(),
+                size: self.size,
+                color: self.color.clone(),
+            })
+        };
+        stamp.apply_texts(modifier, &texts)?;
+        *counter += pages.len() as u64;
+        Ok(texts.into_iter().map(|(_, text)| text).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::dictionary;
+
+    use super::*;
+
+    #[test]
+    fn template() {
+        let template = NumberTemplate::parse("ACME{n:6} p{page}/{total} {{{label}}}").unwrap();
+        assert_eq!(template.format(42, 3, 10, "iii"), "ACME000042 p3/10 {iii}");
+        assert_eq!(NumberTemplate::parse("").unwrap().format(1, 1, 1, ""), "");
+        assert_eq!(
+            NumberTemplate::parse("{page:3}")
+                .unwrap()
+                .format(0, 1234, 1, ""),
+            "1234"
+        );
+    }
+
+    #[test]
+    fn labels_from_zero() {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let kids: Vec<Object> = (0..3)
+            .map(|_| {
+                doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id })
+                    .into()
+            })
+            .collect();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => 3 }),
+        );
+        let catalog = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "PageLabels" => dictionary! {
+                "Nums" => vec![
+                    0.into(),
+                    dictionary! { "S" => "r", "St" => -5 }.into(),
+                    1.into(),
+                    dictionary! { "S" => "A", "St" => 0, "P" => Object::string_literal("x-") }.into(),
+                ],
+            },
+        });
+        doc.trailer.set("Root", catalog);
+        assert_eq!(page_labels(&doc), ["i", "x-A", "x-B"]);
+    }
+
+    #[test]
+    fn template_errors() {
+        for s in ["{n", "}", "{n:x}", "{name}", "{}"] {
+            assert!(NumberTemplate::parse(s).is_err(), "{} was accepted", s);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use lopdf::{dictionary, Document, Object, ObjectId};
+
+use crate::{heading_level, heading_styles, text_runs, text_string, LayoutAnalyzer, PdfModifier};
+
+#[derive(Debug, Clone)]
+pub struct OutlineEntry {
+    pub title: String,
+    // from 1 for the top level
+    pub level: usize,
+    pub page_id: (u32, u16),
+    // the point the destination shows at the top left of the window
+    pub left: f64,
+    pub top: f64,
+}
+
+// Headings found by layout analysis, in reading order, with their levels.
+pub fn detect_headings(modifier: &mut PdfModifier) -> Vec<OutlineEntry> {
+    let analyzer = LayoutAnalyzer::new();
+    let mut layouts = Vec::new();
+    for page_id in modifier.pages() {
+        let texts = text_runs(modifier, page_id);
+        layouts.push((page_id, analyzer.analyze(&texts)));
+    }
+    let pages: Vec<_> = layouts.iter().map(|(_, layout)| layout.clone()).collect();
+    let styles = heading_styles(&pages);
+    let mut entries = Vec::new();
+    for (page_id, layout) in &layouts {
+        for paragraph in layout.paragraphs() {
+            if let Some(level) = heading_level(&styles, paragraph) {
+                entries.push(OutlineEntry {
+                    title: paragraph.text(),
+                    level,
+                    page_id: *page_id,
+                    left: paragraph.bbox.x0,
+                    top: paragraph.bbox.y1,
+                });
+            }
+        }
+    }
+    entries
+}
+
+// An outline dictionary without a first item is empty, as some producers
+// write it.
+pub fn has_outline(doc: &Document) -> bool {
+    doc.catalog()
+        .and_then(|catalog| catalog.get(b"Outlines"))
+        .and_then(|o| doc.dereference(o))
+        .and_then(|(_, o)| o.as_dict())
+        .is_ok_and(|outlines| outlines.has(b"First"))
+}
+
+// Writes the entries as the document outline, nesting each under the
+// closest preceding entry of a lower level. Any outline there was is
+// replaced.
+pub fn set_outline(doc: &mut Document, entries: &[OutlineEntry]) {
+    // the parent of each entry, by index
+    let mut parents: Vec<Option<usize>> = Vec::new();
+    let mut stack: Vec<usize> = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        while stack
+            .last()
+            .is_some_and(|p| entries[*p].level >= entry.level)
+        {
+            stack.pop();
+        }
+        parents.push(stack.last().copied());
+        stack.push(i);
+    }
+    let root_id = doc.new_object_id();
+    let ids: Vec<ObjectId> = entries.iter().map(|_| doc.new_object_id()).collect();
+    let children = |parent: Option<usize>| -> Vec<usize> {
+        (0..entries.len())
+            .filter(|i| parents[*i] == parent)
+            .collect()
+    };
+    let descendants = |parent: usize| {
+        (0..entries.len())
+            .filter(|i| {
+                let mut ancestor = parents[*i];
+                while let Some(a) = ancestor {
+                    if a == parent {
+                        return true;
+                    }
+                    ancestor = parents[a];
+                }
+                false
+            })
+            .count()
+    };
+    for (i, entry) in entries.iter().enumerate() {
+        let mut item = dictionary! {
+            "Title" => text_string(&entry.title),
+            "Parent" => Object::Reference(parents[i].map_or(root_id, |p| ids[p])),
+            "Dest" => vec![
+                Object::Reference(entry.page_id),
+                "XYZ".into(),
+                Object::Real(entry.left as f32),
+                Object::Real(entry.top as f32),
+                Object::Null,
+            ],
+        };
+        let siblings = children(parents[i]);
+        let position = siblings.iter().position(|s| *s == i).unwrap();
+        if position > 0 {
+            item.set("Prev", Object::Reference(ids[siblings[position - 1]]));
+        }
+        if let Some(next) = siblings.get(position + 1) {
+            item.set("Next", Object::Reference(ids[*next]));
+        }
+        let own = children(Some(i));
+        if let (Some(first), Some(last)) = (own.first(), own.last()) {
+            item.set("First", Object::Reference(ids[*first]));
+            item.set("Last", Object::Reference(ids[*last]));
+            item.set("Count", descendants(i) as i64);
+        }
+        doc.objects.insert(ids[i], Object::Dictionary(item));
+    }
+    let mut root = dictionary! {
+        "Type" => "Outlines",
+        "Count" => entries.len() as i64,
+    };
+    let top = children(None);
+    if let (Some(first), Some(last)) = (top.first(), top.last()) {
+        root.set("First", Object::Reference(ids[*first]));
+        root.set("Last", Object::Reference(ids[*last]));
+    }
+    doc.objects.insert(root_id, Object::Dictionary(root));
+    if let Ok(catalog) = doc.catalog_mut() {
+        catalog.set("Outlines", Object::Reference(root_id));
+    }
+}
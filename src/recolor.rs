@@ -0,0 +1,434 @@
+use std::collections::HashSet;
+
+use lopdf::content::Operation;
+use lopdf::{dictionary, Document, Object, ObjectId};
+
+use crate::graphics::color::convert::separations;
+use crate::graphics::color::{Color, ColorSpace, ColorState};
+use crate::graphics::text::RenderingMode;
+use crate::{decode_operations, encode_operations, stream_content, PdfModifier, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Paint {
+    TextFill,
+    TextStroke,
+    PathFill,
+    PathStroke,
+}
+
+impl Paint {
+    pub const ALL: [Paint; 4] = [
+        Paint::TextFill,
+        Paint::TextStroke,
+        Paint::PathFill,
+        Paint::PathStroke,
+    ];
+    pub fn is_stroke(&self) -> bool {
+        matches!(self, Self::TextStroke | Self::PathStroke)
+    }
+    // What a painting operation uses the current colours for.
+    pub fn of(operation: &Operation, state: &State) -> Vec<Paint> {
+        match operation.operator.as_ref() {
+            "f" | "F" | "f*" => vec![Self::PathFill],
+            "S" | "s" => vec![Self::PathStroke],
+            "B" | "B*" | "b" | "b*" => vec![Self::PathFill, Self::PathStroke],
+            "Tj" | "TJ" | "'" | "\"" => match state.graphics.text.rendering_mode {
+                RenderingMode::Fill | RenderingMode::FillAndAddClippingPath => {
+                    vec![Self::TextFill]
+                }
+                RenderingMode::Stroke | RenderingMode::StrokeAndAddClippingPath => {
+                    vec![Self::TextStroke]
+                }
+                RenderingMode::FillAndStroke | RenderingMode::FillStrokeAddClippingPath => {
+                    vec![Self::TextFill, Self::TextStroke]
+                }
+                RenderingMode::Invisible | RenderingMode::AddClippingPath => vec![],
+            },
+            _ => vec![],
+        }
+    }
+}
+
+// The colour written to the output when it differs from the one the
+// original content has in effect at the same point.
+#[derive(Debug, Clone, Default)]
+struct Overrides {
+    stroke: Option<Color>,
+    non_stroke: Option<Color>,
+}
+
+impl Overrides {
+    fn get(&mut self, stroke: bool) -> &mut Option<Color> {
+        if stroke {
+            &mut self.stroke
+        } else {
+            &mut self.non_stroke
+        }
+    }
+}
+
+fn original(color: &ColorState, stroke: bool) -> (&Color, &ColorSpace) {
+    if stroke {
+        (&color.stroke, &color.stroke_space)
+    } else {
+        (&color.non_stroke, &color.non_stroke_space)
+    }
+}
+
+// Resource name under which an ink's Separation space is added to a page.
+fn spot_resource(ink: &str) -> Vec<u8> {
+    let ink: String = ink
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("Spot_{}", ink).into_bytes()
+}
+
+// Operations that set a mapped colour. Spot colours are set in a Separation
+// space whose ink is remembered in `inks` so the space can be added to the
+// page's resources.
+fn set_color(color: &Color, stroke: bool, inks: &mut Vec<String>) -> Vec<Operation> {
+    match color {
+        Color::Spot(ink, tint) => {
+            if !inks.contains(ink) {
+                inks.push(ink.clone());
+            }
+            vec![
+                Operation::new(
+                    if stroke { "CS" } else { "cs" },
+                    vec![Object::Name(spot_resource(ink))],
+                ),
+                Operation::new(if stroke { "SCN" } else { "scn" }, vec![(*tint).into()]),
+            ]
+        }
+        color => vec![color.into_operator(stroke)],
+    }
+}
+
+// The Separation space of an ink as the document defines it, or one with a
+// gray alternate for an ink the document does not have.
+fn separation(doc: &Document, ink: &str) -> Object {
+    separations(doc)
+        .into_iter()
+        .find(|(name, _)| name == ink)
+        .map(|(_, array)| match array.first() {
+            Some(Object::Name(kind)) if kind == b"Separation" => Object::Array(array.clone()),
+            // a single colorant DeviceN space takes the same operands
+            _ => Object::Array(vec![
+                Object::Name(b"Separation".to_vec()),
+                Object::Name(ink.as_bytes().to_vec()),
+                array[2].clone(),
+                array[3].clone(),
+            ]),
+        })
+        .unwrap_or_else(|| {
+            Object::Array(vec![
+                Object::Name(b"Separation".to_vec()),
+                Object::Name(ink.as_bytes().to_vec()),
+                Object::Name(b"DeviceGray".to_vec()),
+                Object::Dictionary(dictionary! {
+                    "FunctionType" => 2,
+                    "Domain" => vec![0.into(), 1.into()],
+                    "C0" => vec![1.into()],
+                    "C1" => vec![0.into()],
+                    "N" => 1,
+                }),
+            ])
+        })
+}
+
+// Operations that set `color`, with the resource name and Separation space
+// a spot colour needs in the `ColorSpace` resources of the content.
+pub fn color_operators(
+    doc: &Document,
+    color: &Color,
+    stroke: bool,
+) -> (Vec<Operation>, Option<(Vec<u8>, Object)>) {
+    let mut inks = Vec::new();
+    let operations = set_color(color, stroke, &mut inks);
+    let space = inks
+        .first()
+        .map(|ink| (spot_resource(ink), separation(doc, ink)));
+    (operations, space)
+}
+
+// Operations that set `color` in a page's content, adding the space of a
+// spot colour to the page's resources.
+pub fn page_color_operators(
+    modifier: &mut PdfModifier,
+    page_id: (u32, u16),
+    color: &Color,
+    stroke: bool,
+) -> Vec<Operation> {
+    let (operations, space) = color_operators(modifier.document(), color, stroke);
+    if let Some((name, space)) = space {
+        modifier.add_resource(page_id, "ColorSpace", &name, space);
+    }
+    operations
+}
+
+// The colour rewriting of one content stream.
+#[derive(Debug, Default)]
+struct Rewriter {
+    overrides: Overrides,
+    stack: Vec<Overrides>,
+    in_text: bool,
+    path: Vec<Operation>,
+    // inks whose Separation space the stream's resources need
+    inks: Vec<String>,
+}
+
+impl Rewriter {
+    fn operation<F>(
+        &mut self,
+        operation: Operation,
+        state: &State,
+        mapper: &mut F,
+    ) -> Vec<Operation>
+    where
+        F: FnMut(&Color, Paint) -> Option<Color>,
+    {
+        let color = &state.graphics.color;
+        let mut map = |stroke: bool, paint: Paint| {
+            if color.is_tracked(stroke) {
+                mapper(original(color, stroke).0, paint)
+            } else {
+                None
+            }
+        };
+        match operation.operator.as_ref() {
+            "q" => {
+                self.stack.push(self.overrides.clone());
+                vec![operation]
+            }
+            "Q" => {
+                self.overrides = self.stack.pop().unwrap_or_default();
+                vec![operation]
+            }
+            "BT" | "ET" => {
+                self.in_text = operation.operator == "BT";
+                vec![operation]
+            }
+            "m" | "l" | "c" | "v" | "y" | "re" | "h" | "W" | "W*" => {
+                self.path.push(operation);
+                vec![]
+            }
+            "G" | "RG" | "K" | "CS" | "SC" | "SCN" | "g" | "rg" | "k" | "cs" | "sc" | "scn" => {
+                let stroke = operation.operator.chars().all(|c| c.is_uppercase());
+                let paint = match (self.in_text, stroke) {
+                    (true, false) => Paint::TextFill,
+                    (true, true) => Paint::TextStroke,
+                    (false, false) => Paint::PathFill,
+                    (false, true) => Paint::PathStroke,
+                };
+                let written = self.overrides.get(stroke);
+                match map(stroke, paint) {
+                    Some(mapped) => {
+                        let operations = set_color(&mapped, stroke, &mut self.inks);
+                        *written = Some(mapped);
+                        operations
+                    }
+                    None => {
+                        let mut operations = Vec::new();
+                        // `sc` relies on the original space, which a mapped
+                        // colour has replaced
+                        if written.is_some()
+                            && matches!(operation.operator.as_ref(), "sc" | "scn" | "SC" | "SCN")
+                        {
+                            operations.push(original(color, stroke).1.into_operator(stroke));
+                        }
+                        *written = None;
+                        operations.push(operation);
+                        operations
+                    }
+                }
+            }
+            _ => {
+                let mut operations = Vec::new();
+                for paint in Paint::of(&operation, state) {
+                    let stroke = paint.is_stroke();
+                    let written = self.overrides.get(stroke);
+                    match (map(stroke, paint), written.as_ref()) {
+                        (Some(mapped), Some(current)) if mapped == *current => (),
+                        (None, None) => (),
+                        (Some(mapped), _) => {
+                            operations.extend(set_color(&mapped, stroke, &mut self.inks));
+                            *written = Some(mapped);
+                        }
+                        (None, Some(_)) => {
+                            operations.extend(color.operators(stroke));
+                            *written = None;
+                        }
+                    }
+                }
+                operations.append(&mut self.path);
+                operations.push(operation);
+                operations
+            }
+        }
+    }
+}
+
+// Rewrites the colours of a page through `mapper`, which returns the colour
+// a paint should have instead of the original one, or `None` to keep it.
+// Colour-setting operators are replaced where they are; a corrective
+// operator is written before painting only when text and paths sharing a
+// colour are mapped differently. Path construction is kept together so no
+// colour operator ends up inside a path object.
+pub fn rewrite_colors<F>(modifier: &mut PdfModifier, page_id: (u32, u16), mapper: &mut F)
+where
+    F: FnMut(&Color, Paint) -> Option<Color>,
+{
+    let mut rewriter = Rewriter::default();
+    modifier.apply(page_id, &mut |operation, state| {
+        rewriter.operation(operation, state, mapper)
+    });
+    for ink in rewriter.inks {
+        let space = separation(modifier.document(), &ink);
+        modifier.add_resource(page_id, "ColorSpace", &spot_resource(&ink), space);
+    }
+}
+
+// Paints single painting operations of a page in other colours: `mapper`
+// returns the colour a paint of the operation with the given id should
+// have. The original colours are set again after them.
+pub fn recolor_operations<F>(modifier: &mut PdfModifier, page_id: (u32, u16), mapper: &mut F)
+where
+    F: FnMut(usize, Paint) -> Option<Color>,
+{
+    let mut rewriter = Rewriter::default();
+    modifier.apply(page_id, &mut |operation, state| {
+        rewriter.operation(operation, state, &mut |_, paint| mapper(state.id, paint))
+    });
+    for ink in rewriter.inks {
+        let space = separation(modifier.document(), &ink);
+        modifier.add_resource(page_id, "ColorSpace", &spot_resource(&ink), space);
+    }
+}
+
+// Rewrites the colours of every form XObject and tiling pattern of the
+// document the same way, except forms painted as soft masks, whose colours
+// are coverage. Returns the number of streams rewritten.
+pub fn rewrite_stream_colors<F>(modifier: &mut PdfModifier, mapper: &mut F) -> usize
+where
+    F: FnMut(&Color, Paint) -> Option<Color>,
+{
+    let doc = modifier.document();
+    let masks: HashSet<ObjectId> = doc
+        .objects
+        .values()
+        .filter_map(|object| {
+            let dict = object.as_dict().ok()?;
+            match dict.get(b"S").and_then(|o| o.as_name()).ok()? {
+                b"Luminosity" | b"Alpha" => dict.get(b"G").and_then(|o| o.as_reference()).ok(),
+                _ => None,
+            }
+        })
+        .collect();
+    let mut rewritten = Vec::new();
+    for (id, object) in doc.objects.iter() {
+        let Object::Stream(stream) = object else {
+            continue;
+        };
+        let dict = &stream.dict;
+        let form = dict.get(b"Subtype").and_then(|o| o.as_name()).ok() == Some(b"Form");
+        let tiling = dict.get(b"PatternType").and_then(|o| o.as_i64()).ok() == Some(1);
+        if !(form || tiling) || masks.contains(id) {
+            continue;
+        }
+        let Some(Ok(operations)) =
+            stream_content(stream).map(|content| decode_operations(&content))
+        else {
+            continue;
+        };
+        let resources = dict
+            .get(b"Resources")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+            .ok();
+        let mut state = State::from_resources(doc, resources);
+        let mut rewriter = Rewriter::default();
+        let mut result = Vec::new();
+        for operation in operations {
+            state.handle_operation(&operation);
+            result.extend(rewriter.operation(operation, &state, mapper));
+        }
+        let mut resources = resources.cloned().unwrap_or_default();
+        if !rewriter.inks.is_empty() {
+            let mut spaces = resources
+                .get(b"ColorSpace")
+                .and_then(|o| doc.dereference(o))
+                .and_then(|(_, o)| o.as_dict())
+                .cloned()
+                .unwrap_or_default();
+            for ink in &rewriter.inks {
+                spaces.set(spot_resource(ink), separation(doc, ink));
+            }
+            resources.set("ColorSpace", spaces);
+        }
+        rewritten.push((*id, result, resources, !rewriter.inks.is_empty()));
+    }
+    let count = rewritten.len();
+    let doc = modifier.document_mut();
+    for (id, operations, resources, inks) in rewritten {
+        if let Ok(Object::Stream(stream)) = doc.get_object_mut(id) {
+            stream.set_plain_content(encode_operations(&operations).unwrap());
+            // a shared resource dictionary is left to the other streams
+            if inks {
+                stream.dict.set("Resources", resources);
+            }
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use lopdf::{Dictionary, Stream};
+
+    #[test]
+    fn spot_target_in_form() {
+        let mut doc = testing::page("/Fm0 Do", Dictionary::new());
+        let form = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+            },
+            b"1 0 0 rg 0 0 10 10 re f".to_vec(),
+        ));
+        let page_id = doc.page_iter().next().unwrap();
+        doc.get_dictionary_mut(page_id)
+            .unwrap()
+            .get_mut(b"Resources")
+            .and_then(|o| o.as_dict_mut())
+            .unwrap()
+            .set("XObject", dictionary! { "Fm0" => form });
+        let mut modifier = PdfModifier::from_document(doc);
+        let gold = Color::Spot("Gold".into(), 0.5);
+        let count = rewrite_stream_colors(&mut modifier, &mut |color, _| {
+            (*color == Color::RGB(1., 0., 0.)).then(|| gold.clone())
+        });
+        assert_eq!(count, 1);
+        let Ok(Object::Stream(form)) = modifier.document().get_object(form) else {
+            panic!("form is not a stream");
+        };
+        let operations = decode_operations(&form.content).unwrap();
+        let operators: Vec<&str> = operations.iter().map(|o| o.operator.as_str()).collect();
+        assert_eq!(operators, ["cs", "scn", "re", "f"]);
+        assert_eq!(operations[0].operands[0].as_name().unwrap(), b"Spot_Gold");
+        let space = form
+            .dict
+            .get(b"Resources")
+            .and_then(|o| o.as_dict())
+            .and_then(|r| r.get(b"ColorSpace"))
+            .and_then(|o| o.as_dict())
+            .and_then(|spaces| spaces.get(b"Spot_Gold"))
+            .and_then(|o| o.as_array())
+            .unwrap();
+        assert_eq!(space[0].as_name().unwrap(), b"Separation");
+        assert_eq!(space[1].as_name().unwrap(), b"Gold");
+    }
+}
@@ -0,0 +1,891 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use kurbo::{Affine, BezPath, Point, Rect};
+use lopdf::content::Operation;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::graphics::color::convert::ColorFamily;
+use crate::graphics::color::{Color, ColorSpace};
+use crate::{
+    decode_operations, decode_samples, deflate, encode_operations, filter_names, object_to_f32,
+    page_color_operators, page_resource, painted_bbox, stream_content, PathSerializer, PdfModifier,
+    State,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct RedactReport {
+    pub glyphs: usize,
+    pub paths_removed: usize,
+    pub paths_clipped: usize,
+    pub images: usize,
+    pub shadings: usize,
+    // what was removed whole because it could not be redacted in part
+    pub removed: Vec<String>,
+}
+
+impl Display for RedactReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} glyphs, {} paths removed, {} paths clipped, {} images masked, {} shadings clipped",
+            self.glyphs, self.paths_removed, self.paths_clipped, self.images, self.shadings
+        )?;
+        if !self.removed.is_empty() {
+            write!(f, ", {} removed whole", self.removed.len())?;
+        }
+        Ok(())
+    }
+}
+
+fn overlaps(areas: &[Rect], bbox: Rect) -> bool {
+    areas.iter().any(|area| {
+        area.intersect(bbox).area() > 0. || (bbox.area() == 0. && area.contains(bbox.center()))
+    })
+}
+
+fn covers(areas: &[Rect], bbox: Rect) -> bool {
+    areas.iter().any(|area| area.union(bbox) == *area)
+}
+
+// Glyphs count as redacted when a tenth of their box is under an area.
+fn hides_glyph(areas: &[Rect], bbox: Rect) -> bool {
+    areas.iter().any(|area| {
+        let overlap = area.intersect(bbox).area();
+        if bbox.area() > 0. {
+            overlap > 0.1 * bbox.area()
+        } else {
+            area.contains(bbox.center())
+        }
+    })
+}
+
+// Clipping paths in the current user space that leave out the areas
+// touching `bbox`. Each area is cut out by a clip of its own so overlapping
+// areas do not cancel out under the even-odd rule.
+fn exclusion(ctm: Affine, page: Rect, areas: &[Rect], bbox: Rect) -> Vec<Operation> {
+    let inverse = ctm.inverse();
+    let quad = |rect: Rect| {
+        let corners = [
+            (rect.x0, rect.y0),
+            (rect.x1, rect.y0),
+            (rect.x1, rect.y1),
+            (rect.x0, rect.y1),
+        ]
+        .map(|p| inverse * Point::from(p));
+        let mut path = BezPath::new();
+        path.move_to(corners[0]);
+        for corner in &corners[1..] {
+            path.line_to(*corner);
+        }
+        path.close_path();
+        PathSerializer::new().serialize(&path)
+    };
+    let outer = areas
+        .iter()
+        .fold(page.union(bbox), |outer, area| outer.union(*area))
+        .inflate(1., 1.);
+    let mut operations = Vec::new();
+    for area in areas.iter().filter(|area| overlaps(&[**area], bbox)) {
+        operations.extend(quad(outer));
+        operations.extend(quad(*area));
+        operations.push(Operation::new("W*", vec![]));
+        operations.push(Operation::new("n", vec![]));
+    }
+    operations
+}
+
+// What redacting an image needs to know, from an image XObject or an inline
+// image dictionary.
+struct ImageLayout {
+    width: usize,
+    height: usize,
+    bits: usize,
+    components: usize,
+    filters: Vec<Vec<u8>>,
+    parms: Option<Dictionary>,
+    decode: Vec<f32>,
+    // decoded values of a redacted pixel: black, or nothing for stencil masks
+    blank: Vec<f32>,
+}
+
+impl ImageLayout {
+    fn new(doc: &Document, dict: &Dictionary, spaces: Option<&Dictionary>) -> Result<Self, String> {
+        let get = |short: &[u8], long: &[u8]| dict.get(long).or(dict.get(short)).ok();
+        let integer = |short: &[u8], long: &[u8]| {
+            get(short, long).and_then(|o| o.as_i64().ok()).unwrap_or(0) as usize
+        };
+        let stencil = get(b"IM", b"ImageMask").and_then(|o| o.as_bool().ok()) == Some(true);
+        let bits = if stencil {
+            1
+        } else {
+            integer(b"BPC", b"BitsPerComponent")
+        };
+        if !matches!(bits, 1 | 2 | 4 | 8 | 16) {
+            return Err(format!("{} bits per component", bits));
+        }
+        let space = get(b"CS", b"ColorSpace").map(|space| match space {
+            Object::Name(name) => match name.as_slice() {
+                b"G" => Object::Name(b"DeviceGray".to_vec()),
+                b"RGB" => Object::Name(b"DeviceRGB".to_vec()),
+                b"CMYK" => Object::Name(b"DeviceCMYK".to_vec()),
+                _ => spaces
+                    .and_then(|spaces| spaces.get(name).ok())
+                    .cloned()
+                    .unwrap_or(space.clone()),
+            },
+            space => space.clone(),
+        });
+        let space = space
+            .as_ref()
+            .and_then(|space| doc.dereference(space).ok())
+            .map(|(_, space)| space);
+        let indexed = match space {
+            Some(Object::Array(array)) => matches!(
+                array.first().and_then(|o| o.as_name().ok()),
+                Some(b"Indexed" | b"I")
+            ),
+            _ => false,
+        };
+        let (components, blank, range) = if stencil {
+            (1, vec![1.], vec![0., 1.])
+        } else if indexed {
+            (1, vec![0.], vec![0., ((1u32 << bits) - 1) as f32])
+        } else {
+            let space = space.ok_or("no colour space")?;
+            match ColorSpace::from_resource(doc, b"", space).family() {
+                Some(ColorFamily::Gray) => (1, vec![0.], vec![0., 1.]),
+                Some(ColorFamily::RGB) => (3, vec![0.; 3], [0., 1.].repeat(3)),
+                Some(ColorFamily::CMYK) => (4, vec![0., 0., 0., 1.], [0., 1.].repeat(4)),
+                Some(ColorFamily::Lab) => {
+                    (3, vec![0.; 3], vec![0., 100., -100., 100., -100., 100.])
+                }
+                // Separation and DeviceN spaces are darkest at full tint
+                None => {
+                    let n = match space {
+                        Object::Array(array) => match array.get(1) {
+                            Some(Object::Array(inks)) => inks.len(),
+                            _ => 1,
+                        },
+                        _ => return Err("unknown colour space".to_string()),
+                    };
+                    (n, vec![1.; n], [0., 1.].repeat(n))
+                }
+            }
+        };
+        let decode: Vec<f32> = get(b"D", b"Decode")
+            .and_then(|o| o.as_array().ok())
+            .map(|a| a.iter().filter_map(object_to_f32).collect())
+            .filter(|d: &Vec<f32>| d.len() == 2 * components)
+            .unwrap_or(range);
+        Ok(Self {
+            width: integer(b"W", b"Width"),
+            height: integer(b"H", b"Height"),
+            bits,
+            components,
+            filters: filter_names(get(b"F", b"Filter")),
+            parms: get(b"DP", b"DecodeParms")
+                .and_then(|o| doc.dereference(o).ok())
+                .and_then(|(_, o)| o.as_dict().ok())
+                .cloned(),
+            decode,
+            blank,
+        })
+    }
+    // Blanks the pixels of the image, painted through `ctm`, that are under
+    // the areas. Returns the unfiltered samples and their bits per
+    // component.
+    fn mask(
+        &self,
+        content: &[u8],
+        ctm: Affine,
+        areas: &[Rect],
+    ) -> Result<(Vec<u8>, usize), String> {
+        let samples = decode_samples(
+            content,
+            &self.filters,
+            self.parms.as_ref(),
+            self.bits,
+            self.components,
+        )?;
+        let (mut data, bits, components) = (samples.data, samples.bits, samples.components);
+        if !matches!(bits, 1 | 2 | 4 | 8 | 16) || components != self.components {
+            return Err(format!("{} bits per component", bits));
+        }
+        let (width, height) = (self.width, self.height);
+        let stride = (width * components * bits).div_ceil(8);
+        if data.len() < stride * height {
+            return Err("truncated image data".to_string());
+        }
+        let max = ((1u32 << bits) - 1) as f32;
+        let values: Vec<u32> = self
+            .blank
+            .iter()
+            .zip(self.decode.chunks_exact(2))
+            .map(|(target, range)| {
+                let sample = if range[1] == range[0] {
+                    0.
+                } else {
+                    ((target - range[0]) / (range[1] - range[0])).clamp(0., 1.)
+                };
+                (sample * max).round() as u32
+            })
+            .collect();
+        let inverse = ctm.inverse();
+        for area in areas {
+            // the image fills the unit square, its first row at the top
+            let unit = inverse.transform_rect_bbox(*area);
+            let columns = (unit.x0 * width as f64).floor().max(0.) as usize
+                ..((unit.x1 * width as f64).ceil().max(0.) as usize).min(width);
+            let rows = ((1. - unit.y1) * height as f64).floor().max(0.) as usize
+                ..(((1. - unit.y0) * height as f64).ceil().max(0.) as usize).min(height);
+            for row in rows {
+                for column in columns.clone() {
+                    for (c, value) in values.iter().enumerate() {
+                        let offset = row * stride * 8 + (column * components + c) * bits;
+                        match bits {
+                            16 => {
+                                data[offset / 8] = (value >> 8) as u8;
+                                data[offset / 8 + 1] = *value as u8;
+                            }
+                            8 => data[offset / 8] = *value as u8,
+                            _ => {
+                                let shift = 8 - bits - offset % 8;
+                                let mask = (((1u32 << bits) - 1) << shift) as u8;
+                                let byte = &mut data[offset / 8];
+                                *byte = (*byte & !mask) | ((value << shift) as u8 & mask);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok((data, bits))
+    }
+}
+
+struct Redaction<'a> {
+    doc: &'a mut Document,
+    page: Rect,
+    report: RedactReport,
+    depth: usize,
+    // XObjects other pages draw, which are redacted in a copy
+    shared: HashSet<ObjectId>,
+}
+
+// The XObjects a resource dictionary draws, and those their forms draw.
+pub fn drawn_xobjects(
+    doc: &Document,
+    resources: Option<&Dictionary>,
+    found: &mut HashSet<ObjectId>,
+) {
+    let Some(xobjects) = resources
+        .and_then(|r| r.get(b"XObject").ok())
+        .and_then(|o| doc.dereference(o).ok())
+        .and_then(|(_, o)| o.as_dict().ok())
+    else {
+        return;
+    };
+    for (_, object) in xobjects.iter() {
+        let Ok(id) = object.as_reference() else {
+            continue;
+        };
+        if !found.insert(id) {
+            continue;
+        }
+        if let Ok(Object::Stream(stream)) = doc.get_object(id) {
+            let resources = stream
+                .dict
+                .get(b"Resources")
+                .and_then(|o| doc.dereference(o))
+                .and_then(|(_, o)| o.as_dict())
+                .ok();
+            drawn_xobjects(doc, resources, found);
+        }
+    }
+}
+
+impl Redaction<'_> {
+    fn path(
+        &mut self,
+        path: Vec<Operation>,
+        operation: Operation,
+        state: &State,
+        areas: &[Rect],
+    ) -> Vec<Operation> {
+        let bbox = match operation.operator.as_ref() {
+            "n" => None,
+            _ => painted_bbox(&operation, state, &HashMap::new(), self.page),
+        };
+        let mut operations = path.clone();
+        match bbox {
+            Some(bbox) if overlaps(areas, bbox) => {
+                let clips = path.iter().any(|o| o.operator.starts_with('W'));
+                if covers(areas, bbox) {
+                    self.report.paths_removed += 1;
+                    operations.push(Operation::new("n", vec![]));
+                } else {
+                    self.report.paths_clipped += 1;
+                    operations = vec![Operation::new("q", vec![])];
+                    operations.extend(exclusion(state.graphics.ctm, self.page, areas, bbox));
+                    operations.extend(
+                        path.iter()
+                            .filter(|o| !o.operator.starts_with('W'))
+                            .cloned(),
+                    );
+                    operations.push(operation);
+                    operations.push(Operation::new("Q", vec![]));
+                    // the clipping the path also did
+                    if clips {
+                        operations.extend(path);
+                        operations.push(Operation::new("n", vec![]));
+                    }
+                }
+            }
+            _ => operations.push(operation),
+        }
+        operations
+    }
+    // Removes the glyphs under the areas, moving the glyphs after them by
+    // the same amount they would have moved.
+    fn text(&mut self, operation: Operation, state: &State, areas: &[Rect]) -> Vec<Operation> {
+        let glyphs = state.shown_glyphs(&operation);
+        if !glyphs.iter().any(|g| hides_glyph(areas, g.bbox)) {
+            return vec![operation];
+        }
+        let elements: Vec<(usize, Object)> =
+            match (operation.operator.as_ref(), &operation.operands[..]) {
+                ("TJ", [Object::Array(array)]) => array.iter().cloned().enumerate().collect(),
+                ("\"", [_, _, string]) => vec![(2, string.clone())],
+                (_, [string, ..]) => vec![(0, string.clone())],
+                _ => return vec![operation],
+            };
+        let mut operations = Vec::new();
+        let mut array = Vec::new();
+        for (element, object) in elements {
+            let Object::String(bytes, format) = &object else {
+                array.push(object);
+                continue;
+            };
+            let mut kept = Vec::new();
+            for glyph in glyphs.iter().filter(|g| g.element == element) {
+                if !hides_glyph(areas, glyph.bbox) {
+                    kept.extend_from_slice(&bytes[glyph.start..glyph.end]);
+                    continue;
+                }
+                self.report.glyphs += 1;
+                if !kept.is_empty() {
+                    array.push(Object::String(std::mem::take(&mut kept), format.clone()));
+                }
+                // the glyph's advance is kept, a text size of 0 set aside
+                // meanwhile as it is for removed operations
+                let hidden = Object::String(bytes[glyph.start..glyph.end].to_vec(), format.clone());
+                match &state.skip_operations(&Operation::new("Tj", vec![hidden]))[..] {
+                    [skip] if skip.operator == "TJ" => {
+                        array.extend(skip.operands[0].as_array().into_iter().flatten().cloned())
+                    }
+                    skip => {
+                        if !array.is_empty() {
+                            let shown = Object::Array(std::mem::take(&mut array));
+                            operations.push(Operation::new("TJ", vec![shown]));
+                        }
+                        operations.extend_from_slice(skip);
+                    }
+                }
+            }
+            if !kept.is_empty() {
+                array.push(Object::String(kept, format.clone()));
+            }
+        }
+        if !array.is_empty() {
+            operations.push(Operation::new("TJ", vec![Object::Array(array)]));
+        }
+        let mut result = match (operation.operator.as_ref(), &operation.operands[..]) {
+            ("'", _) => vec![Operation::new("T*", vec![])],
+            ("\"", [word, character, _]) => vec![
+                Operation::new("Tw", vec![word.clone()]),
+                Operation::new("Tc", vec![character.clone()]),
+                Operation::new("T*", vec![]),
+            ],
+            _ => Vec::new(),
+        };
+        result.extend(operations);
+        result
+    }
+    fn image(&mut self, stream: &Stream, ctm: Affine, areas: &[Rect]) -> Result<Stream, String> {
+        let layout = ImageLayout::new(self.doc, &stream.dict, None)?;
+        let (data, bits) = layout.mask(&stream.content, ctm, areas)?;
+        let mut masked = stream.clone();
+        masked.dict.set("BitsPerComponent", bits as i64);
+        masked.set_plain_content(data);
+        self.report.images += 1;
+        Ok(masked)
+    }
+    fn inline_image(
+        &mut self,
+        dict: &Dictionary,
+        data: &[u8],
+        ctm: Affine,
+        resources: &Dictionary,
+        areas: &[Rect],
+    ) -> Result<Operation, String> {
+        let spaces = resources
+            .get(b"ColorSpace")
+            .and_then(|o| self.doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+            .ok();
+        let layout = ImageLayout::new(self.doc, dict, spaces)?;
+        let (data, bits) = layout.mask(data, ctm, areas)?;
+        let mut masked = dict.clone();
+        for key in [
+            b"Filter".as_slice(),
+            b"F",
+            b"DecodeParms",
+            b"DP",
+            b"BitsPerComponent",
+            b"BPC",
+        ] {
+            masked.remove(key);
+        }
+        if !dict.has(b"IM") && !dict.has(b"ImageMask") {
+            masked.set("BPC", bits as i64);
+        }
+        masked.set("F", Object::Name(b"Fl".to_vec()));
+        self.report.images += 1;
+        Ok(Operation::new(
+            "BI",
+            vec![
+                Object::Dictionary(masked),
+                Object::String(deflate(&data), StringFormat::Literal),
+            ],
+        ))
+    }
+    // A copy of a form XObject with its content redacted.
+    fn form(&mut self, stream: &Stream, ctm: Affine, areas: &[Rect]) -> Result<Stream, String> {
+        if self.depth >= 8 {
+            return Err("nested too deeply".to_string());
+        }
+        let content = stream_content(stream).ok_or("corrupt content")?;
+        let operations = decode_operations(&content).map_err(|e| e.to_string())?;
+        let mut resources = stream
+            .dict
+            .get(b"Resources")
+            .and_then(|o| self.doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+            .cloned()
+            .unwrap_or_default();
+        let mut state = State::from_resources(self.doc, Some(&resources));
+        let matrix: Vec<f64> = stream
+            .dict
+            .get(b"Matrix")
+            .and_then(|o| o.as_array())
+            .map(|a| a.iter().filter_map(object_to_f32).map(f64::from).collect())
+            .unwrap_or_default();
+        state.graphics.ctm = match matrix[..] {
+            [a, b, c, d, e, f] => ctm * Affine::new([a, b, c, d, e, f]),
+            _ => ctm,
+        };
+        self.depth += 1;
+        let (operations, xobjects) = self.content(operations, state, &resources, areas);
+        self.depth -= 1;
+        let mut form = stream.clone();
+        if !xobjects.is_empty() {
+            let mut category = resources
+                .get(b"XObject")
+                .and_then(|o| self.doc.dereference(o))
+                .and_then(|(_, o)| o.as_dict())
+                .cloned()
+                .unwrap_or_default();
+            for (name, object) in xobjects {
+                category.set(name, object);
+            }
+            resources.set("XObject", category);
+            form.dict.set("Resources", resources);
+        }
+        form.set_plain_content(encode_operations(&operations).map_err(|e| e.to_string())?);
+        Ok(form)
+    }
+    // Redacts the operations of a content stream. Returns them with the
+    // XObjects that replace redacted ones, to be added to the resources.
+    fn content(
+        &mut self,
+        operations: Vec<Operation>,
+        mut state: State,
+        resources: &Dictionary,
+        areas: &[Rect],
+    ) -> (Vec<Operation>, Vec<(Vec<u8>, Object)>) {
+        let xobjects = resources
+            .get(b"XObject")
+            .and_then(|o| self.doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+            .cloned()
+            .unwrap_or_default();
+        let mut added: Vec<(Vec<u8>, Object)> = Vec::new();
+        let mut result = Vec::new();
+        let mut path = Vec::new();
+        for operation in operations {
+            state.handle_operation(&operation);
+            let ctm = state.graphics.ctm;
+            match operation.operator.as_ref() {
+                "m" | "l" | "c" | "v" | "y" | "re" | "h" | "W" | "W*" => path.push(operation),
+                "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "n" => {
+                    result.extend(self.path(std::mem::take(&mut path), operation, &state, areas))
+                }
+                "Tj" | "TJ" | "'" | "\"" => result.extend(self.text(operation, &state, areas)),
+                "sh" => match painted_bbox(&operation, &state, &HashMap::new(), self.page) {
+                    Some(bbox) if overlaps(areas, bbox) => {
+                        self.report.shadings += 1;
+                        result.push(Operation::new("q", vec![]));
+                        result.extend(exclusion(ctm, self.page, areas, bbox));
+                        result.push(operation);
+                        result.push(Operation::new("Q", vec![]));
+                    }
+                    _ => result.push(operation),
+                },
+                "BI" => {
+                    let bbox = ctm.transform_rect_bbox(Rect::new(0., 0., 1., 1.));
+                    match &operation.operands[..] {
+                        [Object::Dictionary(dict), Object::String(data, _)]
+                            if overlaps(areas, bbox) =>
+                        {
+                            match self.inline_image(dict, data, ctm, resources, areas) {
+                                Ok(masked) => result.push(masked),
+                                Err(reason) => self
+                                    .report
+                                    .removed
+                                    .push(format!("inline image: {}", reason)),
+                            }
+                        }
+                        _ => result.push(operation),
+                    }
+                }
+                "Do" => {
+                    let Some((name, id, stream)) = operation
+                        .operands
+                        .first()
+                        .and_then(|o| o.as_name().ok())
+                        .and_then(|name| {
+                            let object = xobjects.get(name).ok()?;
+                            let id = object.as_reference().ok();
+                            let (_, object) = self.doc.dereference(object).ok()?;
+                            Some((name.to_vec(), id, object.as_stream().ok()?.clone()))
+                        })
+                    else {
+                        result.push(operation);
+                        continue;
+                    };
+                    let form =
+                        stream.dict.get(b"Subtype").and_then(|o| o.as_name()).ok() == Some(b"Form");
+                    let bbox = if form {
+                        painted_bbox(
+                            &operation,
+                            &state,
+                            &[(name.clone(), form_bbox(&stream))].into(),
+                            self.page,
+                        )
+                    } else {
+                        Some(ctm.transform_rect_bbox(Rect::new(0., 0., 1., 1.)))
+                    };
+                    if !bbox.is_some_and(|bbox| overlaps(areas, bbox)) {
+                        result.push(operation);
+                        continue;
+                    }
+                    let redacted = if form {
+                        self.form(&stream, ctm, areas)
+                    } else {
+                        self.image(&stream, ctm, areas)
+                    };
+                    if let Err(reason) = &redacted {
+                        self.report.removed.push(format!(
+                            "{} {}: {}",
+                            if form { "form" } else { "image" },
+                            String::from_utf8_lossy(&name),
+                            reason
+                        ));
+                    }
+                    // an XObject of this page alone is replaced, so nothing
+                    // of the original is left in the file; drawing it again
+                    // elsewhere on the page draws the redacted one
+                    match (id.filter(|id| !self.shared.contains(id)), redacted) {
+                        (Some(id), redacted) => {
+                            let replacement = redacted.unwrap_or_else(|_| {
+                                Stream::new(
+                                    dictionary! {
+                                        "Type" => "XObject",
+                                        "Subtype" => "Form",
+                                        "BBox" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+                                    },
+                                    Vec::new(),
+                                )
+                            });
+                            self.doc.objects.insert(id, Object::Stream(replacement));
+                            result.push(operation);
+                        }
+                        (None, Ok(redacted)) => {
+                            let id = self.doc.add_object(redacted);
+                            let mut n = 1;
+                            let new_name = loop {
+                                let candidate =
+                                    [name.as_slice(), format!("R{}", n).as_bytes()].concat();
+                                if !xobjects.has(&candidate)
+                                    && !added.iter().any(|(a, _)| *a == candidate)
+                                {
+                                    break candidate;
+                                }
+                                n += 1;
+                            };
+                            added.push((new_name.clone(), Object::Reference(id)));
+                            result.push(Operation::new("Do", vec![Object::Name(new_name)]));
+                        }
+                        (None, Err(_)) => (),
+                    }
+                }
+                _ => result.push(operation),
+            }
+        }
+        result.extend(path);
+        (result, added)
+    }
+}
+
+// The box of a form in its parent's user space.
+fn form_bbox(stream: &Stream) -> Rect {
+    let array = |key: &[u8]| -> Vec<f64> {
+        stream
+            .dict
+            .get(key)
+            .and_then(|o| o.as_array())
+            .map(|a| a.iter().filter_map(object_to_f32).map(f64::from).collect())
+            .unwrap_or_default()
+    };
+    let bbox = match array(b"BBox")[..] {
+        [x0, y0, x1, y1] => Rect::new(x0, y0, x1, y1),
+        _ => Rect::new(0., 0., 1., 1.),
+    };
+    match array(b"Matrix")[..] {
+        [a, b, c, d, e, f] => Affine::new([a, b, c, d, e, f]).transform_rect_bbox(bbox),
+        _ => bbox,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    // painted over the redacted areas
+    pub fill: Option<Color>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Redactor {
+    pub fn new() -> Self {
+        Self {
+            fill: Some(Color::Gray(0.)),
+        }
+    }
+    // Removes what is painted in the areas, given in default user space:
+    // glyphs are taken out of their strings, paths entirely inside an area
+    // are dropped and others clipped, image pixels are blanked and forms
+    // redacted, in place or in a copy when other pages draw them too. Images
+    // that cannot be decoded are removed whole. The replaced objects stay in
+    // the document until it is pruned.
+    pub fn redact(
+        &self,
+        modifier: &mut PdfModifier,
+        page_id: (u32, u16),
+        areas: &[Rect],
+    ) -> RedactReport {
+        if areas.is_empty() {
+            return RedactReport::default();
+        }
+        let page = modifier
+            .visible_box(page_id)
+            .unwrap_or_else(|| areas.iter().fold(areas[0], |a, b| a.union(*b)));
+        let doc = modifier.document_mut();
+        let content = doc.get_page_content(page_id).unwrap();
+        let operations = decode_operations(&content).unwrap();
+        let state = State::new(doc, page_id);
+        let mut resources = Dictionary::new();
+        for category in [b"XObject".as_slice(), b"ColorSpace"] {
+            if let Some(dict) = page_resource(doc, page_id, category) {
+                resources.set(category, dict.clone());
+            }
+        }
+        let mut shared = HashSet::new();
+        for (_, other) in doc.get_pages() {
+            if other != page_id {
+                let (direct, inherited) = doc.get_page_resources(other);
+                let inherited = inherited
+                    .into_iter()
+                    .filter_map(|id| doc.get_dictionary(id).ok());
+                for resources in direct.into_iter().chain(inherited) {
+                    drawn_xobjects(doc, Some(resources), &mut shared);
+                }
+            }
+        }
+        let mut redaction = Redaction {
+            doc,
+            page,
+            report: RedactReport::default(),
+            depth: 0,
+            shared,
+        };
+        let (operations, xobjects) = redaction.content(operations, state, &resources, areas);
+        let report = redaction.report;
+        // a new stream, as the old ones may be shared, or parts of an array
+        // that changing the content would leave behind
+        let doc = modifier.document_mut();
+        let content = doc.add_object(Stream::new(
+            Dictionary::new(),
+            encode_operations(&operations).unwrap(),
+        ));
+        doc.get_object_mut(page_id)
+            .and_then(|page| page.as_dict_mut())
+            .unwrap()
+            .set("Contents", Object::Reference(content));
+        for (name, object) in xobjects {
+            modifier.add_resource(page_id, "XObject", &name, object);
+        }
+        if let Some(fill) = &self.fill {
+            let mut cover = page_color_operators(modifier, page_id, fill, false);
+            for area in areas {
+                cover.push(Operation::new(
+                    "re",
+                    [area.x0, area.y0, area.width(), area.height()]
+                        .map(|v| Object::Real(v as f32))
+                        .to_vec(),
+                ));
+            }
+            cover.push(Operation::new("f", vec![]));
+            modifier.insert_content(page_id, &cover, false);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn stream(content: &str) -> Stream {
+        Stream::new(Dictionary::new(), format!("{}\n", content).into_bytes())
+    }
+
+    // A page showing the secret directly, in a content array, and through a
+    // form XObject.
+    fn document() -> Document {
+        let mut doc = Document::with_version("1.5");
+        let font = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let resources = dictionary! { "Font" => dictionary! { "F1" => font } };
+        let form = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), 200.into(), 50.into()],
+                "Resources" => resources.clone(),
+            },
+            b"BT /F1 12 Tf 10 10 Td (FORMSECRET) Tj ET".to_vec(),
+        ));
+        let first = doc.add_object(stream("BT /F1 12 Tf 50 700 Td (PAGESECRET) Tj ET"));
+        let second = doc.add_object(stream("q 1 0 0 1 40 600 cm /Fm1 Do Q"));
+        let pages = doc.new_object_id();
+        let mut page_resources = resources;
+        page_resources.set("XObject", dictionary! { "Fm1" => form });
+        let page = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Contents" => vec![first.into(), second.into()],
+            "Resources" => page_resources,
+        });
+        doc.objects.insert(
+            pages,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+        doc.trailer.set("Root", catalog);
+        doc
+    }
+
+    #[test]
+    fn redacted_text_is_not_saved() {
+        let directory = std::env::temp_dir();
+        let input = directory.join(format!("redact-in-{}.pdf", std::process::id()));
+        let output = directory.join(format!("redact-out-{}.pdf", std::process::id()));
+        document().save(&input).unwrap();
+        let mut modifier = PdfModifier::new(&input).unwrap();
+        let page_id = modifier.pages()[0];
+        let report =
+            Redactor::new().redact(&mut modifier, page_id, &[Rect::new(0., 550., 612., 792.)]);
+        assert!(report.glyphs >= "PAGESECRETFORMSECRET".len(), "{}", report);
+        modifier.document_mut().prune_objects();
+        modifier.save(&output);
+        let saved = Document::load(&output).unwrap();
+        std::fs::remove_file(&input).ok();
+        std::fs::remove_file(&output).ok();
+        for object in saved.objects.values() {
+            if let Object::Stream(stream) = object {
+                let content = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                let content = String::from_utf8_lossy(&content);
+                assert!(!content.contains("SECRET"), "{}", content);
+            }
+        }
+    }
+
+    #[test]
+    fn hidden_glyphs_keep_their_advance() {
+        // at size 0 only the character spacing moves the glyphs
+        let (mut modifier, page_id) = testing::modifier("BT /F1 0 Tf 10 Tc 100 700 Td (ABC) Tj ET");
+        let report =
+            Redactor::new().redact(&mut modifier, page_id, &[Rect::new(105., 690., 115., 710.)]);
+        assert_eq!(report.glyphs, 1);
+        let mut shown = Vec::new();
+        modifier.for_each(page_id, &mut |operation, state| {
+            shown.extend(state.shown_glyphs(&operation));
+        });
+        let texts: Vec<&str> = shown.iter().map(|g| g.text.as_str()).collect();
+        assert_eq!(texts, ["A", "C"]);
+        assert!(
+            (shown[1].origin.x - 120.).abs() < 0.01,
+            "{:?}",
+            shown[1].origin
+        );
+    }
+
+    #[test]
+    fn image_bits_are_checked() {
+        let doc = Document::with_version("1.5");
+        for bits in [0, 3, 32, 64] {
+            let dict = dictionary! {
+                "Width" => 1,
+                "Height" => 1,
+                "BitsPerComponent" => bits,
+                "ColorSpace" => vec![
+                    "Indexed".into(),
+                    "DeviceRGB".into(),
+                    0.into(),
+                    Object::string_literal(vec![0, 0, 0]),
+                ],
+            };
+            assert!(
+                ImageLayout::new(&doc, &dict, None).is_err(),
+                "{} bits",
+                bits
+            );
+        }
+    }
+}
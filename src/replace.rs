@@ -0,0 +1,376 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use kurbo::{Affine, Point};
+use lopdf::content::Operation;
+use lopdf::{Object, StringFormat};
+
+use crate::{page_fonts, Font, PdfModifier, ShownGlyph};
+
+#[derive(Debug, Clone)]
+pub struct TextReplacement {
+    pub find: String,
+    pub replace: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplaceFailure {
+    pub page: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplaceReport {
+    pub replaced: usize,
+    pub failures: Vec<ReplaceFailure>,
+}
+
+impl Display for ReplaceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} replaced, {} not replaced",
+            self.replaced,
+            self.failures.len()
+        )
+    }
+}
+
+// A glyph shown by a text operation: the bytes of its code in one string
+// operand, or one string of a `TJ` array.
+struct Glyph {
+    op: usize,
+    element: usize,
+    start: usize,
+    end: usize,
+    text: String,
+    font: Vec<u8>,
+    // what the page text has between the previous glyph and this one
+    separator: &'static str,
+    em: Affine,
+    next_origin: Point,
+    // character and word spacing in units of the font size
+    spacing: (f64, f64),
+}
+
+fn glyphs(modifier: &mut PdfModifier, page_id: (u32, u16)) -> Vec<Glyph> {
+    let mut glyphs = Vec::new();
+    let mut previous: Option<ShownGlyph> = None;
+    modifier.for_each(page_id, &mut |operation, state| {
+        let text = &state.graphics.text;
+        let Some(font) = &text.font else {
+            return;
+        };
+        let size = f64::from(text.font_size.unwrap_or(0.));
+        let spacing = (
+            f64::from(text.charactor_spacing) / size,
+            f64::from(text.word_spacing) / size,
+        );
+        for glyph in state.shown_glyphs(&operation) {
+            glyphs.push(Glyph {
+                op: state.id,
+                element: glyph.element,
+                start: glyph.start,
+                end: glyph.end,
+                text: glyph.text.clone(),
+                font: font.clone(),
+                separator: previous.as_ref().map_or("", |p| glyph.separator(p)),
+                em: glyph.em,
+                next_origin: glyph.next_origin,
+                spacing,
+            });
+            previous = Some(glyph);
+        }
+    });
+    glyphs
+}
+
+// A byte range of a string, the bytes that replace it and the `TJ`
+// adjustment written after them.
+type Splice = (usize, usize, Vec<u8>, f64);
+
+#[derive(Default)]
+struct Edits {
+    // by operation and string
+    ranges: HashMap<(usize, usize), Vec<Splice>>,
+    // kerning numbers of `TJ` arrays removed along with the glyphs around them
+    kerning: HashSet<(usize, usize)>,
+}
+
+// The strings and adjustments an edited string becomes.
+fn edit_string(bytes: &[u8], format: &StringFormat, ranges: &[Splice]) -> Vec<Object> {
+    let mut ranges = ranges.to_vec();
+    ranges.sort_by_key(|(start, _, _, _)| *start);
+    let mut result = Vec::new();
+    let mut string = Vec::new();
+    let mut position = 0;
+    for (start, end, replacement, adjustment) in ranges {
+        string.extend_from_slice(&bytes[position.min(start)..start]);
+        string.extend(replacement);
+        if adjustment != 0. {
+            result.push(Object::String(std::mem::take(&mut string), format.clone()));
+            result.push(Object::Real(adjustment as f32));
+        }
+        position = end;
+    }
+    string.extend_from_slice(&bytes[position.min(bytes.len())..]);
+    result.push(Object::String(string, format.clone()));
+    result
+}
+
+// The `TJ` adjustment that keeps the glyph after a match in place when
+// `encoded` replaces the matched glyphs, all shown by one operation.
+fn adjustment(font: &Font, matched: &[Glyph], encoded: &[u8]) -> f64 {
+    let (first, last) = (&matched[0], &matched[matched.len() - 1]);
+    let (character, word) = first.spacing;
+    if first.em.determinant() == 0. || !character.is_finite() {
+        return 0.;
+    }
+    // in units of the font size along the baseline
+    let old = (first.em.inverse() * last.next_origin).x;
+    let new: f64 = font
+        .codes(encoded)
+        .into_iter()
+        .map(|code| {
+            let word = if font.code_length() == 1 && code == 32 {
+                word
+            } else {
+                0.
+            };
+            f64::from(font.width(code)) / 1000. + character + word
+        })
+        .sum();
+    ((new - old) * 1000. * 1000.).round() / 1000.
+}
+
+// Replaces text shown on a page, wherever the searched text is split
+// between the strings and kerning of one operation. Glyphs apart from each
+// other are separated as `ShownGlyph::separator` says, so a match never
+// joins them. The replacement is encoded with the font of the match and
+// takes the place of the first string's part of the match; the rest of the
+// match is removed, and an adjustment keeps the text after it in place.
+// Matches across operations, which may change font or position in between,
+// and matches the font has no glyphs for are left and reported.
+pub fn replace_text(
+    modifier: &mut PdfModifier,
+    page_id: (u32, u16),
+    page_number: usize,
+    replacement: &TextReplacement,
+    report: &mut ReplaceReport,
+) {
+    if replacement.find.is_empty() {
+        return;
+    }
+    let glyphs = glyphs(modifier, page_id);
+    let mut text = String::new();
+    let mut starts = Vec::new();
+    let mut ends = Vec::new();
+    for glyph in &glyphs {
+        text.push_str(glyph.separator);
+        starts.push(text.len());
+        text.push_str(&glyph.text);
+        ends.push(text.len());
+    }
+    let fonts = page_fonts(modifier.document(), page_id);
+    let mut edits = Edits::default();
+    for (position, found) in text.match_indices(&replacement.find) {
+        let end = position + found.len();
+        let first = starts.binary_search(&position);
+        let last = ends.binary_search(&end).map(|last| last + 1);
+        let (Ok(first), Ok(last)) = (first, last) else {
+            report.failures.push(ReplaceFailure {
+                page: page_number,
+                text: found.to_string(),
+                reason: "the match starts or ends inside a ligature or a gap".to_string(),
+            });
+            continue;
+        };
+        let matched = &glyphs[first..last];
+        if matched.iter().any(|glyph| glyph.op != matched[0].op) {
+            report.failures.push(ReplaceFailure {
+                page: page_number,
+                text: found.to_string(),
+                reason: "the match spans several text operations".to_string(),
+            });
+            continue;
+        }
+        let Some(font) = fonts.get(&matched[0].font) else {
+            report.failures.push(ReplaceFailure {
+                page: page_number,
+                text: found.to_string(),
+                reason: "the font is not in the page resources".to_string(),
+            });
+            continue;
+        };
+        let encoded = match font.encode(&replacement.replace) {
+            Ok(encoded) => encoded,
+            Err(c) => {
+                report.failures.push(ReplaceFailure {
+                    page: page_number,
+                    text: found.to_string(),
+                    reason: format!("{} has no glyph for {:?}", font.base_font, c),
+                });
+                continue;
+            }
+        };
+        let adjustment = adjustment(font, matched, &encoded);
+        let mut encoded = Some(encoded);
+        let mut previous: Option<&Glyph> = None;
+        for glyph in matched {
+            let ranges = edits.ranges.entry((glyph.op, glyph.element)).or_default();
+            match (previous, ranges.last_mut()) {
+                // the glyph continues the range of the previous one
+                (Some(p), Some((_, range_end, _, _)))
+                    if p.element == glyph.element && *range_end == glyph.start =>
+                {
+                    *range_end = glyph.end
+                }
+                _ => ranges.push((
+                    glyph.start,
+                    glyph.end,
+                    encoded.take().unwrap_or_default(),
+                    0.,
+                )),
+            }
+            if let Some(p) = previous {
+                for element in p.element + 1..glyph.element {
+                    edits.kerning.insert((glyph.op, element));
+                }
+            }
+            previous = Some(glyph);
+        }
+        let last = &matched[matched.len() - 1];
+        if let Some(range) = edits
+            .ranges
+            .get_mut(&(last.op, last.element))
+            .and_then(|ranges| ranges.last_mut())
+        {
+            range.3 = adjustment;
+        }
+        report.replaced += 1;
+    }
+    if edits.ranges.is_empty() {
+        return;
+    }
+    modifier.apply(page_id, &mut |mut operation, state| {
+        let id = state.id;
+        if !edits.ranges.keys().any(|(op, _)| *op == id) {
+            return vec![operation];
+        }
+        let edit =
+            |element: usize, object: &Object| match (object, edits.ranges.get(&(id, element))) {
+                (Object::String(bytes, format), Some(ranges)) => edit_string(bytes, format, ranges),
+                (object, _) => vec![object.clone()],
+            };
+        let shown = |element: usize, object: &Object| {
+            let mut objects = edit(element, object);
+            objects.retain(|o| !matches!(o, Object::String(bytes, _) if bytes.is_empty()));
+            Operation::new("TJ", vec![Object::Array(objects)])
+        };
+        // a string split by an adjustment is shown by a `TJ`
+        match (operation.operator.as_ref(), &operation.operands[..]) {
+            ("TJ", [Object::Array(array)]) => {
+                let array = array
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !edits.kerning.contains(&(id, *i)))
+                    .flat_map(|(i, o)| edit(i, o))
+                    .filter(|o| !matches!(o, Object::String(bytes, _) if bytes.is_empty()))
+                    .collect();
+                operation.operands = vec![Object::Array(array)];
+                vec![operation]
+            }
+            ("Tj", [string]) => match &edit(0, string)[..] {
+                [string] => {
+                    operation.operands = vec![string.clone()];
+                    vec![operation]
+                }
+                _ => vec![shown(0, string)],
+            },
+            ("'", [string]) => match &edit(0, string)[..] {
+                [string] => {
+                    operation.operands = vec![string.clone()];
+                    vec![operation]
+                }
+                _ => vec![Operation::new("T*", vec![]), shown(0, string)],
+            },
+            ("\"", [word, character, string]) => match &edit(2, string)[..] {
+                [string] => {
+                    operation.operands[2] = string.clone();
+                    vec![operation]
+                }
+                _ => vec![
+                    Operation::new("Tw", vec![word.clone()]),
+                    Operation::new("Tc", vec![character.clone()]),
+                    Operation::new("T*", vec![]),
+                    shown(2, string),
+                ],
+            },
+            _ => vec![operation],
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn replace(content: &str, find: &str, replace: &str) -> (PdfModifier, ReplaceReport) {
+        let (mut modifier, page_id) = testing::modifier(content);
+        let mut report = ReplaceReport::default();
+        let replacement = TextReplacement {
+            find: find.to_string(),
+            replace: replace.to_string(),
+        };
+        replace_text(&mut modifier, page_id, 1, &replacement, &mut report);
+        (modifier, report)
+    }
+
+    // The text of the page and where each of its glyphs is.
+    fn shown(modifier: &mut PdfModifier) -> (String, Vec<Point>) {
+        let page_id = modifier.pages()[0];
+        let (mut text, mut origins) = (String::new(), Vec::new());
+        modifier.for_each(page_id, &mut |operation, state| {
+            for glyph in state.shown_glyphs(&operation) {
+                text.push_str(&glyph.text);
+                origins.push(glyph.origin);
+            }
+        });
+        (text, origins)
+    }
+
+    #[test]
+    fn next_glyph_stays() {
+        for content in [
+            "BT /F1 10 Tf 2 Tc 72 700 Td (Hello World) Tj ET",
+            "BT /F1 10 Tf 72 700 Td [(He) -50 (llo) 20 ( World)] TJ ET",
+            "BT /F1 10 Tf 72 700 Td 3 1 (Hello World) \" ET",
+        ] {
+            let (mut original, _) = testing::modifier(content);
+            let (_, before) = shown(&mut original);
+            let (mut modifier, report) = replace(content, "Hello", "Hi");
+            assert_eq!(report.replaced, 1);
+            let (text, after) = shown(&mut modifier);
+            assert_eq!(text, "Hi World");
+            // the space after the match
+            assert!(
+                (after[2] - before[5]).hypot() < 0.01,
+                "{}: {:?} moved to {:?}",
+                content,
+                before[5],
+                after[2]
+            );
+        }
+    }
+
+    #[test]
+    fn matches_across_operations_are_kept() {
+        let content = "BT /F1 10 Tf 72 700 Td (Hel) Tj /F1 12 Tf (lo) Tj ET";
+        let (mut modifier, report) = replace(content, "Hello", "Hi");
+        assert_eq!(report.replaced, 0);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(shown(&mut modifier).0, "Hello");
+    }
+}
@@ -0,0 +1,225 @@
+use std::ops::Range;
+
+use kurbo::{Point, Rect};
+use regex::Regex;
+
+use crate::graphics::color::Color;
+use crate::{markup_annotation, text_string, PdfModifier, Quad, ShownGlyph};
+
+#[derive(Debug, Clone)]
+pub struct TextMatch {
+    pub text: String,
+    // one rectangle per text showing operation the match is spread over
+    pub rects: Vec<Rect>,
+    // one quadrilateral per line, along the baseline of its text
+    pub quads: Vec<Quad>,
+}
+
+// The quadrilateral around glyphs of one line, in the text space of the
+// first one so rotated and skewed text is covered closely.
+fn line_quad(glyphs: &[&ShownGlyph]) -> Option<Quad> {
+    let frame = glyphs.first()?.em;
+    if frame.determinant().abs() < 1e-12 {
+        return None;
+    }
+    let inverse = frame.inverse();
+    let extent = glyphs
+        .iter()
+        .flat_map(|glyph| {
+            let Rect { x0, y0, x1, y1 } = glyph.extent;
+            [(x0, y0), (x1, y0), (x0, y1), (x1, y1)]
+                .map(|corner| inverse * (glyph.em * Point::from(corner)))
+        })
+        .map(|p| Rect::from_points(p, p))
+        .reduce(|a, b| a.union(b))?;
+    Some([
+        frame * Point::new(extent.x0, extent.y1),
+        frame * Point::new(extent.x1, extent.y1),
+        frame * Point::new(extent.x0, extent.y0),
+        frame * Point::new(extent.x1, extent.y0),
+    ])
+}
+
+// Finds text on a page and in the forms it draws. `find` returns the byte ranges of the matches in the
+// text of the page, which is the text of its glyphs in content order with
+// the separators of `ShownGlyph::separator` between them. Matches starting
+// or ending inside a ligature take the whole glyph.
+pub fn text_matches<F>(modifier: &mut PdfModifier, page_id: (u32, u16), find: F) -> Vec<TextMatch>
+where
+    F: Fn(&str) -> Vec<Range<usize>>,
+{
+    // glyphs with the index of the operation showing them, counting the
+    // operations of the forms the page draws
+    let mut glyphs: Vec<(usize, ShownGlyph)> = Vec::new();
+    let mut op = 0;
+    modifier.for_each_nested(page_id, &mut |operation, state| {
+        glyphs.extend(
+            state
+                .shown_glyphs(&operation)
+                .into_iter()
+                .map(|glyph| (op, glyph)),
+        );
+        op += 1;
+    });
+    let mut text = String::new();
+    let mut starts = Vec::new();
+    for (i, (_, glyph)) in glyphs.iter().enumerate() {
+        if let Some((_, previous)) = i.checked_sub(1).map(|i| &glyphs[i]) {
+            text.push_str(glyph.separator(previous));
+        }
+        starts.push(text.len());
+        text.push_str(&glyph.text);
+    }
+    find(&text)
+        .into_iter()
+        .filter(|range| !range.is_empty())
+        .filter_map(|range| {
+            let mut first = starts
+                .partition_point(|s| *s <= range.start)
+                .saturating_sub(1);
+            // a match starting with a separator begins at the next glyph
+            if starts.get(first)? + glyphs[first].1.text.len() <= range.start {
+                first += 1;
+            }
+            let last = starts.partition_point(|s| *s < range.end);
+            if first >= last {
+                return None;
+            }
+            let mut rects: Vec<(usize, Rect)> = Vec::new();
+            let mut lines: Vec<Vec<&ShownGlyph>> = Vec::new();
+            let mut previous: Option<&ShownGlyph> = None;
+            for (op, glyph) in &glyphs[first..last] {
+                match rects.last_mut() {
+                    Some((last_op, rect)) if last_op == op => *rect = rect.union(glyph.bbox),
+                    _ => rects.push((*op, glyph.bbox)),
+                }
+                match (lines.last_mut(), previous) {
+                    (Some(line), Some(previous)) if glyph.separator(previous) != "\n" => {
+                        line.push(glyph)
+                    }
+                    _ => lines.push(vec![glyph]),
+                }
+                previous = Some(glyph);
+            }
+            Some(TextMatch {
+                text: text[range].to_string(),
+                rects: rects.into_iter().map(|(_, rect)| rect).collect(),
+                quads: lines.iter().filter_map(|line| line_quad(line)).collect(),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct Highlighter {
+    pub pattern: Regex,
+    pub color: Color,
+    // written as the annotation's title, which viewers show as its author
+    pub author: Option<String>,
+}
+
+impl Highlighter {
+    pub fn new(pattern: Regex) -> Self {
+        Self {
+            pattern,
+            color: Color::RGB(1., 1., 0.),
+            author: None,
+        }
+    }
+    // Adds a Highlight annotation over every match of the pattern on the
+    // page, leaving the content alone. Returns the number of matches.
+    pub fn highlight(&self, modifier: &mut PdfModifier, page_id: (u32, u16)) -> usize {
+        let matches = text_matches(modifier, page_id, |text| {
+            self.pattern.find_iter(text).map(|m| m.range()).collect()
+        });
+        for found in &matches {
+            let mut annotation = markup_annotation(
+                modifier.document_mut(),
+                "Highlight",
+                &found.quads,
+                &self.color,
+            );
+            annotation.set("Contents", text_string(&found.text));
+            if let Some(author) = &self.author {
+                annotation.set("T", text_string(author));
+            }
+            modifier.add_annotation(page_id, annotation);
+        }
+        matches.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_operations, testing};
+    use lopdf::Object;
+
+    fn quads(content: &str, pattern: &str) -> Vec<Quad> {
+        let (mut modifier, page_id) = testing::modifier(content);
+        let pattern = Regex::new(pattern).unwrap();
+        let matches = text_matches(&mut modifier, page_id, |text| {
+            pattern.find_iter(text).map(|m| m.range()).collect()
+        });
+        assert_eq!(matches.len(), 1);
+        matches[0].quads.clone()
+    }
+
+    #[test]
+    fn rotated_quad() {
+        // the baseline runs up the page from (100, 100)
+        let [[upper_left, upper_right, lower_left, lower_right]] =
+            quads("BT /F1 10 Tf 0 1 -1 0 100 100 Tm (Hello) Tj ET", "Hello")[..]
+        else {
+            panic!("not one quad");
+        };
+        assert!(upper_left.x < 100. && lower_left.x > 100.);
+        assert!((upper_left.y - 100.).abs() < 0.01 && (lower_left.y - 100.).abs() < 0.01);
+        assert!(upper_right.y > 120. && (upper_right.x - upper_left.x).abs() < 0.01);
+        assert!((lower_right.x - lower_left.x).abs() < 0.01);
+    }
+
+    #[test]
+    fn quad_per_line() {
+        let found = quads(
+            "BT /F1 10 Tf 12 TL 72 700 Td (Hello) Tj (World) ' ET",
+            r"Hello\sWorld",
+        );
+        assert_eq!(found.len(), 2);
+        // from the descent to the ascent of each line
+        assert!(found[0][2].y < 700. && found[0][0].y > 700. && found[0][0].y < 712.);
+        assert!(found[1][2].y < 688. && found[1][0].y > 688. && found[1][0].y < 700.);
+        assert!((found[1][0].x - 72.).abs() < 0.01);
+    }
+
+    #[test]
+    fn highlight_appearance() {
+        let (mut modifier, page_id) = testing::modifier("BT /F1 10 Tf 72 700 Td (Hello) Tj ET");
+        let highlighter = Highlighter::new(Regex::new("Hello").unwrap());
+        assert_eq!(highlighter.highlight(&mut modifier, page_id), 1);
+        let doc = modifier.document();
+        let annots = doc
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(|o| o.as_array())
+            .unwrap();
+        let annotation = doc
+            .get_dictionary(annots[0].as_reference().unwrap())
+            .unwrap();
+        let appearance = annotation
+            .get(b"AP")
+            .and_then(|o| o.as_dict())
+            .and_then(|ap| ap.get(b"N"))
+            .and_then(|o| o.as_reference())
+            .unwrap();
+        let Ok(Object::Stream(appearance)) = doc.get_object(appearance) else {
+            panic!("no appearance stream");
+        };
+        let operators: Vec<String> = decode_operations(&appearance.content)
+            .unwrap()
+            .into_iter()
+            .map(|o| o.operator)
+            .collect();
+        assert_eq!(operators, ["gs", "rg", "m", "l", "l", "l", "h", "f"]);
+    }
+}
@@ -0,0 +1,212 @@
+use kurbo::{BezPath, PathEl, Point};
+use lopdf::content::Operation;
+
+use crate::number_to_operand;
+
+#[derive(Debug, Clone)]
+pub struct PathSerializer {
+    // number of decimal places written for each coordinate
+    pub precision: usize,
+    // write axis-aligned closed rectangles as a single `re`
+    pub detect_rect: bool,
+}
+
+impl Default for PathSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PathSerializer {
+    pub fn new() -> Self {
+        Self {
+            precision: 3,
+            detect_rect: true,
+        }
+    }
+    pub fn serialize(&self, path: &BezPath) -> Vec<Operation> {
+        let mut operations = Vec::new();
+        let mut subpath: Vec<PathEl> = Vec::new();
+        // the start of the subpath and the current point, which a subpath
+        // following a close without a move of its own carries on from
+        let mut position = (Point::ZERO, Point::ZERO);
+        for el in path.elements() {
+            if let PathEl::MoveTo(_) = el {
+                self.serialize_subpath(&subpath, &mut position, &mut operations);
+                subpath.clear();
+            }
+            subpath.push(*el);
+            if let PathEl::ClosePath = el {
+                self.serialize_subpath(&subpath, &mut position, &mut operations);
+                subpath.clear();
+            }
+        }
+        self.serialize_subpath(&subpath, &mut position, &mut operations);
+        operations
+    }
+    pub fn serialize_all<'a, I>(&self, paths: I) -> Vec<Operation>
+    where
+        I: IntoIterator<Item = &'a BezPath>,
+    {
+        paths
+            .into_iter()
+            .flat_map(|path| self.serialize(path))
+            .collect()
+    }
+    fn serialize_subpath(
+        &self,
+        elements: &[PathEl],
+        (start, current): &mut (Point, Point),
+        operations: &mut Vec<Operation>,
+    ) {
+        if elements.is_empty() {
+            return;
+        }
+        if self.detect_rect {
+            if let Some((origin, width, height)) = Self::as_rect(elements) {
+                operations.push(self.operation("re", &[origin.x, origin.y, width, height]));
+                *start = origin;
+                *current = origin;
+                return;
+            }
+        }
+        for el in elements {
+            match el {
+                PathEl::MoveTo(p) => {
+                    operations.push(self.operation("m", &[p.x, p.y]));
+                    *start = *p;
+                    *current = *p;
+                }
+                PathEl::LineTo(p) => {
+                    operations.push(self.operation("l", &[p.x, p.y]));
+                    *current = *p;
+                }
+                PathEl::QuadTo(q, p) => {
+                    // a quadratic segment is written as the equivalent cubic
+                    let c1 = *current + (*q - *current) * (2. / 3.);
+                    let c2 = *p + (*q - *p) * (2. / 3.);
+                    operations.push(self.operation("c", &[c1.x, c1.y, c2.x, c2.y, p.x, p.y]));
+                    *current = *p;
+                }
+                PathEl::CurveTo(c1, c2, p) => {
+                    operations.push(self.operation("c", &[c1.x, c1.y, c2.x, c2.y, p.x, p.y]));
+                    *current = *p;
+                }
+                PathEl::ClosePath => {
+                    operations.push(Operation::new("h", vec![]));
+                    *current = *start;
+                }
+            }
+        }
+    }
+    // Matches the element sequence `re` itself produces: a move, three lines
+    // (optionally a fourth back to the start) and a close, going along x first.
+    fn as_rect(elements: &[PathEl]) -> Option<(Point, f64, f64)> {
+        let points = match elements {
+            [PathEl::MoveTo(p0), PathEl::LineTo(p1), PathEl::LineTo(p2), PathEl::LineTo(p3), PathEl::ClosePath] => {
+                [*p0, *p1, *p2, *p3]
+            }
+            [PathEl::MoveTo(p0), PathEl::LineTo(p1), PathEl::LineTo(p2), PathEl::LineTo(p3), PathEl::LineTo(p4), PathEl::ClosePath]
+                if p4 == p0 =>
+            {
+                [*p0, *p1, *p2, *p3]
+            }
+            _ => return None,
+        };
+        let [p0, p1, p2, p3] = points;
+        if p0.y == p1.y && p1.x == p2.x && p2.y == p3.y && p3.x == p0.x {
+            Some((p0, p1.x - p0.x, p2.y - p1.y))
+        } else {
+            None
+        }
+    }
+    fn operation(&self, operator: &str, values: &[f64]) -> Operation {
+        Operation::new(
+            operator,
+            values
+                .iter()
+                .map(|v| number_to_operand(*v, self.precision))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::Shape;
+    use lopdf::Object;
+
+    use super::*;
+    use crate::object_to_f32;
+
+    fn operators(operations: &[Operation]) -> Vec<&str> {
+        operations.iter().map(|o| o.operator.as_str()).collect()
+    }
+
+    fn values(operation: &Operation) -> Vec<f32> {
+        operation
+            .operands
+            .iter()
+            .map(|o| object_to_f32(o).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rect() {
+        let path = kurbo::Rect::new(10., 20., 40., 30.).to_path(0.1);
+        let operations = PathSerializer::new().serialize(&path);
+        assert_eq!(operators(&operations), ["re"]);
+        assert_eq!(values(&operations[0]), [10., 20., 30., 10.]);
+        let serializer = PathSerializer {
+            detect_rect: false,
+            ..PathSerializer::new()
+        };
+        assert_eq!(
+            operators(&serializer.serialize(&path)),
+            ["m", "l", "l", "l", "h"]
+        );
+        // a rotated square is not a rectangle for `re`
+        let mut diamond = BezPath::new();
+        diamond.move_to((0., 1.));
+        diamond.line_to((1., 0.));
+        diamond.line_to((0., -1.));
+        diamond.line_to((-1., 0.));
+        diamond.close_path();
+        assert_eq!(
+            operators(&PathSerializer::new().serialize(&diamond)),
+            ["m", "l", "l", "l", "h"]
+        );
+    }
+
+    #[test]
+    fn quad_to_cubic() {
+        // a subpath without a move of its own starts where the last closed
+        let path = BezPath::from_vec(vec![
+            PathEl::MoveTo((0., 0.).into()),
+            PathEl::QuadTo((3., 3.).into(), (6., 0.).into()),
+            PathEl::ClosePath,
+            PathEl::QuadTo((3., -3.).into(), (6., 0.).into()),
+        ]);
+        let operations = PathSerializer::new().serialize(&path);
+        assert_eq!(operators(&operations), ["m", "c", "h", "c"]);
+        assert_eq!(values(&operations[1]), [2., 2., 4., 2., 6., 0.]);
+        assert_eq!(values(&operations[3]), [2., -2., 4., -2., 6., 0.]);
+    }
+
+    #[test]
+    fn precision() {
+        let mut path = BezPath::new();
+        path.move_to((1.23456, 2.));
+        path.line_to((0.0004, -7.5));
+        let serializer = PathSerializer {
+            precision: 2,
+            ..PathSerializer::new()
+        };
+        let operations = serializer.serialize(&path);
+        assert_eq!(values(&operations[0]), [1.23, 2.]);
+        assert_eq!(values(&operations[1]), [0., -7.5]);
+        // whole numbers are written as integers
+        assert!(matches!(operations[0].operands[1], Object::Integer(2)));
+        assert!(matches!(operations[1].operands[0], Object::Integer(0)));
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+
+use kurbo::{Affine, Point, Rect, Vec2};
+use lopdf::content::Operation;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::graphics::color::Color;
+use crate::{
+    color_operators, encode_operations, number_array, page_color_operators, page_resource, Font,
+    PdfModifier,
+};
+
+#[derive(Debug, Clone)]
+pub enum StampContent {
+    // in one of the standard fonts, which need not be embedded
+    Text {
+        text: String,
+        font: String,
+        size: f64,
+        color: Color,
+    },
+    // a JPEG file, one point per pixel before scaling
+    Image(Vec<u8>),
+    // a page of another document, by index
+    Page(Document, usize),
+}
+
+#[derive(Debug, Clone)]
+pub enum StampPosition {
+    // where the stamp sits inside the margins, from (0, 0) at the bottom
+    // left to (1, 1) at the top right
+    Relative(f64, f64),
+    // the centre of the stamp in default user space
+    Absolute(Point),
+}
+
+#[derive(Debug, Clone)]
+pub struct Stamp {
+    pub content: StampContent,
+    pub opacity: f32,
+    // counterclockwise, in degrees
+    pub rotation: f64,
+    pub position: StampPosition,
+    pub margin: f64,
+    // scales the stamp to this width before rotating it
+    pub width: Option<f64>,
+    // paints the stamp beneath the page content
+    pub underlay: bool,
+}
+
+// Copies an object from another document, with everything it refers to.
+fn import(
+    target: &mut Document,
+    source: &Document,
+    object: &Object,
+    ids: &mut HashMap<ObjectId, ObjectId>,
+) -> Object {
+    match object {
+        Object::Reference(id) => {
+            if let Some(new_id) = ids.get(id) {
+                return Object::Reference(*new_id);
+            }
+            let new_id = target.new_object_id();
+            ids.insert(*id, new_id);
+            let copy = match source.get_object(*id) {
+                Ok(object) => import(target, source, object, ids),
+                Err(_) => Object::Null,
+            };
+            target.objects.insert(new_id, copy);
+            Object::Reference(new_id)
+        }
+        Object::Array(array) => Object::Array(
+            array
+                .iter()
+                .map(|o| import(target, source, o, ids))
+                .collect(),
+        ),
+        Object::Dictionary(dict) => {
+            Object::Dictionary(import_dictionary(target, source, dict, ids))
+        }
+        Object::Stream(stream) => {
+            let mut copy = stream.clone();
+            copy.dict = import_dictionary(target, source, &stream.dict, ids);
+            Object::Stream(copy)
+        }
+        object => object.clone(),
+    }
+}
+
+fn import_dictionary(
+    target: &mut Document,
+    source: &Document,
+    dict: &Dictionary,
+    ids: &mut HashMap<ObjectId, ObjectId>,
+) -> Dictionary {
+    let mut copy = Dictionary::new();
+    for (key, value) in dict {
+        // pages of the source document are not followed
+        if key != b"Parent" {
+            copy.set(key.clone(), import(target, source, value, ids));
+        }
+    }
+    copy
+}
+
+// The name of a page resource referring to `id`: the one there is, as
+// pages may share resources, or a new one.
+pub fn resource_name(
+    modifier: &PdfModifier,
+    page_id: (u32, u16),
+    category: &[u8],
+    prefix: &str,
+    id: ObjectId,
+) -> (Vec<u8>, bool) {
+    let taken = page_resource(modifier.document(), page_id, category);
+    if let Some((name, _)) = taken.and_then(|dict| {
+        dict.iter()
+            .find(|(_, value)| value.as_reference().ok() == Some(id))
+    }) {
+        return (name.clone(), true);
+    }
+    let name = (1..)
+        .map(|n| format!("{}{}", prefix, n).into_bytes())
+        .find(|name| !taken.is_some_and(|dict| dict.has(name)))
+        .unwrap();
+    (name, false)
+}
+
+// A standard font for stamp text, which viewers have without embedding.
+fn text_font(font: &str) -> Dictionary {
+    dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => Object::Name(font.as_bytes().to_vec()),
+        "Encoding" => "WinAnsiEncoding",
+    }
+}
+
+// The operations showing `text` in the font of resource `name`, and their
+// box. The colour is set by the caller.
+fn text_operations(
+    metrics: &Font,
+    name: &[u8],
+    text: &str,
+    size: f64,
+) -> Result<(Rect, Vec<Operation>), String> {
+    let encoded = metrics
+        .encode(text)
+        .map_err(|c| format!("{} has no glyph for {:?}", metrics.base_font, c))?;
+    let width: f64 = encoded
+        .iter()
+        .map(|c| metrics.width(*c as u32) as f64)
+        .sum::<f64>()
+        * size
+        / 1000.;
+    let bbox = Rect::new(
+        0.,
+        metrics.descent as f64 * size / 1000.,
+        width,
+        metrics.ascent as f64 * size / 1000.,
+    );
+    let operations = vec![
+        Operation::new("BT", vec![]),
+        Operation::new(
+            "Tf",
+            vec![Object::Name(name.to_vec()), Object::Real(size as f32)],
+        ),
+        Operation::new("Tj", vec![Object::string_literal(encoded)]),
+        Operation::new("ET", vec![]),
+    ];
+    Ok((bbox, operations))
+}
+
+impl Stamp {
+    pub fn new(content: StampContent) -> Self {
+        Self {
+            content,
+            opacity: 1.,
+            rotation: 0.,
+            position: StampPosition::Relative(0.5, 0.5),
+            margin: 36.,
+            width: None,
+            underlay: false,
+        }
+    }
+    // A form XObject painting the stamp, and its box.
+    fn form(&self, doc: &mut Document) -> Result<(ObjectId, Rect), String> {
+        let (bbox, resources, content) = match &self.content {
+            StampContent::Text {
+                text,
+                font,
+                size,
+                color,
+            } => {
+                let font_dict = text_font(font);
+                let metrics = Font::from_dict(doc, &font_dict);
+                let (bbox, text) = text_operations(&metrics, b"F0", text, *size)?;
+                let (mut operations, space) = color_operators(doc, color, false);
+                operations.extend(text);
+                let font_id = doc.add_object(font_dict);
+                let mut resources = dictionary! { "Font" => dictionary! { "F0" => font_id } };
+                if let Some((name, space)) = space {
+                    resources.set("ColorSpace", dictionary! { name => space });
+                }
+                (bbox, resources, operations)
+            }
+            StampContent::Image(data) => {
+                let mut decoder = jpeg_decoder::Decoder::new(data.as_slice());
+                decoder.read_info().map_err(|e| e.to_string())?;
+                let info = decoder.info().ok_or("not a JPEG image")?;
+                let space = match info.pixel_format {
+                    jpeg_decoder::PixelFormat::L8 => "DeviceGray",
+                    jpeg_decoder::PixelFormat::CMYK32 => "DeviceCMYK",
+                    jpeg_decoder::PixelFormat::RGB24 => "DeviceRGB",
+                    _ => return Err("unsupported JPEG pixel format".to_string()),
+                };
+                let (width, height) = (info.width as f64, info.height as f64);
+                let image = Stream::new(
+                    dictionary! {
+                        "Type" => "XObject",
+                        "Subtype" => "Image",
+                        "Width" => info.width as i64,
+                        "Height" => info.height as i64,
+                        "ColorSpace" => space,
+                        "BitsPerComponent" => 8,
+                        "Filter" => "DCTDecode",
+                    },
+                    data.clone(),
+                );
+                let image_id = doc.add_object(image);
+                let operations = vec![
+                    Operation::new(
+                        "cm",
+                        [width, 0., 0., height, 0., 0.]
+                            .map(|v| Object::Real(v as f32))
+                            .to_vec(),
+                    ),
+                    Operation::new("Do", vec![Object::Name(b"Im0".to_vec())]),
+                ];
+                (
+                    Rect::new(0., 0., width, height),
+                    dictionary! { "XObject" => dictionary! { "Im0" => image_id } },
+                    operations,
+                )
+            }
+            StampContent::Page(source, index) => {
+                let page_id = *source
+                    .get_pages()
+                    .values()
+                    .nth(*index)
+                    .ok_or(format!("the document has no page {}", index + 1))?;
+                // boundaries and resources may be inherited
+                let mut bbox = None;
+                let mut resources = Vec::new();
+                let mut dict = source.get_dictionary(page_id).ok();
+                while let Some(page) = dict {
+                    for key in [b"CropBox".as_slice(), b"MediaBox"] {
+                        if bbox.is_none() {
+                            bbox = page
+                                .get(key)
+                                .and_then(|o| source.dereference(o))
+                                .and_then(|(_, o)| o.as_array())
+                                .ok()
+                                .map(|a| {
+                                    a.iter()
+                                        .filter_map(|o| {
+                                            o.as_float().or(o.as_i64().map(|v| v as f32)).ok()
+                                        })
+                                        .map(f64::from)
+                                        .collect::<Vec<_>>()
+                                })
+                                .and_then(|v| match v[..] {
+                                    [x0, y0, x1, y1] => Some(Rect::new(x0, y0, x1, y1).abs()),
+                                    _ => None,
+                                });
+                        }
+                    }
+                    if let Ok((_, Object::Dictionary(r))) =
+                        page.get(b"Resources").and_then(|o| source.dereference(o))
+                    {
+                        resources.push(r.clone());
+                    }
+                    dict = page
+                        .get(b"Parent")
+                        .and_then(|o| o.as_reference())
+                        .and_then(|id| source.get_dictionary(id))
+                        .ok();
+                }
+                let content = source
+                    .get_page_content(page_id)
+                    .map_err(|e| e.to_string())?;
+                let mut merged = Dictionary::new();
+                for r in resources.iter().rev() {
+                    for (key, value) in r {
+                        merged.set(key.clone(), value.clone());
+                    }
+                }
+                let mut ids = HashMap::new();
+                let resources = import_dictionary(doc, source, &merged, &mut ids);
+                let bbox = bbox.unwrap_or(Rect::new(0., 0., 612., 792.));
+                let form = Stream::new(
+                    dictionary! {
+                        "Type" => "XObject",
+                        "Subtype" => "Form",
+                        "BBox" => number_array(&[bbox.x0, bbox.y0, bbox.x1, bbox.y1]),
+                        "Resources" => resources,
+                    },
+                    content,
+                );
+                return Ok((doc.add_object(form), bbox));
+            }
+        };
+        let form = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => number_array(&[bbox.x0, bbox.y0, bbox.x1, bbox.y1]),
+                "Resources" => resources,
+            },
+            encode_operations(&content).map_err(|e| e.to_string())?,
+        );
+        Ok((doc.add_object(form), bbox))
+    }
+    // The matrix placing the form's box on a page.
+    fn placement(&self, bbox: Rect, page: Rect) -> Affine {
+        let scale = match self.width {
+            Some(width) if bbox.width() > 0. => width / bbox.width(),
+            _ => 1.,
+        };
+        let centered = Affine::rotate(self.rotation.to_radians())
+            * Affine::scale(scale)
+            * Affine::translate(-bbox.center().to_vec2());
+        let extent = centered.transform_rect_bbox(bbox);
+        let target = match self.position {
+            StampPosition::Relative(x, y) => {
+                let area = page.inflate(-self.margin, -self.margin);
+                Point::new(
+                    area.x0 + extent.width() / 2. + x * (area.width() - extent.width()),
+                    area.y0 + extent.height() / 2. + y * (area.height() - extent.height()),
+                )
+            }
+            StampPosition::Absolute(point) => point,
+        };
+        Affine::translate(Vec2::new(target.x, target.y)) * centered
+    }
+    fn graphics_state(&self, modifier: &mut PdfModifier) -> Option<ObjectId> {
+        (self.opacity < 1.).then(|| {
+            modifier.document_mut().add_object(dictionary! {
+                "Type" => "ExtGState",
+                "ca" => self.opacity,
+                "CA" => self.opacity,
+            })
+        })
+    }
+    // Paints `content`, which is drawn inside `bbox`, at the stamp's place
+    // on a page.
+    fn place(
+        &self,
+        modifier: &mut PdfModifier,
+        page_id: (u32, u16),
+        bbox: Rect,
+        gs_id: Option<ObjectId>,
+        content: Vec<Operation>,
+    ) {
+        let page = modifier
+            .visible_box(page_id)
+            .unwrap_or(Rect::new(0., 0., 612., 792.));
+        let mut operations = Vec::new();
+        if let Some(gs_id) = gs_id {
+            let (gs, present) = resource_name(modifier, page_id, b"ExtGState", "StampGS", gs_id);
+            if !present {
+                modifier.add_resource(page_id, "ExtGState", &gs, Object::Reference(gs_id));
+            }
+            operations.push(Operation::new("gs", vec![Object::Name(gs)]));
+        }
+        let matrix = self.placement(bbox, page).as_coeffs();
+        operations.push(Operation::new(
+            "cm",
+            matrix.map(|v| Object::Real(v as f32)).to_vec(),
+        ));
+        operations.extend(content);
+        modifier.insert_content(page_id, &operations, self.underlay);
+    }
+    // Stamps the pages, the form painting the stamp being shared by all of
+    // them.
+    pub fn apply(&self, modifier: &mut PdfModifier, pages: &[(u32, u16)]) -> Result<(), String> {
+        let (form_id, bbox) = self.form(modifier.document_mut())?;
+        let gs_id = self.graphics_state(modifier);
+        for page_id in pages {
+            let (name, present) = resource_name(modifier, *page_id, b"XObject", "Stamp", form_id);
+            if !present {
+                modifier.add_resource(*page_id, "XObject", &name, Object::Reference(form_id));
+            }
+            let content = vec![Operation::new("Do", vec![Object::Name(name)])];
+            self.place(modifier, *page_id, bbox, gs_id, content);
+        }
+        Ok(())
+    }
+    // Stamps a different text on each page in the font, size and colour of
+    // the stamp's text, such as page numbers. The text goes into the page
+    // content, all pages sharing the font.
+    pub fn apply_texts(
+        &self,
+        modifier: &mut PdfModifier,
+        texts: &[((u32, u16), String)],
+    ) -> Result<(), String> {
+        let StampContent::Text {
+            font, size, color, ..
+        } = &self.content
+        else {
+            return Err("the stamp is not text".to_string());
+        };
+        let font_dict = text_font(font);
+        let metrics = Font::from_dict(modifier.document(), &font_dict);
+        // checked before anything is added to the document
+        let operations = texts
+            .iter()
+            .map(|(_, text)| text_operations(&metrics, b"F0", text, *size))
+            .collect::<Result<Vec<_>, _>>()?;
+        let font_id = modifier.document_mut().add_object(font_dict);
+        let gs_id = self.graphics_state(modifier);
+        for ((page_id, _), (bbox, mut text)) in texts.iter().zip(operations) {
+            let (name, present) = resource_name(modifier, *page_id, b"Font", "StampF", font_id);
+            if !present {
+                modifier.add_resource(*page_id, "Font", &name, Object::Reference(font_id));
+            }
+            for operation in &mut text {
+                if operation.operator == "Tf" {
+                    operation.operands[0] = Object::Name(name.clone());
+                }
+            }
+            let mut content = page_color_operators(modifier, *page_id, color, false);
+            content.extend(text);
+            self.place(modifier, *page_id, bbox, gs_id, content);
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,58 @@
 use std::collections::HashMap;
 
-use lopdf::{content::Operation, Dictionary, Document};
+use kurbo::{Affine, Point, Rect};
+use lopdf::{content::Operation, Dictionary, Document, Object};
 
+use self::graphics::color::ColorSpace;
 use self::graphics::GraphicsState;
+use crate::{page_fonts, page_resource, Font};
 
 pub mod graphics;
 pub mod path;
 
+// A glyph of a text showing operation: where its code is in the string
+// operands and where it is painted.
+#[derive(Debug, Clone)]
+pub struct ShownGlyph {
+    // index of the string among the operands, or in the `TJ` array
+    pub element: usize,
+    // byte range of the code in the string
+    pub start: usize,
+    pub end: usize,
+    pub code: u32,
+    pub text: String,
+    // in default user space
+    pub origin: Point,
+    pub bbox: Rect,
+    // where a glyph following on is shown
+    pub next_origin: Point,
+    // from units of the font size, along the baseline from the origin, to
+    // default user space
+    pub em: Affine,
+    // the glyph's box in units of the font size
+    pub extent: Rect,
+}
+
+impl ShownGlyph {
+    // What separates this glyph from the one shown before it when the text
+    // of a page is read: nothing when it follows on, a space after a gap
+    // along the same baseline and a line break when it is elsewhere.
+    pub fn separator(&self, previous: &ShownGlyph) -> &'static str {
+        if previous.em.determinant().abs() < 1e-12 {
+            return "\n";
+        }
+        let inverse = previous.em.inverse();
+        let offset = inverse * self.origin - inverse * previous.next_origin;
+        if offset.y.abs() > 0.5 || offset.x < -0.5 || offset.x > 3. {
+            "\n"
+        } else if offset.x > 0.15 && previous.text != " " && self.text != " " {
+            " "
+        } else {
+            ""
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct State {
     pub id: usize,
@@ -14,7 +60,15 @@ pub struct State {
     pub graphics: GraphicsState,
     pub path: path::Path,
     graphics_dict: HashMap<Vec<u8>, Dictionary>,
+    color_spaces: HashMap<Vec<u8>, ColorSpace>,
     graphics_stack: Vec<GraphicsState>,
+    clip_pending: bool,
+    fonts: HashMap<Vec<u8>, Font>,
+    // used for fonts that are not in the resources
+    fallback_font: Font,
+    // horizontal displacement of the last string shown, applied to the text
+    // matrix before the next operation
+    advance: f64,
 }
 
 impl State {
@@ -23,18 +77,192 @@ impl State {
             id: 0,
             graphics: GraphicsState::new(),
             path: path::Path::new(),
-            graphics_dict: Self::extgstate_map(doc, page_id),
+            graphics_dict: Self::extgstate_map(doc, page_resource(doc, page_id, b"ExtGState")),
+            color_spaces: Self::color_space_map(doc, page_resource(doc, page_id, b"ColorSpace")),
             graphics_stack: Vec::new(),
+            clip_pending: false,
+            fonts: page_fonts(doc, page_id),
+            fallback_font: Font::from_dict(doc, &Dictionary::new()),
+            advance: 0.,
         }
     }
-    fn extgstate_map(doc: &Document, page_id: (u32, u16)) -> HashMap<Vec<u8>, Dictionary> {
+    // State for a form XObject or tiling pattern, which carries its own resources.
+    pub fn from_resources(doc: &Document, resources: Option<&Dictionary>) -> Self {
+        let category = |key: &[u8]| {
+            resources
+                .and_then(|r| r.get(key).ok())
+                .and_then(|o| doc.dereference(o).ok())
+                .and_then(|(_, o)| o.as_dict().ok())
+        };
+        Self {
+            id: 0,
+            graphics: GraphicsState::new(),
+            path: path::Path::new(),
+            graphics_dict: Self::extgstate_map(doc, category(b"ExtGState")),
+            color_spaces: Self::color_space_map(doc, category(b"ColorSpace")),
+            graphics_stack: Vec::new(),
+            clip_pending: false,
+            fonts: category(b"Font")
+                .map(|fonts| {
+                    fonts
+                        .iter()
+                        .filter_map(|(name, font)| match doc.dereference(font) {
+                            Ok((_, Object::Dictionary(dict))) => {
+                                Some((name.clone(), Font::from_dict(doc, dict)))
+                            }
+                            _ => None,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            fallback_font: Font::from_dict(doc, &Dictionary::new()),
+            advance: 0.,
+        }
+    }
+    // The font set with `Tf`.
+    pub fn font(&self) -> &Font {
+        self.graphics
+            .text
+            .font
+            .as_ref()
+            .and_then(|name| self.fonts.get(name))
+            .unwrap_or(&self.fallback_font)
+    }
+    // Lays out the glyphs a text showing operation paints, from the text
+    // matrix at its start. Vertical writing is not supported.
+    pub fn shown_glyphs(&self, operation: &Operation) -> Vec<ShownGlyph> {
+        self.layout(operation).0
+    }
+    // A `TJ` that moves the text position as far as a showing operation
+    // does without painting, for operations that are removed. Adjustments
+    // scale with the font size, so at size 0, where character spacing still
+    // moves the position, the size is set to 1 meanwhile.
+    pub fn skip_operations(&self, operation: &Operation) -> Vec<Operation> {
+        let text = &self.graphics.text;
+        let advance = self.layout(operation).1;
+        let scaling = f64::from(text.horizontal_scaling) / 100.;
+        if advance == 0. || scaling == 0. {
+            return Vec::new();
+        }
+        let size = f64::from(text.font_size.unwrap_or(0.));
+        let adjustment = |size: f64| {
+            Operation::new(
+                "TJ",
+                vec![Object::Array(vec![Object::Real(
+                    (-advance * 1000. / (size * scaling)) as f32,
+                )])],
+            )
+        };
+        match &text.font {
+            Some(font) if size == 0. => {
+                let font_size = |size: i64| {
+                    Operation::new("Tf", vec![Object::Name(font.clone()), Object::from(size)])
+                };
+                vec![font_size(1), adjustment(1.), font_size(0)]
+            }
+            _ if size == 0. => Vec::new(),
+            _ => vec![adjustment(size)],
+        }
+    }
+    // The text a showing operation paints. Large negative adjustments in a
+    // `TJ` array are taken as word gaps.
+    pub fn shown_text(&self, operation: &Operation) -> String {
+        let adjustments: &[Object] = match (operation.operator.as_ref(), &operation.operands[..]) {
+            ("TJ", [Object::Array(array)]) => array,
+            _ => &[],
+        };
+        let mut text = String::new();
+        let mut element = None;
+        for glyph in self.shown_glyphs(operation) {
+            if let Some(previous) = element.filter(|e| *e < glyph.element) {
+                let gap: f32 = adjustments[previous + 1..glyph.element]
+                    .iter()
+                    .filter_map(|o| o.as_float().or(o.as_i64().map(|v| v as f32)).ok())
+                    .sum();
+                if gap < -200. && !text.ends_with(' ') && glyph.text != " " {
+                    text.push(' ');
+                }
+            }
+            element = Some(glyph.element);
+            text.push_str(&glyph.text);
+        }
+        text
+    }
+    fn layout(&self, operation: &Operation) -> (Vec<ShownGlyph>, f64) {
+        let text = &self.graphics.text;
+        let font = self.font();
+        let size = f64::from(text.font_size.unwrap_or(0.));
+        let scaling = f64::from(text.horizontal_scaling) / 100.;
+        let rise = f64::from(text.rise);
+        let matrix = self.graphics.ctm * text.matrix;
+        let elements: Vec<(usize, &Object)> =
+            match (operation.operator.as_ref(), &operation.operands[..]) {
+                ("TJ", [Object::Array(array)]) => array.iter().enumerate().collect(),
+                ("Tj" | "'", [string]) => vec![(0, string)],
+                ("\"", [_, _, string]) => vec![(2, string)],
+                _ => Vec::new(),
+            };
+        let length = font.code_length();
+        let mut glyphs = Vec::new();
+        let mut x = 0.;
+        for (element, object) in elements {
+            let bytes = match object {
+                Object::String(bytes, _) => bytes,
+                adjustment => {
+                    let adjustment = adjustment
+                        .as_float()
+                        .or(adjustment.as_i64().map(|v| v as f32))
+                        .unwrap_or(0.);
+                    x -= f64::from(adjustment) / 1000. * size * scaling;
+                    continue;
+                }
+            };
+            for (i, code) in font.codes(bytes).into_iter().enumerate() {
+                let width = f64::from(font.width(code)) / 1000. * size;
+                let glyph_matrix =
+                    matrix * Affine::translate((x, rise)) * Affine::scale_non_uniform(scaling, 1.);
+                let extent = Rect::new(
+                    0.,
+                    f64::from(font.descent) / 1000. * size,
+                    width,
+                    f64::from(font.ascent) / 1000. * size,
+                );
+                // word spacing applies to the single byte code 32 only
+                let spacing = f64::from(text.charactor_spacing)
+                    + if length == 1 && code == 32 {
+                        f64::from(text.word_spacing)
+                    } else {
+                        0.
+                    };
+                let advance = (width + spacing) * scaling;
+                glyphs.push(ShownGlyph {
+                    element,
+                    start: i * length,
+                    end: ((i + 1) * length).min(bytes.len()),
+                    code,
+                    text: font.unicode(code).unwrap_or("\u{fffd}").to_string(),
+                    origin: glyph_matrix * Point::ZERO,
+                    bbox: glyph_matrix.transform_rect_bbox(extent),
+                    next_origin: matrix * Point::new(x + advance, rise),
+                    em: glyph_matrix * Affine::scale(size),
+                    extent: Rect::new(
+                        0.,
+                        f64::from(font.descent) / 1000.,
+                        f64::from(font.width(code)) / 1000.,
+                        f64::from(font.ascent) / 1000.,
+                    ),
+                });
+                x += advance;
+            }
+        }
+        (glyphs, x)
+    }
+    fn extgstate_map(
+        doc: &Document,
+        resource_dict: Option<&Dictionary>,
+    ) -> HashMap<Vec<u8>, Dictionary> {
         let mut result = HashMap::new();
-        if let Some(resource_dict) = doc
-            .get_page_resources(page_id)
-            .0
-            .and_then(|resource| resource.get(b"ExtGState").ok())
-            .and_then(|d| d.as_dict().ok())
-        {
+        if let Some(resource_dict) = resource_dict {
             for (k, v) in resource_dict {
                 let extgstate_dict = v
                     .as_reference()
@@ -47,8 +275,24 @@ impl State {
         }
         result
     }
+    fn color_space_map(
+        doc: &Document,
+        resource_dict: Option<&Dictionary>,
+    ) -> HashMap<Vec<u8>, ColorSpace> {
+        let mut result = HashMap::new();
+        if let Some(resource_dict) = resource_dict {
+            for (k, v) in resource_dict {
+                result.insert(k.to_vec(), ColorSpace::from_resource(doc, k, v));
+            }
+        }
+        result
+    }
     pub fn handle_operation(&mut self, operation: &Operation) {
         self.id += 1;
+        if self.advance != 0. {
+            self.graphics.text.matrix *= Affine::translate((self.advance, 0.));
+            self.advance = 0.;
+        }
         self.path.handle_operation(&operation);
         self.graphics.handle_operation(&operation);
         match operation.operator.as_ref() {
@@ -60,6 +304,39 @@ impl State {
                     self.graphics = state;
                 }
             }
+            "W" | "W*" => {
+                self.clip_pending = true;
+            }
+            "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "n" if self.clip_pending => {
+                self.clip_pending = false;
+                if let Some(bbox) = self.path.bounding_box() {
+                    let clip = self.graphics.ctm.transform_rect_bbox(bbox);
+                    self.graphics.clip = Some(match self.graphics.clip {
+                        Some(current) => current.intersect(clip),
+                        None => clip,
+                    });
+                }
+            }
+            "cs" | "CS" => {
+                // device spaces are handled by the colour state itself
+                if let Some(name) = operation
+                    .operands
+                    .first()
+                    .and_then(|o| o.as_name().ok())
+                    .filter(|name| ColorSpace::from_name(name).is_none())
+                {
+                    let space = self
+                        .color_spaces
+                        .get(name)
+                        .cloned()
+                        .unwrap_or_else(|| ColorSpace::Resource(name.to_vec(), None));
+                    let stroke = operation.operator == "CS";
+                    self.graphics.color.set_space(stroke, space);
+                }
+            }
+            "Tj" | "TJ" | "'" | "\"" => {
+                self.advance = self.layout(operation).1;
+            }
             "gs" => {
                 if let Some(dict) = operation
                     .operands
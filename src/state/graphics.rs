@@ -14,6 +14,7 @@ pub struct GraphicsState {
     pub ctm: kurbo::Affine,
 
     //clipping path
+    pub clip: Option<kurbo::Rect>,
 
     //color space
     //color
@@ -48,6 +49,7 @@ impl GraphicsState {
     pub fn new() -> Self {
         Self {
             ctm: kurbo::Affine::IDENTITY,
+            clip: None,
             color: ColorState::new(),
             text: Text::new(),
             line: Line::new(),
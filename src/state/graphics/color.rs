@@ -1,28 +1,32 @@
 use std::fmt::Display;
 
 use crate::*;
-use lopdf::{content::Operation, Object};
+use lopdf::{content::Operation, Document, Object};
 
-#[derive(Debug, Clone)]
+use self::convert::{lab_to_srgb, ColorConverter, ColorFamily};
+
+pub mod convert;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Color {
     CMYK(f32, f32, f32, f32),
     RGB(f32, f32, f32),
     Gray(f32),
+    // L* in 0..100, a* and b* around 0
+    Lab(f32, f32, f32),
+    // a named ink and its tint, 0..1
+    Spot(String, f32),
 }
 
 impl Display for Color {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Gray(g) => write!(f, "gray({})", 255. * g),
-            Self::RGB(r, g, b) => write!(f, "rgb({},{},{})", 255. * r, 255. * g, 255. * b),
-            Self::CMYK(c, m, y, k) => write!(
-                f,
-                "cmyk({},{},{},{})",
-                255. * c,
-                255. * m,
-                255. * y,
-                255. * k
-            ),
+            Self::Gray(g) => write!(f, "gray({})", g),
+            Self::RGB(r, g, b) => write!(f, "rgb({},{},{})", r, g, b),
+            Self::CMYK(c, m, y, k) => write!(f, "cmyk({},{},{},{})", c, m, y, k),
+            Self::Lab(l, a, b) => write!(f, "lab({},{},{})", l, a, b),
+            Self::Spot(name, tint) if *tint == 1. => write!(f, "spot({})", name),
+            Self::Spot(name, tint) => write!(f, "spot({},{})", name, tint),
         }
     }
 }
@@ -48,41 +52,31 @@ impl Color {
                     *self = Self::CMYK(*c, *m, *y, *k);
                 }
             }
-            "cs" | "CS" => {
-                if let Some(name) = operation.operands.get(0).and_then(|o| o.as_name_str().ok()) {
-                    match name {
-                        "DeviceGray" => {
-                            *self = Self::Gray(0.);
-                        }
-                        "DeviceRGB" => {
-                            *self = Self::RGB(0., 0., 0.);
-                        }
-                        "DeviceCMYK" => {
-                            *self = Self::CMYK(0., 0., 0., 1.);
-                        }
-                        _ => (),
-                    }
-                }
-            }
             _ => (),
         }
     }
     pub fn equals_to(&self, rhs: &Self) -> bool {
-        let l = Self::to_rgb(&self);
-        let r = Self::to_rgb(&rhs);
-        (l.0 - r.0).abs() <= f32::EPSILON
-            && (l.1 - r.1).abs() <= f32::EPSILON
-            && (l.2 - r.2).abs() <= f32::EPSILON
-    }
-    fn to_rgb(color: &Self) -> (f32, f32, f32) {
-        match color {
-            Self::Gray(g) => (*g, *g, *g),
-            Self::RGB(r, g, b) => (*r, *g, *b),
-            Self::CMYK(c, m, y, k) => (
-                255. * (1. - c) * (1. - k),
-                255. * (1. - m) * (1. - k),
-                255. * (1. - y) * (1. - k),
-            ),
+        ColorConverter::naive().equals(self, rhs)
+    }
+    pub fn matches(&self, rhs: &Self, tolerance: &ColorTolerance) -> bool {
+        ColorConverter::naive().matches(self, rhs, tolerance)
+    }
+    pub fn to_rgb(&self) -> (f32, f32, f32) {
+        ColorConverter::naive().to_rgb(self)
+    }
+    pub fn to_lab(&self) -> (f32, f32, f32) {
+        ColorConverter::naive().to_lab(self)
+    }
+    pub fn delta_e(&self, rhs: &Self) -> f32 {
+        ColorConverter::naive().delta_e(self, rhs)
+    }
+    pub fn components(&self) -> Vec<f32> {
+        match self {
+            Self::Gray(g) => vec![*g],
+            Self::RGB(r, g, b) => vec![*r, *g, *b],
+            Self::CMYK(c, m, y, k) => vec![*c, *m, *y, *k],
+            Self::Lab(l, a, b) => vec![*l, *a, *b],
+            Self::Spot(_, tint) => vec![*tint],
         }
     }
     pub fn into_operator(&self, stroke: bool) -> Operation {
@@ -107,14 +101,156 @@ impl Color {
             Self::CMYK(c, m, y, k) => {
                 create_operation("k", [c, m, y, k].map(|v| Object::from(*v)).to_vec())
             }
+            // Lab needs a colour space resource, so it is written as sRGB
+            Self::Lab(l, a, b) => {
+                let (r, g, b) = lab_to_srgb((*l, *a, *b));
+                create_operation("rg", [r, g, b].map(Object::from).to_vec())
+            }
+            // so does a spot colour, approximated by its process equivalent;
+            // `color_operators` keeps the ink by adding the space
+            Self::Spot(..) => {
+                let (r, g, b) = self.to_rgb();
+                create_operation("rg", [r, g, b].map(Object::from).to_vec())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ColorTolerance {
+    Exact,
+    // largest allowed difference of each RGB component, 0..1
+    Channel(f32),
+    // largest allowed CIEDE2000 difference
+    DeltaE(f32),
+}
+
+impl Display for ColorTolerance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact => write!(f, "exact"),
+            Self::Channel(t) => write!(f, "channel:{}", t),
+            Self::DeltaE(t) => write!(f, "de:{}", t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorSpace {
+    Device(ColorFamily),
+    // a space from the page's `/ColorSpace` resources, with the family its
+    // components are read in when that is known
+    Resource(Vec<u8>, Option<ColorFamily>),
+    // a Separation resource (or DeviceN with one colorant) and its ink name
+    Separation(Vec<u8>, String),
+}
+
+impl ColorSpace {
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"DeviceGray" => Some(Self::Device(ColorFamily::Gray)),
+            b"DeviceRGB" => Some(Self::Device(ColorFamily::RGB)),
+            b"DeviceCMYK" => Some(Self::Device(ColorFamily::CMYK)),
+            _ => None,
+        }
+    }
+    // Reads a `/ColorSpace` resource entry.
+    pub fn from_resource(doc: &Document, name: &[u8], object: &Object) -> Self {
+        let ink = match doc.dereference(object).map(|(_, o)| o) {
+            Ok(Object::Array(array)) => match &array[..] {
+                [Object::Name(kind), Object::Name(ink), ..] if kind == b"Separation" => Some(ink),
+                [Object::Name(kind), Object::Array(inks), ..] if kind == b"DeviceN" => {
+                    match &inks[..] {
+                        [Object::Name(ink)] => Some(ink),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(ink) = ink {
+            return Self::Separation(name.to_vec(), String::from_utf8_lossy(ink).to_string());
+        }
+        let family = match doc.dereference(object).map(|(_, o)| o) {
+            Ok(Object::Name(base)) => Self::from_name(base).and_then(|s| s.family()),
+            Ok(Object::Array(array)) => match array.first().and_then(|o| o.as_name().ok()) {
+                Some(b"CalGray") => Some(ColorFamily::Gray),
+                Some(b"CalRGB") => Some(ColorFamily::RGB),
+                Some(b"Lab") => Some(ColorFamily::Lab),
+                Some(b"ICCBased") => array
+                    .get(1)
+                    .and_then(|o| doc.dereference(o).ok())
+                    .and_then(|(_, o)| o.as_stream().ok())
+                    .and_then(|stream| stream.dict.get(b"N").and_then(|n| n.as_i64()).ok())
+                    .and_then(|n| match n {
+                        1 => Some(ColorFamily::Gray),
+                        3 => Some(ColorFamily::RGB),
+                        4 => Some(ColorFamily::CMYK),
+                        _ => None,
+                    }),
+                _ => None,
+            },
+            _ => None,
+        };
+        Self::Resource(name.to_vec(), family)
+    }
+    pub fn of(color: &Color) -> Option<Self> {
+        ColorFamily::of(color).map(Self::Device)
+    }
+    pub fn family(&self) -> Option<ColorFamily> {
+        match self {
+            Self::Device(family) => Some(*family),
+            Self::Resource(_, family) => *family,
+            Self::Separation(..) => None,
+        }
+    }
+    // Whether colours in this space can be represented by `Color`.
+    pub fn is_tracked(&self) -> bool {
+        self.family().is_some() || matches!(self, Self::Separation(..))
+    }
+    // The colour a space starts with when it is selected.
+    pub fn initial_color(&self) -> Option<Color> {
+        if let Self::Separation(_, ink) = self {
+            return Some(Color::Spot(ink.clone(), 1.));
+        }
+        match self.family()? {
+            ColorFamily::Gray => Some(Color::Gray(0.)),
+            ColorFamily::RGB => Some(Color::RGB(0., 0., 0.)),
+            ColorFamily::CMYK => Some(Color::CMYK(0., 0., 0., 1.)),
+            ColorFamily::Lab => Some(Color::Lab(0., 0., 0.)),
         }
     }
+    pub fn name(&self) -> Vec<u8> {
+        match self {
+            Self::Device(ColorFamily::Gray) => b"DeviceGray".to_vec(),
+            Self::Device(ColorFamily::CMYK) => b"DeviceCMYK".to_vec(),
+            Self::Device(_) => b"DeviceRGB".to_vec(),
+            Self::Resource(name, _) | Self::Separation(name, _) => name.clone(),
+        }
+    }
+    pub fn into_operator(&self, stroke: bool) -> Operation {
+        Operation::new(
+            if stroke { "CS" } else { "cs" },
+            vec![Object::Name(self.name())],
+        )
+    }
+    // Reads the operands of `sc`/`scn` in this space.
+    pub fn color_from(&self, operation: &Operation) -> Option<Color> {
+        let components = operand_to_f32(operation).ok()?;
+        if let (Self::Separation(_, ink), [tint]) = (self, &components[..]) {
+            return Some(Color::Spot(ink.clone(), *tint));
+        }
+        self.family()?.color(&components)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ColorState {
     pub stroke: Color,
     pub non_stroke: Color,
+    pub stroke_space: ColorSpace,
+    pub non_stroke_space: ColorSpace,
 }
 
 impl ColorState {
@@ -122,19 +258,89 @@ impl ColorState {
         Self {
             stroke: Color::new(),
             non_stroke: Color::new(),
+            stroke_space: ColorSpace::Device(ColorFamily::Gray),
+            non_stroke_space: ColorSpace::Device(ColorFamily::Gray),
         }
     }
     pub fn handle_operation(&mut self, operation: &Operation) {
         match operation.operator.as_ref() {
-            "G" | "RG" | "K" | "CS" => self.stroke.handle_operation(operation),
-            "g" | "rg" | "k" | "cs" => self.non_stroke.handle_operation(operation),
+            "G" | "RG" | "K" => {
+                self.stroke.handle_operation(operation);
+                if let Some(space) = ColorSpace::of(&self.stroke) {
+                    self.stroke_space = space;
+                }
+            }
+            "g" | "rg" | "k" => {
+                self.non_stroke.handle_operation(operation);
+                if let Some(space) = ColorSpace::of(&self.non_stroke) {
+                    self.non_stroke_space = space;
+                }
+            }
+            "CS" | "cs" => {
+                if let Some(space) = operation
+                    .operands
+                    .first()
+                    .and_then(|o| o.as_name().ok())
+                    .and_then(ColorSpace::from_name)
+                {
+                    self.set_space(operation.operator == "CS", space);
+                }
+            }
+            "SC" | "SCN" => {
+                if let Some(color) = self.stroke_space.color_from(operation) {
+                    self.stroke = color;
+                }
+            }
+            "sc" | "scn" => {
+                if let Some(color) = self.non_stroke_space.color_from(operation) {
+                    self.non_stroke = color;
+                }
+            }
             _ => (),
         }
     }
+    pub fn set_space(&mut self, stroke: bool, space: ColorSpace) {
+        let (color, current) = if stroke {
+            (&mut self.stroke, &mut self.stroke_space)
+        } else {
+            (&mut self.non_stroke, &mut self.non_stroke_space)
+        };
+        if let Some(initial) = space.initial_color() {
+            *color = initial;
+        }
+        *current = space;
+    }
+    // Whether the current colour is known; colours in pattern, indexed and
+    // similar spaces are not tracked.
+    pub fn is_tracked(&self, stroke: bool) -> bool {
+        if stroke {
+            self.stroke_space.is_tracked()
+        } else {
+            self.non_stroke_space.is_tracked()
+        }
+    }
     pub fn operator_stroke(&self) -> Operation {
         self.stroke.into_operator(true)
     }
     pub fn operator_non_stroke(&self) -> Operation {
         self.non_stroke.into_operator(false)
     }
+    // Operations that set the current colour again in its current space.
+    pub fn operators(&self, stroke: bool) -> Vec<Operation> {
+        let (color, space) = if stroke {
+            (&self.stroke, &self.stroke_space)
+        } else {
+            (&self.non_stroke, &self.non_stroke_space)
+        };
+        match space {
+            ColorSpace::Device(_) => vec![color.into_operator(stroke)],
+            ColorSpace::Resource(..) | ColorSpace::Separation(..) => vec![
+                space.into_operator(stroke),
+                Operation::new(
+                    if stroke { "SC" } else { "sc" },
+                    color.components().into_iter().map(Object::from).collect(),
+                ),
+            ],
+        }
+    }
 }
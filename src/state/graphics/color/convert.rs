@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+
+use lopdf::{Document, Object};
+use qcms::{DataType, Intent, Profile, Transform};
+
+use super::{Color, ColorSpace, ColorTolerance};
+use crate::Function;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorFamily {
+    Gray,
+    RGB,
+    CMYK,
+    Lab,
+}
+
+impl ColorFamily {
+    // A spot colour has no family of its own; `ColorConverter::family`
+    // gives the one its ink prints in.
+    pub fn of(color: &Color) -> Option<Self> {
+        match color {
+            Color::Gray(_) => Some(Self::Gray),
+            Color::RGB(..) => Some(Self::RGB),
+            Color::CMYK(..) => Some(Self::CMYK),
+            Color::Lab(..) => Some(Self::Lab),
+            Color::Spot(..) => None,
+        }
+    }
+    pub fn components(&self) -> usize {
+        match self {
+            Self::Gray => 1,
+            Self::RGB | Self::Lab => 3,
+            Self::CMYK => 4,
+        }
+    }
+    pub fn color(&self, components: &[f32]) -> Option<Color> {
+        match (self, components) {
+            (Self::Gray, [g]) => Some(Color::Gray(*g)),
+            (Self::RGB, [r, g, b]) => Some(Color::RGB(*r, *g, *b)),
+            (Self::CMYK, [c, m, y, k]) => Some(Color::CMYK(*c, *m, *y, *k)),
+            (Self::Lab, [l, a, b]) => Some(Color::Lab(*l, *a, *b)),
+            _ => None,
+        }
+    }
+}
+
+// A spot ink as a Separation space describes it: the tint transform gives
+// the equivalent colour in the alternate family.
+#[derive(Debug, Clone)]
+pub struct Ink {
+    pub alternate: ColorFamily,
+    pub tint: Function,
+}
+
+impl Ink {
+    pub fn process(&self, tint: f32) -> Option<Color> {
+        self.alternate.color(&self.tint.evaluate(&[tint]))
+    }
+}
+
+// The `[/Separation name alternate tint]` arrays of a document with their ink
+// names, including single colorant DeviceN spaces.
+pub fn separations(doc: &Document) -> Vec<(String, &Vec<Object>)> {
+    fn collect<'a>(object: &'a Object, found: &mut Vec<(String, &'a Vec<Object>)>) {
+        match object {
+            Object::Array(array) => {
+                let ink = match (array.first(), array.get(1)) {
+                    (Some(Object::Name(kind)), Some(Object::Name(ink)))
+                        if kind == b"Separation" =>
+                    {
+                        Some(ink)
+                    }
+                    (Some(Object::Name(kind)), Some(Object::Array(inks)))
+                        if kind == b"DeviceN" && inks.len() == 1 =>
+                    {
+                        match &inks[0] {
+                            Object::Name(ink) => Some(ink),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                match ink {
+                    Some(ink) if array.len() >= 4 => {
+                        found.push((String::from_utf8_lossy(ink).to_string(), array))
+                    }
+                    _ => array.iter().for_each(|o| collect(o, found)),
+                }
+            }
+            Object::Dictionary(dict) => dict.iter().for_each(|(_, o)| collect(o, found)),
+            Object::Stream(stream) => stream.dict.iter().for_each(|(_, o)| collect(o, found)),
+            _ => (),
+        }
+    }
+    let mut found = Vec::new();
+    for object in doc.objects.values() {
+        collect(object, &mut found);
+    }
+    found
+}
+
+// Converts between colour families. Without profiles the device spaces are
+// related by the simple formulas of the PDF specification and RGB is taken
+// to be sRGB; with an ICC profile for a family, colours of that family are
+// converted to sRGB through it. Lab values are relative to D65. Spot
+// colours are converted through their ink's tint transform, or taken as a
+// gray ink when the ink is unknown.
+pub struct ColorConverter {
+    gray: Option<Transform>,
+    rgb: Option<Transform>,
+    cmyk: Option<Transform>,
+    inks: HashMap<String, Ink>,
+}
+
+impl std::fmt::Debug for ColorConverter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ColorConverter")
+            .field("gray", &self.gray.is_some())
+            .field("rgb", &self.rgb.is_some())
+            .field("cmyk", &self.cmyk.is_some())
+            .field("inks", &self.inks.keys())
+            .finish()
+    }
+}
+
+impl Default for ColorConverter {
+    fn default() -> Self {
+        Self::naive()
+    }
+}
+
+impl ColorConverter {
+    pub fn naive() -> Self {
+        Self {
+            gray: None,
+            rgb: None,
+            cmyk: None,
+            inks: HashMap::new(),
+        }
+    }
+    // Uses the profiles of the document's output intents, which describe its
+    // device colours. The profile of an ICC-based space belongs to that space
+    // alone, so it is not applied to colours of the same family.
+    pub fn from_document(doc: &Document) -> Self {
+        let mut converter = Self::naive();
+        let intents = doc
+            .catalog()
+            .and_then(|catalog| catalog.get(b"OutputIntents"))
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_array());
+        if let Ok(intents) = intents {
+            for intent in intents {
+                if let Some(data) = doc
+                    .dereference(intent)
+                    .and_then(|(_, o)| o.as_dict())
+                    .and_then(|dict| dict.get(b"DestOutputProfile"))
+                    .ok()
+                    .and_then(|o| profile_data(doc, o))
+                {
+                    converter.add_profile(&data);
+                }
+            }
+        }
+        converter.add_inks(doc);
+        converter
+    }
+    // Registers the inks of the document's Separation spaces, keeping the
+    // first definition of each.
+    pub fn add_inks(&mut self, doc: &Document) {
+        for (name, array) in separations(doc) {
+            if self.inks.contains_key(&name) {
+                continue;
+            }
+            let alternate = ColorSpace::from_resource(doc, b"", &array[2]).family();
+            let tint = Function::from_object(doc, &array[3]);
+            if let (Some(alternate), Some(tint)) = (alternate, tint) {
+                self.inks.insert(name, Ink { alternate, tint });
+            }
+        }
+    }
+    pub fn ink(&self, name: &str) -> Option<&Ink> {
+        self.inks.get(name)
+    }
+    // The process colour a spot colour prints as, a gray ink when the ink is
+    // unknown; other colours are returned as they are.
+    pub fn process(&self, color: &Color) -> Color {
+        match color {
+            Color::Spot(name, tint) => self
+                .ink(name)
+                .and_then(|ink| ink.process(*tint))
+                .unwrap_or(Color::Gray(1. - tint)),
+            color => color.clone(),
+        }
+    }
+    // The family of a colour, or of the process colour a spot colour prints as.
+    pub fn family(&self, color: &Color) -> ColorFamily {
+        match color {
+            Color::Spot(..) => self.family(&self.process(color)),
+            Color::Gray(_) => ColorFamily::Gray,
+            Color::RGB(..) => ColorFamily::RGB,
+            Color::CMYK(..) => ColorFamily::CMYK,
+            Color::Lab(..) => ColorFamily::Lab,
+        }
+    }
+    // Sets the profile for the family named in its header, replacing any
+    // profile already set. Returns false if the profile cannot be used.
+    pub fn add_profile(&mut self, data: &[u8]) -> bool {
+        let family = profile_family(data);
+        let ty = match family {
+            Some(ColorFamily::Gray) => DataType::Gray8,
+            Some(ColorFamily::RGB) => DataType::RGB8,
+            Some(ColorFamily::CMYK) => DataType::CMYK,
+            _ => return false,
+        };
+        let transform = Profile::new_from_slice(data, false).and_then(|profile| {
+            let mut srgb = Profile::new_sRGB();
+            srgb.precache_output_transform();
+            Transform::new_to(
+                &profile,
+                &srgb,
+                ty,
+                DataType::RGB8,
+                Intent::RelativeColorimetric,
+            )
+        });
+        match (family, transform) {
+            (Some(ColorFamily::Gray), Some(t)) => self.gray = Some(t),
+            (Some(ColorFamily::RGB), Some(t)) => self.rgb = Some(t),
+            (Some(ColorFamily::CMYK), Some(t)) => self.cmyk = Some(t),
+            _ => return false,
+        }
+        true
+    }
+    fn transform(&self, family: Option<ColorFamily>) -> Option<&Transform> {
+        match family? {
+            ColorFamily::Gray => self.gray.as_ref(),
+            ColorFamily::RGB => self.rgb.as_ref(),
+            ColorFamily::CMYK => self.cmyk.as_ref(),
+            ColorFamily::Lab => None,
+        }
+    }
+    // sRGB components in 0..1
+    pub fn to_rgb(&self, color: &Color) -> (f32, f32, f32) {
+        if let Color::Spot(..) = color {
+            return self.to_rgb(&self.process(color));
+        }
+        if let Some(transform) = self.transform(ColorFamily::of(color)) {
+            let src: Vec<u8> = color
+                .components()
+                .iter()
+                .map(|c| (c.clamp(0., 1.) * 255.).round() as u8)
+                .collect();
+            let mut dst = [0u8; 3];
+            transform.convert(&src, &mut dst);
+            return (dst.map(|c| f32::from(c) / 255.)).into();
+        }
+        self.naive_rgb(color)
+    }
+    // The components related by the formulas alone, unquantized.
+    fn naive_rgb(&self, color: &Color) -> (f32, f32, f32) {
+        match color {
+            Color::Gray(g) => (*g, *g, *g),
+            Color::RGB(r, g, b) => (*r, *g, *b),
+            Color::CMYK(c, m, y, k) => (
+                (1. - c) * (1. - k),
+                (1. - m) * (1. - k),
+                (1. - y) * (1. - k),
+            ),
+            Color::Lab(l, a, b) => lab_to_srgb((*l, *a, *b)),
+            Color::Spot(..) => self.naive_rgb(&self.process(color)),
+        }
+    }
+    pub fn to_lab(&self, color: &Color) -> (f32, f32, f32) {
+        match color {
+            Color::Lab(l, a, b) => (*l, *a, *b),
+            color => srgb_to_lab(self.to_rgb(color)),
+        }
+    }
+    pub fn convert(&self, color: &Color, family: ColorFamily) -> Color {
+        if ColorFamily::of(color) == Some(family) {
+            return color.clone();
+        }
+        match family {
+            ColorFamily::Gray => {
+                let (r, g, b) = self.to_rgb(color);
+                Color::Gray(0.3 * r + 0.59 * g + 0.11 * b)
+            }
+            ColorFamily::RGB => {
+                let (r, g, b) = self.to_rgb(color);
+                Color::RGB(r, g, b)
+            }
+            ColorFamily::CMYK => {
+                let (r, g, b) = self.to_rgb(color);
+                let k = 1. - r.max(g).max(b);
+                if k >= 1. {
+                    Color::CMYK(0., 0., 0., 1.)
+                } else {
+                    Color::CMYK(
+                        (1. - r - k) / (1. - k),
+                        (1. - g - k) / (1. - k),
+                        (1. - b - k) / (1. - k),
+                        k,
+                    )
+                }
+            }
+            ColorFamily::Lab => {
+                let (l, a, b) = self.to_lab(color);
+                Color::Lab(l, a, b)
+            }
+        }
+    }
+    pub fn equals(&self, lhs: &Color, rhs: &Color) -> bool {
+        if let (Color::Spot(l, lt), Color::Spot(r, rt)) = (lhs, rhs) {
+            return l == r && (lt - rt).abs() <= f32::EPSILON;
+        }
+        // a profile works on 8 bits, which would merge nearby colours
+        let l = self.naive_rgb(lhs);
+        let r = self.naive_rgb(rhs);
+        (l.0 - r.0).abs() <= f32::EPSILON
+            && (l.1 - r.1).abs() <= f32::EPSILON
+            && (l.2 - r.2).abs() <= f32::EPSILON
+    }
+    pub fn matches(&self, lhs: &Color, rhs: &Color, tolerance: &ColorTolerance) -> bool {
+        // spot colours are recognised by their ink, not by how they look
+        match (lhs, rhs) {
+            (Color::Spot(l, _), Color::Spot(r, _)) if l != r => return false,
+            (Color::Spot(..), Color::Spot(..)) => (),
+            (Color::Spot(..), _) | (_, Color::Spot(..)) => return false,
+            _ => (),
+        }
+        match tolerance {
+            ColorTolerance::Exact => self.equals(lhs, rhs),
+            ColorTolerance::Channel(t) => {
+                let l = self.to_rgb(lhs);
+                let r = self.to_rgb(rhs);
+                (l.0 - r.0).abs() <= *t && (l.1 - r.1).abs() <= *t && (l.2 - r.2).abs() <= *t
+            }
+            ColorTolerance::DeltaE(t) => self.delta_e(lhs, rhs) <= *t,
+        }
+    }
+    // CIEDE2000 colour difference.
+    pub fn delta_e(&self, lhs: &Color, rhs: &Color) -> f32 {
+        delta_e_2000(self.to_lab(lhs), self.to_lab(rhs))
+    }
+}
+
+fn profile_data(doc: &Document, object: &Object) -> Option<Vec<u8>> {
+    let stream = doc.dereference(object).ok()?.1.as_stream().ok()?;
+    stream
+        .decompressed_content()
+        .ok()
+        .or_else(|| Some(stream.content.clone()))
+}
+
+fn profile_family(data: &[u8]) -> Option<ColorFamily> {
+    match data.get(16..20)? {
+        b"GRAY" => Some(ColorFamily::Gray),
+        b"RGB " => Some(ColorFamily::RGB),
+        b"CMYK" => Some(ColorFamily::CMYK),
+        _ => None,
+    }
+}
+
+const WHITE_D65: (f64, f64, f64) = (0.95047, 1., 1.08883);
+
+pub fn srgb_to_lab(rgb: (f32, f32, f32)) -> (f32, f32, f32) {
+    let linear = |c: f32| {
+        let c = f64::from(c.clamp(0., 1.));
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(rgb.0), linear(rgb.1), linear(rgb.2));
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / WHITE_D65.0;
+    let y = (0.2126729 * r + 0.7151522 * g + 0.0721750 * b) / WHITE_D65.1;
+    let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / WHITE_D65.2;
+    let f = |t: f64| {
+        if t > 216. / 24389. {
+            t.cbrt()
+        } else {
+            (24389. / 27. * t + 16.) / 116.
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    (
+        (116. * fy - 16.) as f32,
+        (500. * (fx - fy)) as f32,
+        (200. * (fy - fz)) as f32,
+    )
+}
+
+pub fn lab_to_srgb(lab: (f32, f32, f32)) -> (f32, f32, f32) {
+    let (l, a, b) = (f64::from(lab.0), f64::from(lab.1), f64::from(lab.2));
+    let fy = (l + 16.) / 116.;
+    let (fx, fz) = (fy + a / 500., fy - b / 200.);
+    let f_inv = |t: f64| {
+        if t.powi(3) > 216. / 24389. {
+            t.powi(3)
+        } else {
+            (116. * t - 16.) * 27. / 24389.
+        }
+    };
+    let x = f_inv(fx) * WHITE_D65.0;
+    let y = f_inv(fy) * WHITE_D65.1;
+    let z = f_inv(fz) * WHITE_D65.2;
+    let gamma = |c: f64| {
+        let c = if c <= 0.0031308 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1. / 2.4) - 0.055
+        };
+        c.clamp(0., 1.) as f32
+    };
+    (
+        gamma(3.2404542 * x - 1.5371385 * y - 0.4985314 * z),
+        gamma(-0.9692660 * x + 1.8760108 * y + 0.0415560 * z),
+        gamma(0.0556434 * x - 0.2040259 * y + 1.0572252 * z),
+    )
+}
+
+pub fn delta_e_2000(lab1: (f32, f32, f32), lab2: (f32, f32, f32)) -> f32 {
+    let (l1, a1, b1) = lab1;
+    let (l2, a2, b2) = lab2;
+    let (l1, a1, b1) = (f64::from(l1), f64::from(a1), f64::from(b1));
+    let (l2, a2, b2) = (f64::from(l2), f64::from(a2), f64::from(b2));
+
+    let c_bar = (a1.hypot(b1) + a2.hypot(b2)) / 2.;
+    let g = 0.5 * (1. - (c_bar.powi(7) / (c_bar.powi(7) + 25f64.powi(7))).sqrt());
+    let (a1, a2) = ((1. + g) * a1, (1. + g) * a2);
+    let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+    let hue = |b: f64, a: f64| {
+        if a == 0. && b == 0. {
+            0.
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.)
+        }
+    };
+    let (h1, h2) = (hue(b1, a1), hue(b2, a2));
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = if c1 * c2 == 0. {
+        0.
+    } else if (h2 - h1).abs() <= 180. {
+        h2 - h1
+    } else if h2 <= h1 {
+        h2 - h1 + 360.
+    } else {
+        h2 - h1 - 360.
+    };
+    let delta_h = 2. * (c1 * c2).sqrt() * (delta_h / 2.).to_radians().sin();
+
+    let l_bar = (l1 + l2) / 2.;
+    let c_bar = (c1 + c2) / 2.;
+    let h_bar = if c1 * c2 == 0. {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180. {
+        (h1 + h2) / 2.
+    } else if h1 + h2 < 360. {
+        (h1 + h2 + 360.) / 2.
+    } else {
+        (h1 + h2 - 360.) / 2.
+    };
+    let t = 1. - 0.17 * (h_bar - 30.).to_radians().cos()
+        + 0.24 * (2. * h_bar).to_radians().cos()
+        + 0.32 * (3. * h_bar + 6.).to_radians().cos()
+        - 0.20 * (4. * h_bar - 63.).to_radians().cos();
+    let delta_theta = 30. * (-((h_bar - 275.) / 25.).powi(2)).exp();
+    let r_c = 2. * (c_bar.powi(7) / (c_bar.powi(7) + 25f64.powi(7))).sqrt();
+    let s_l = 1. + 0.015 * (l_bar - 50.).powi(2) / (20. + (l_bar - 50.).powi(2)).sqrt();
+    let s_c = 1. + 0.045 * c_bar;
+    let s_h = 1. + 0.015 * c_bar * t;
+    let r_t = -(2. * delta_theta).to_radians().sin() * r_c;
+
+    let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+    (l * l + c * c + h * h + r_t * c * h).sqrt() as f32
+}
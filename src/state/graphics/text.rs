@@ -28,6 +28,18 @@ impl RenderingMode {
             _ => RenderingMode::Fill,
         }
     }
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            RenderingMode::Fill => 0,
+            RenderingMode::Stroke => 1,
+            RenderingMode::FillAndStroke => 2,
+            RenderingMode::Invisible => 3,
+            RenderingMode::FillAndAddClippingPath => 4,
+            RenderingMode::StrokeAndAddClippingPath => 5,
+            RenderingMode::FillStrokeAddClippingPath => 6,
+            RenderingMode::AddClippingPath => 7,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -36,13 +48,15 @@ pub struct Text {
     pub word_spacing: f32,
     pub horizontal_scaling: f32,
     pub leading: f32,
-    // font: Option<Vec<u8>>,
+    // resource name of the font set with `Tf`
+    pub font: Option<Vec<u8>>,
     pub font_size: Option<f32>,
     pub rendering_mode: RenderingMode,
     pub rise: f32,
     pub knockout: bool,
 
-    // matrix: na::Matrix3<f32>,
+    // text matrix where the string being shown starts
+    pub matrix: kurbo::Affine,
     pub line_matrix: kurbo::Affine,
 }
 
@@ -53,12 +67,12 @@ impl Text {
             word_spacing: 0.,
             horizontal_scaling: 100.,
             leading: 0.,
-            // font: None,
+            font: None,
             font_size: None,
             rendering_mode: RenderingMode::Fill,
             rise: 0.,
             knockout: true,
-            // matrix: na::Matrix3::identity(),
+            matrix: kurbo::Affine::IDENTITY,
             line_matrix: kurbo::Affine::IDENTITY,
         }
     }
@@ -85,14 +99,14 @@ impl Text {
                 }
             }
             "Tf" => {
-                let font = operation.operands.get(0).and_then(|o| o.as_name_str().ok());
+                let font = operation.operands.get(0).and_then(|o| o.as_name().ok());
                 let font_size = operation
                     .operands
                     .get(1)
                     .and_then(|o| o.as_float().or(o.as_i64().map(|v| v as f32)).ok());
                 match (font, font_size) {
-                    (Some(_f), Some(fs)) => {
-                        // self.font = Some(f);
+                    (Some(f), Some(fs)) => {
+                        self.font = Some(f.to_vec());
                         self.font_size = Some(fs);
                     }
                     (_, _) => (),
@@ -110,34 +124,34 @@ impl Text {
             }
             "BT" => {
                 self.line_matrix = kurbo::Affine::IDENTITY;
-                // self.matrix = na::Matrix3::identity();
+                self.matrix = kurbo::Affine::IDENTITY;
             }
             "ET" => {
                 self.line_matrix = kurbo::Affine::IDENTITY;
-                // self.matrix = na::Matrix3::identity();
+                self.matrix = kurbo::Affine::IDENTITY;
             }
             "Td" => {
                 if let Ok([x, y]) = operand_to_f32(operation).as_deref() {
                     self.line_matrix *= kurbo::Affine::translate(((*x).into(), (*y).into()));
-                    // self.matrix = self.line_matrix;
+                    self.matrix = self.line_matrix;
                 }
             }
             "TD" => {
                 if let Ok([x, y]) = operand_to_f32(operation).as_deref() {
                     self.leading = -y;
                     self.line_matrix *= kurbo::Affine::translate(((*x).into(), (*y).into()));
-                    // self.matrix = self.line_matrix;
+                    self.matrix = self.line_matrix;
                 }
             }
             "Tm" => {
                 if let Ok([a, b, c, d, e, f]) = operand_to_f32(operation).as_deref() {
                     self.line_matrix = kurbo::Affine::new([*a, *b, *c, *d, *e, *f].map(f32::into));
-                    // self.matrix = self.line_matrix;
+                    self.matrix = self.line_matrix;
                 }
             }
             "T*" | "'" => {
                 self.line_matrix *= kurbo::Affine::translate((0., (-self.leading).into()));
-                // self.matrix = self.line_matrix;
+                self.matrix = self.line_matrix;
             }
             "\"" => {
                 if let [aw, ac] = operation.operands[0..2]
@@ -148,6 +162,8 @@ impl Text {
                     self.word_spacing = aw;
                     self.charactor_spacing = ac;
                 }
+                self.line_matrix *= kurbo::Affine::translate((0., (-self.leading).into()));
+                self.matrix = self.line_matrix;
             }
             _ => (),
         }
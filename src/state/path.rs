@@ -1,5 +1,5 @@
 use crate::*;
-use kurbo::{BezPath, PathEl, Point, Rect, Shape, Size};
+use kurbo::{BezPath, Line, PathEl, PathSeg, Point, Rect, Shape, Size};
 use lopdf::content::Operation;
 
 #[derive(Debug, Clone)]
@@ -102,6 +102,23 @@ impl Path {
             .filter(|path| !path.elements().is_empty() && path.bounding_box().area() > 0.)
             .collect::<Vec<_>>()
     }
+    pub fn bounding_box(&self) -> Option<Rect> {
+        self.paths
+            .iter()
+            .filter(|path| !path.elements().is_empty())
+            .map(|path| path.bounding_box())
+            .reduce(|a, b| a.union(b))
+    }
+    pub fn lines(&self) -> Vec<Line> {
+        self.paths
+            .iter()
+            .flat_map(|path| path.segments())
+            .filter_map(|seg| match seg {
+                PathSeg::Line(line) => Some(line),
+                _ => None,
+            })
+            .collect()
+    }
     pub fn is_rect(&self, between: (f32, f32)) -> bool {
         let filtered = self.subpaths();
         if filtered.len() != 1 {
@@ -0,0 +1,322 @@
+use kurbo::{Line, Point, Rect, Shape};
+
+use crate::{PdfModifier, ShownGlyph};
+
+#[derive(Debug, Clone)]
+pub struct Ruling {
+    pub line: Line,
+}
+
+impl Ruling {
+    fn horizontal(y: f64, x0: f64, x1: f64) -> Self {
+        Self {
+            line: Line::new((x0.min(x1), y), (x0.max(x1), y)),
+        }
+    }
+    fn vertical(x: f64, y0: f64, y1: f64) -> Self {
+        Self {
+            line: Line::new((x, y0.min(y1)), (x, y0.max(y1))),
+        }
+    }
+    pub fn is_horizontal(&self) -> bool {
+        self.line.p0.y == self.line.p1.y
+    }
+    fn crosses(&self, other: &Self, tolerance: f64) -> bool {
+        let (h, v) = match (self.is_horizontal(), other.is_horizontal()) {
+            (true, false) => (self, other),
+            (false, true) => (other, self),
+            _ => return false,
+        };
+        let x = v.line.p0.x;
+        let y = h.line.p0.y;
+        h.line.p0.x - tolerance <= x
+            && x <= h.line.p1.x + tolerance
+            && v.line.p0.y - tolerance <= y
+            && y <= v.line.p1.y + tolerance
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextRun {
+    pub id: usize,
+    pub position: Point,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub bbox: Rect,
+    // x coordinates of column boundaries, left to right
+    pub columns: Vec<f64>,
+    // y coordinates of row boundaries, top to bottom
+    pub rows: Vec<f64>,
+    pub cells: Vec<Vec<String>>,
+    // ids of the text operations placed in each cell
+    pub cell_operations: Vec<Vec<Vec<usize>>>,
+}
+
+impl Table {
+    fn new(columns: Vec<f64>, rows: Vec<f64>) -> Self {
+        let n_rows = rows.len() - 1;
+        let n_columns = columns.len() - 1;
+        Self {
+            bbox: Rect::new(columns[0], rows[n_rows], columns[n_columns], rows[0]),
+            columns,
+            rows,
+            cells: vec![vec![String::new(); n_columns]; n_rows],
+            cell_operations: vec![vec![Vec::new(); n_columns]; n_rows],
+        }
+    }
+    pub fn cell_at(&self, point: Point) -> Option<(usize, usize)> {
+        let column = self
+            .columns
+            .windows(2)
+            .position(|w| w[0] <= point.x && point.x < w[1])?;
+        let row = self
+            .rows
+            .windows(2)
+            .position(|w| w[0] >= point.y && point.y > w[1])?;
+        Some((row, column))
+    }
+    fn insert(&mut self, run: &TextRun) {
+        if let Some((row, column)) = self.cell_at(run.position) {
+            let cell = &mut self.cells[row][column];
+            if !cell.is_empty() && !run.text.starts_with(' ') && !cell.ends_with(' ') {
+                cell.push(' ');
+            }
+            cell.push_str(&run.text);
+            self.cell_operations[row][column].push(run.id);
+        }
+    }
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for row in &self.cells {
+            let fields: Vec<String> = row
+                .iter()
+                .map(|cell| {
+                    let cell = cell.trim();
+                    if cell.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", cell.replace('"', "\"\""))
+                    } else {
+                        cell.to_string()
+                    }
+                })
+                .collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "bbox": [self.bbox.x0, self.bbox.y0, self.bbox.x1, self.bbox.y1],
+            "columns": self.columns,
+            "rows": self.rows,
+            "cells": self
+                .cells
+                .iter()
+                .map(|row| row.iter().map(|cell| cell.trim()).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            "operations": self.cell_operations,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableDetector {
+    // filled rectangles thinner than this are treated as ruling lines
+    pub max_thickness: f64,
+    pub min_length: f64,
+    pub tolerance: f64,
+}
+
+impl Default for TableDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TableDetector {
+    pub fn new() -> Self {
+        Self {
+            max_thickness: 3.,
+            min_length: 5.,
+            tolerance: 2.,
+        }
+    }
+    pub fn detect(&self, modifier: &mut PdfModifier, page_id: (u32, u16)) -> Vec<Table> {
+        let mut rulings: Vec<Ruling> = Vec::new();
+        let mut shown: Vec<(usize, Vec<ShownGlyph>)> = Vec::new();
+        modifier.for_each(page_id, &mut |operation, state| {
+            let ctm = state.graphics.ctm;
+            match operation.operator.as_ref() {
+                "f" | "F" | "f*" => {
+                    rulings.extend(state.path.subpaths().iter().filter_map(|path| {
+                        self.ruling_from_rect(ctm.transform_rect_bbox(path.bounding_box()))
+                    }));
+                }
+                "S" | "s" | "B" | "B*" | "b" | "b*" => {
+                    rulings.extend(
+                        state
+                            .path
+                            .lines()
+                            .into_iter()
+                            .filter_map(|line| self.ruling_from_line(ctm * line)),
+                    );
+                }
+                "Tj" | "TJ" | "'" | "\"" => shown.push((state.id, state.shown_glyphs(&operation))),
+                _ => (),
+            }
+        });
+        let mut tables: Vec<Table> = self
+            .group(&rulings)
+            .iter()
+            .filter_map(|group| self.build(group))
+            .collect();
+        // an operation may show text in several cells, so its glyphs are
+        // placed one by one, by the middle of their baseline
+        for (id, glyphs) in &shown {
+            let mut runs: Vec<(usize, (usize, usize), TextRun, &ShownGlyph)> = Vec::new();
+            for glyph in glyphs {
+                let position = Point::new(glyph.bbox.center().x, glyph.origin.y);
+                let Some((table, cell)) = tables
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, t)| Some((i, t.cell_at(position)?)))
+                else {
+                    continue;
+                };
+                match runs.last_mut() {
+                    Some((t, c, run, previous)) if *t == table && *c == cell => {
+                        run.text.push_str(glyph.separator(previous));
+                        run.text.push_str(&glyph.text);
+                        *previous = glyph;
+                    }
+                    _ => runs.push((
+                        table,
+                        cell,
+                        TextRun {
+                            id: *id,
+                            position,
+                            text: glyph.text.clone(),
+                        },
+                        glyph,
+                    )),
+                }
+            }
+            for (table, _, run, _) in runs {
+                tables[table].insert(&run);
+            }
+        }
+        tables
+    }
+    fn ruling_from_rect(&self, rect: Rect) -> Option<Ruling> {
+        if rect.height() <= self.max_thickness && rect.width() >= self.min_length {
+            Some(Ruling::horizontal(rect.center().y, rect.x0, rect.x1))
+        } else if rect.width() <= self.max_thickness && rect.height() >= self.min_length {
+            Some(Ruling::vertical(rect.center().x, rect.y0, rect.y1))
+        } else {
+            None
+        }
+    }
+    fn ruling_from_line(&self, line: Line) -> Option<Ruling> {
+        let d = line.p1 - line.p0;
+        if d.y.abs() <= self.tolerance && d.x.abs() >= self.min_length {
+            Some(Ruling::horizontal(
+                line.p0.midpoint(line.p1).y,
+                line.p0.x,
+                line.p1.x,
+            ))
+        } else if d.x.abs() <= self.tolerance && d.y.abs() >= self.min_length {
+            Some(Ruling::vertical(
+                line.p0.midpoint(line.p1).x,
+                line.p0.y,
+                line.p1.y,
+            ))
+        } else {
+            None
+        }
+    }
+    // Splits the rulings into connected groups of crossing lines.
+    fn group<'a>(&self, rulings: &'a [Ruling]) -> Vec<Vec<&'a Ruling>> {
+        let mut group_of: Vec<usize> = (0..rulings.len()).collect();
+        fn root(group_of: &mut [usize], i: usize) -> usize {
+            let mut i = i;
+            while group_of[i] != i {
+                group_of[i] = group_of[group_of[i]];
+                i = group_of[i];
+            }
+            i
+        }
+        for i in 0..rulings.len() {
+            for j in (i + 1)..rulings.len() {
+                if rulings[i].crosses(&rulings[j], self.tolerance) {
+                    let (a, b) = (root(&mut group_of, i), root(&mut group_of, j));
+                    group_of[a] = b;
+                }
+            }
+        }
+        let mut groups: Vec<Vec<&Ruling>> = vec![Vec::new(); rulings.len()];
+        for (i, ruling) in rulings.iter().enumerate() {
+            groups[root(&mut group_of, i)].push(ruling);
+        }
+        groups.into_iter().filter(|g| !g.is_empty()).collect()
+    }
+    fn build(&self, group: &[&Ruling]) -> Option<Table> {
+        let mut columns = self.cluster(
+            group
+                .iter()
+                .filter(|r| !r.is_horizontal())
+                .map(|r| r.line.p0.x),
+        );
+        let mut rows = self.cluster(
+            group
+                .iter()
+                .filter(|r| r.is_horizontal())
+                .map(|r| r.line.p0.y),
+        );
+        if columns.len() < 2 || rows.len() < 2 {
+            return None;
+        }
+        columns.sort_by(f64::total_cmp);
+        rows.sort_by(|a, b| b.total_cmp(a));
+        Some(Table::new(columns, rows))
+    }
+    fn cluster<I: Iterator<Item = f64>>(&self, values: I) -> Vec<f64> {
+        let mut values: Vec<f64> = values.collect();
+        values.sort_by(f64::total_cmp);
+        let mut clusters: Vec<Vec<f64>> = Vec::new();
+        for v in values {
+            match clusters.last_mut() {
+                Some(c) if v - c[c.len() - 1] <= self.tolerance => c.push(v),
+                _ => clusters.push(vec![v]),
+            }
+        }
+        clusters
+            .iter()
+            .map(|c| c.iter().sum::<f64>() / c.len() as f64)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::modifier;
+
+    #[test]
+    fn one_operation_across_cells() {
+        // two cells side by side, 100pt wide, and a TJ whose kerning moves
+        // its second string into the second cell
+        let (mut modifier, page_id) = modifier(concat!(
+            "1 w 100 700 m 300 700 l 100 720 m 300 720 l ",
+            "100 700 m 100 720 l 200 700 m 200 720 l 300 700 m 300 720 l S ",
+            "BT /F1 10 Tf 105 705 Td [(Left) -8000 (Right)] TJ ET ",
+            "BT /F1 10 Tf 105 705 Td (One) Tj ( more) Tj ET"
+        ));
+        let tables = TableDetector::new().detect(&mut modifier, page_id);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].cells, [["Left One more", "Right"]]);
+        assert_eq!(tables[0].cell_operations, [[vec![16, 21, 22], vec![16]]]);
+    }
+}
@@ -0,0 +1,45 @@
+use lopdf::{dictionary, Dictionary, Document, Object, Stream};
+
+use crate::PdfModifier;
+
+// A letter-sized page drawing `content`, with Helvetica as `F1` and the
+// extra `resources`.
+pub fn page(content: &str, resources: Dictionary) -> Document {
+    let mut doc = Document::with_version("1.5");
+    let font = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let mut page_resources = dictionary! { "Font" => dictionary! { "F1" => font } };
+    for (key, value) in resources.iter() {
+        page_resources.set(key.clone(), value.clone());
+    }
+    let content = doc.add_object(Stream::new(Dictionary::new(), content.as_bytes().to_vec()));
+    let pages = doc.new_object_id();
+    let page = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        "Contents" => content,
+        "Resources" => page_resources,
+    });
+    doc.objects.insert(
+        pages,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page.into()],
+            "Count" => 1,
+        }),
+    );
+    let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+    doc.trailer.set("Root", catalog);
+    doc
+}
+
+pub fn modifier(content: &str) -> (PdfModifier, (u32, u16)) {
+    let modifier = PdfModifier::from_document(page(content, Dictionary::new()));
+    let page_id = modifier.pages()[0];
+    (modifier, page_id)
+}
@@ -1,9 +1,12 @@
 use std::path::PathBuf;
 
-use lopdf::content::{Content, Operation};
-use lopdf::Document;
+use kurbo::{Affine, Rect};
+use lopdf::content::Operation;
+use lopdf::{Dictionary, Document, Object};
 
-use crate::State;
+use crate::{
+    decode_operations, encode_operations, object_to_f32, page_resource, stream_content, State,
+};
 
 pub struct PdfModifier {
     doc: Document,
@@ -15,6 +18,9 @@ impl PdfModifier {
             doc: Document::load(input_path)?,
         })
     }
+    pub fn from_document(doc: Document) -> Self {
+        Self { doc }
+    }
     pub fn save(&mut self, output_path: &PathBuf) {
         self.doc.compress();
         self.doc.save(&output_path).unwrap();
@@ -24,21 +30,188 @@ impl PdfModifier {
         self.doc.page_iter().collect()
     }
 
+    pub fn document(&self) -> &Document {
+        &self.doc
+    }
+
+    pub fn document_mut(&mut self) -> &mut Document {
+        &mut self.doc
+    }
+
+    // Looks up a page boundary such as `MediaBox`, following inheritance
+    // through the page tree.
+    pub fn page_box(&self, page_id: (u32, u16), key: &[u8]) -> Option<Rect> {
+        let mut dict = self.doc.get_dictionary(page_id).ok()?;
+        loop {
+            if let Ok(array) = dict
+                .get(key)
+                .and_then(|o| self.doc.dereference(o))
+                .and_then(|(_, o)| o.as_array())
+            {
+                if let [x0, y0, x1, y1] = array
+                    .iter()
+                    .filter_map(|o| o.as_float().or(o.as_i64().map(|v| v as f32)).ok())
+                    .collect::<Vec<f32>>()[..]
+                {
+                    return Some(Rect::new(x0.into(), y0.into(), x1.into(), y1.into()));
+                }
+            }
+            let parent = dict.get(b"Parent").and_then(|o| o.as_reference()).ok()?;
+            dict = self.doc.get_dictionary(parent).ok()?;
+        }
+    }
+
+    // The visible region of the page: the crop box clipped to the media box.
+    pub fn visible_box(&self, page_id: (u32, u16)) -> Option<Rect> {
+        let media_box = self.page_box(page_id, b"MediaBox")?;
+        Some(match self.page_box(page_id, b"CropBox") {
+            Some(crop_box) => crop_box.intersect(media_box),
+            None => media_box,
+        })
+    }
+
+    pub fn set_crop_box(&mut self, page_id: (u32, u16), rect: Rect) {
+        let array = [rect.x0, rect.y0, rect.x1, rect.y1]
+            .map(|v| Object::from(v as f32))
+            .to_vec();
+        self.set_page_entry(page_id, "CropBox", Object::Array(array));
+    }
+
+    pub fn add_annotation(&mut self, page_id: (u32, u16), annotation: Dictionary) {
+        let annotation_id = self.doc.add_object(annotation);
+        let annots = self
+            .doc
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Annots"))
+            .ok()
+            .cloned();
+        match annots {
+            Some(Object::Reference(array_id)) => {
+                if let Ok(Object::Array(array)) = self.doc.get_object_mut(array_id) {
+                    array.push(Object::Reference(annotation_id));
+                }
+            }
+            Some(Object::Array(mut array)) => {
+                array.push(Object::Reference(annotation_id));
+                self.set_page_entry(page_id, "Annots", Object::Array(array));
+            }
+            _ => {
+                self.set_page_entry(
+                    page_id,
+                    "Annots",
+                    Object::Array(vec![Object::Reference(annotation_id)]),
+                );
+            }
+        }
+    }
+
+    // Adds operations beneath (underlay) or on top of the page content. The
+    // existing content is wrapped in `q`/`Q` so its graphics state does not
+    // leak into what is drawn over it.
+    pub fn insert_content(
+        &mut self,
+        page_id: (u32, u16),
+        operations: &[Operation],
+        underlay: bool,
+    ) {
+        let existing = self.doc.get_page_content(page_id).unwrap();
+        let inserted = encode_operations(operations).unwrap();
+        let mut content = Vec::new();
+        if underlay {
+            content.extend_from_slice(b"q\n");
+            content.extend(inserted);
+            content.extend_from_slice(b"\nQ\n");
+            content.extend(existing);
+        } else {
+            content.extend_from_slice(b"q\n");
+            content.extend(existing);
+            content.extend_from_slice(b"\nQ\nq\n");
+            content.extend(inserted);
+            content.extend_from_slice(b"\nQ\n");
+        }
+        self.doc.change_page_content(page_id, content).unwrap();
+    }
+
+    // Adds an entry to a resource category of the page such as `ColorSpace`.
+    // Inherited resources are copied to the page first so other pages are
+    // not affected; a referenced resource dictionary is shared and extended.
+    pub fn add_resource(
+        &mut self,
+        page_id: (u32, u16),
+        category: &str,
+        name: &[u8],
+        value: Object,
+    ) {
+        let resources = self
+            .doc
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Resources"))
+            .ok()
+            .cloned();
+        let resources_id = match resources {
+            Some(Object::Reference(id)) => Some(id),
+            Some(_) => None,
+            None => {
+                let (_, inherited) = self.doc.get_page_resources(page_id);
+                let copy = inherited
+                    .first()
+                    .and_then(|id| self.doc.get_dictionary(*id).ok())
+                    .cloned()
+                    .unwrap_or_default();
+                self.set_page_entry(page_id, "Resources", Object::Dictionary(copy));
+                None
+            }
+        };
+        let resources = match resources_id {
+            Some(id) => self.doc.get_object_mut(id),
+            None => self
+                .doc
+                .get_object_mut(page_id)
+                .and_then(|page| page.as_dict_mut())
+                .and_then(|page| page.get_mut(b"Resources")),
+        }
+        .and_then(|o| o.as_dict_mut())
+        .unwrap();
+        let entries = match resources.get(category.as_bytes()) {
+            Ok(Object::Reference(id)) => Some(*id),
+            Ok(Object::Dictionary(_)) => None,
+            _ => {
+                resources.set(category, Dictionary::new());
+                None
+            }
+        };
+        let entries = match entries {
+            Some(id) => self.doc.get_object_mut(id),
+            None => resources.get_mut(category.as_bytes()),
+        }
+        .and_then(|o| o.as_dict_mut())
+        .unwrap();
+        entries.set(name.to_vec(), value);
+    }
+
+    fn set_page_entry(&mut self, page_id: (u32, u16), key: &str, value: Object) {
+        self.doc
+            .get_object_mut(page_id)
+            .and_then(|o| o.as_dict_mut())
+            .unwrap()
+            .set(key, value);
+    }
+
     pub fn apply<F>(&mut self, page_id: (u32, u16), converter: &mut F)
     where
         F: FnMut(Operation, &State) -> Vec<Operation>,
     {
         let content_data = self.doc.get_page_content(page_id).unwrap();
-        let content = Content::decode(&content_data).unwrap();
+        let content = decode_operations(&content_data).unwrap();
 
         let mut state = State::new(&self.doc, page_id);
 
         let mut operations: Vec<Operation> = Vec::new();
-        for operation in content.operations {
+        for operation in content {
             state.handle_operation(&operation);
             operations.extend(converter(operation, &state));
         }
-        let modified_content = Content { operations }.encode().unwrap();
+        let modified_content = encode_operations(&operations).unwrap();
         self.doc
             .change_page_content(page_id, modified_content)
             .unwrap();
@@ -49,13 +222,89 @@ impl PdfModifier {
         F: FnMut(Operation, &State),
     {
         let content_data = self.doc.get_page_content(page_id).unwrap();
-        let content = Content::decode(&content_data).unwrap();
+        let content = decode_operations(&content_data).unwrap();
 
         let mut state = State::new(&self.doc, page_id);
 
-        for operation in content.operations {
+        for operation in content {
             state.handle_operation(&operation);
             converter(operation, &state);
         }
     }
+
+    // Like `for_each`, also going through the content of the forms the page
+    // draws, right after their `Do`, in the state they are drawn in.
+    pub fn for_each_nested<F>(&mut self, page_id: (u32, u16), converter: &mut F)
+    where
+        F: FnMut(Operation, &State),
+    {
+        let content_data = self.doc.get_page_content(page_id).unwrap();
+        let content = decode_operations(&content_data).unwrap();
+        let xobjects = page_resource(&self.doc, page_id, b"XObject");
+        let state = State::new(&self.doc, page_id);
+        for_each_in(&self.doc, page_id, content, state, xobjects, 0, converter);
+    }
+}
+
+fn for_each_in<F>(
+    doc: &Document,
+    page_id: (u32, u16),
+    content: Vec<Operation>,
+    mut state: State,
+    xobjects: Option<&Dictionary>,
+    depth: usize,
+    converter: &mut F,
+) where
+    F: FnMut(Operation, &State),
+{
+    for operation in content {
+        state.handle_operation(&operation);
+        let form = match (operation.operator.as_str(), operation.operands.first()) {
+            ("Do", Some(Object::Name(name))) if depth < 8 => xobjects
+                .and_then(|x| x.get(name).ok())
+                .and_then(|o| doc.dereference(o).ok())
+                .and_then(|(_, o)| o.as_stream().ok())
+                .filter(|s| s.dict.get(b"Subtype").and_then(|o| o.as_name()).ok() == Some(b"Form")),
+            _ => None,
+        };
+        converter(operation, &state);
+        let Some(form) = form else {
+            continue;
+        };
+        let Some(content) = stream_content(form).and_then(|c| decode_operations(&c).ok()) else {
+            continue;
+        };
+        let resources = form
+            .dict
+            .get(b"Resources")
+            .and_then(|o| doc.dereference(o))
+            .and_then(|(_, o)| o.as_dict())
+            .ok();
+        // a form without resources uses those of the page
+        let (mut inner, xobjects) = match resources {
+            Some(resources) => (
+                State::from_resources(doc, Some(resources)),
+                resources
+                    .get(b"XObject")
+                    .and_then(|o| doc.dereference(o))
+                    .and_then(|(_, o)| o.as_dict())
+                    .ok(),
+            ),
+            None => (
+                State::new(doc, page_id),
+                page_resource(doc, page_id, b"XObject"),
+            ),
+        };
+        inner.graphics = state.graphics.clone();
+        let matrix: Vec<f64> = form
+            .dict
+            .get(b"Matrix")
+            .and_then(|o| o.as_array())
+            .map(|a| a.iter().filter_map(object_to_f32).map(f64::from).collect())
+            .unwrap_or_default();
+        if let [a, b, c, d, e, f] = matrix[..] {
+            inner.graphics.ctm = state.graphics.ctm * Affine::new([a, b, c, d, e, f]);
+        }
+        for_each_in(doc, page_id, content, inner, xobjects, depth + 1, converter);
+    }
 }
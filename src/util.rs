@@ -1,4 +1,4 @@
-use lopdf::content::Operation;
+use lopdf::{content::Operation, Dictionary, Document, Object};
 
 pub fn operand_to_f32(op: &Operation) -> Result<Vec<f32>, ()> {
     let mut res = Vec::<f32>::new();
@@ -12,3 +12,73 @@ pub fn operand_to_f32(op: &Operation) -> Result<Vec<f32>, ()> {
     }
     Ok(res)
 }
+
+// An integer or real object as a number.
+pub fn object_to_f32(object: &Object) -> Option<f32> {
+    object.as_float().or(object.as_i64().map(|v| v as f32)).ok()
+}
+
+pub fn number_to_operand(value: f64, precision: usize) -> Object {
+    let scale = 10f64.powi(precision as i32);
+    let rounded = (value * scale).round() / scale;
+    if rounded.fract() == 0. && rounded.abs() < i64::MAX as f64 {
+        Object::from(rounded as i64)
+    } else {
+        Object::from(rounded as f32)
+    }
+}
+
+// An array of reals, such as a rectangle.
+pub fn number_array(values: &[f64]) -> Object {
+    Object::Array(values.iter().map(|v| Object::Real(*v as f32)).collect())
+}
+
+// Finds a resource category such as `ExtGState` or `XObject` for a page,
+// whether the resource dictionary is direct, referenced or inherited.
+pub fn page_resource<'a>(
+    doc: &'a Document,
+    page_id: (u32, u16),
+    key: &[u8],
+) -> Option<&'a Dictionary> {
+    let (direct, inherited) = doc.get_page_resources(page_id);
+    direct
+        .into_iter()
+        .chain(
+            inherited
+                .into_iter()
+                .filter_map(|id| doc.get_dictionary(id).ok()),
+        )
+        .find_map(|resources| {
+            resources
+                .get(key)
+                .and_then(|o| doc.dereference(o))
+                .and_then(|(_, o)| o.as_dict())
+                .ok()
+        })
+}
+
+// A PDF text string: ASCII as is, anything else as UTF-16BE with a byte
+// order mark.
+pub fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        Object::string_literal(text)
+    } else {
+        let mut bytes = vec![0xfe, 0xff];
+        bytes.extend(text.encode_utf16().flat_map(|u| u.to_be_bytes()));
+        Object::String(bytes, lopdf::StringFormat::Hexadecimal)
+    }
+}
+
+// The text of a PDF text string, in UTF-16BE with a byte order mark or
+// single bytes.
+pub fn decode_text_string(bytes: &[u8]) -> String {
+    if let [0xfe, 0xff, rest @ ..] = bytes {
+        let units: Vec<u16> = rest
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|b| char::from(*b)).collect()
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+
+use kurbo::{Affine, BezPath};
+use lopdf::content::Operation;
+use lopdf::Object;
+
+use crate::graphics::text::RenderingMode;
+use crate::{
+    number_to_operand, page_resource, GlyphOutlines, PathSerializer, PdfModifier, ShownGlyph, State,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct VectorizeReport {
+    pub converted: usize,
+    pub kept: usize,
+    // fonts whose text is kept, with the reason
+    pub fonts: BTreeSet<String>,
+}
+
+impl Display for VectorizeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} glyphs converted, {} kept", self.converted, self.kept)
+    }
+}
+
+// A text showing operation in a text object of its own, the text state it
+// depends on being set again.
+fn text_object(operation: Operation, state: &State) -> Vec<Operation> {
+    let text = &state.graphics.text;
+    let real = |v: f32| Object::Real(v);
+    let mut operations = vec![Operation::new("BT", vec![])];
+    if let (Some(font), Some(size)) = (&text.font, text.font_size) {
+        operations.push(Operation::new(
+            "Tf",
+            vec![Object::Name(font.clone()), real(size)],
+        ));
+    }
+    operations.extend([
+        Operation::new("Tc", vec![real(text.charactor_spacing)]),
+        Operation::new("Tw", vec![real(text.word_spacing)]),
+        Operation::new("Tz", vec![real(text.horizontal_scaling)]),
+        Operation::new("TL", vec![real(text.leading)]),
+        Operation::new("Ts", vec![real(text.rise)]),
+        Operation::new("Tr", vec![Object::Integer(text.rendering_mode.to_i64())]),
+        Operation::new(
+            "Tm",
+            text.matrix
+                .as_coeffs()
+                .map(|v| number_to_operand(v, 5))
+                .to_vec(),
+        ),
+    ]);
+    // the line has been moved already
+    let string = match operation.operator.as_ref() {
+        "'" => operation.operands.first().cloned(),
+        "\"" => operation.operands.get(2).cloned(),
+        _ => None,
+    };
+    match string {
+        Some(string) => operations.push(Operation::new("Tj", vec![string])),
+        None => operations.push(operation),
+    }
+    operations.push(Operation::new("ET", vec![]));
+    operations
+}
+
+fn adds_clip(mode: &RenderingMode) -> bool {
+    matches!(
+        mode,
+        RenderingMode::FillAndAddClippingPath
+            | RenderingMode::StrokeAndAddClippingPath
+            | RenderingMode::FillStrokeAddClippingPath
+            | RenderingMode::AddClippingPath
+    )
+}
+
+// The outlines of the glyphs shown, in user space, or None when the text is
+// kept: its font has no outlines, the glyphs collapse to nothing, or a glyph
+// that is not white space has no outline.
+fn text_outlines(
+    fonts: &HashMap<Vec<u8>, Result<GlyphOutlines, String>>,
+    outlines: &mut HashMap<(Vec<u8>, u32), Option<BezPath>>,
+    glyphs: &[ShownGlyph],
+    state: &State,
+) -> Option<BezPath> {
+    let text = &state.graphics.text;
+    let name = text.font.clone().unwrap_or_default();
+    let ctm = state.graphics.ctm;
+    let font = match fonts.get(&name) {
+        Some(Ok(font)) if ctm.determinant() != 0. => font,
+        _ => return None,
+    };
+    let size = f64::from(text.font_size.unwrap_or(0.));
+    let scaling = f64::from(text.horizontal_scaling) / 100.;
+    let [a, b, c, d, _, _] = text.matrix.as_coeffs();
+    let glyph_space =
+        Affine::new([a, b, c, d, 0., 0.]) * Affine::scale_non_uniform(size * scaling, size);
+    let mut path = BezPath::new();
+    for glyph in glyphs {
+        let outline = outlines
+            .entry((name.clone(), glyph.code))
+            .or_insert_with(|| font.outline(glyph.code));
+        let Some(outline) = outline else {
+            if glyph.text.trim().is_empty() {
+                continue;
+            }
+            return None;
+        };
+        // the origins are in default user space
+        let origin = ctm.inverse() * glyph.origin;
+        let matrix = Affine::translate(origin.to_vec2()) * glyph_space;
+        path.extend((matrix * &*outline).elements().iter().cloned());
+    }
+    Some(path)
+}
+
+// Replaces the text of a page with the outlines of its glyphs, painted as
+// the rendering mode says. Text in fonts without embedded outlines is kept.
+pub fn text_to_outlines(
+    modifier: &mut PdfModifier,
+    page_id: (u32, u16),
+    report: &mut VectorizeReport,
+) {
+    let doc = modifier.document();
+    let fonts: HashMap<Vec<u8>, Result<GlyphOutlines, String>> =
+        page_resource(doc, page_id, b"Font")
+            .map(|fonts| {
+                fonts
+                    .iter()
+                    .filter_map(|(name, font)| {
+                        let (_, font) = doc.dereference(font).ok()?;
+                        let outlines = GlyphOutlines::from_dict(doc, font.as_dict().ok()?);
+                        Some((name.clone(), outlines))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+    let mut outlines: HashMap<(Vec<u8>, u32), Option<BezPath>> = HashMap::new();
+    // text objects adding to the clipping path are kept whole when any of
+    // their clipping text is kept, as text objects of their own would clip
+    // to the intersection of their text rather than to all of it
+    let mut whole_objects = HashSet::new();
+    let mut object = 0;
+    modifier.for_each(
+        page_id,
+        &mut |operation, state| match operation.operator.as_ref() {
+            "BT" => object += 1,
+            "Tj" | "TJ" | "'" | "\"" if adds_clip(&state.graphics.text.rendering_mode) => {
+                let glyphs = state.shown_glyphs(&operation);
+                if text_outlines(&fonts, &mut outlines, &glyphs, state).is_none() {
+                    whole_objects.insert(object);
+                }
+            }
+            _ => (),
+        },
+    );
+    let mut object = 0;
+    let mut whole = false;
+    let mut in_text = false;
+    // the clipping path of text objects in a clipping rendering mode
+    let mut clip: Option<BezPath> = None;
+    modifier.apply(page_id, &mut |operation, state| {
+        if whole {
+            if let "Tj" | "TJ" | "'" | "\"" = operation.operator.as_ref() {
+                report.kept += state.shown_glyphs(&operation).len();
+            }
+            whole = operation.operator != "ET";
+            return vec![operation];
+        }
+        match operation.operator.as_ref() {
+            "BT" => {
+                object += 1;
+                whole = whole_objects.contains(&object);
+                in_text = !whole;
+                if whole {
+                    vec![operation]
+                } else {
+                    vec![]
+                }
+            }
+            "ET" => {
+                in_text = false;
+                match clip.take() {
+                    Some(path) => {
+                        let mut operations = PathSerializer::new().serialize(&path);
+                        operations.push(Operation::new("W", vec![]));
+                        operations.push(Operation::new("n", vec![]));
+                        operations
+                    }
+                    None => vec![],
+                }
+            }
+            // paths may not be painted in text objects, which are taken
+            // apart; the text state is known from `state`
+            "Tf" | "Tc" | "Tw" | "Tz" | "TL" | "Ts" | "Tr" | "Td" | "TD" | "Tm" | "T*"
+                if in_text =>
+            {
+                vec![]
+            }
+            "Tj" | "TJ" | "'" | "\"" => {
+                let text = &state.graphics.text;
+                let glyphs = state.shown_glyphs(&operation);
+                let Some(path) = text_outlines(&fonts, &mut outlines, &glyphs, state) else {
+                    report.kept += glyphs.len();
+                    let reason = match text.font.as_ref().and_then(|f| fonts.get(f)) {
+                        Some(Err(reason)) => Some(reason.as_str()),
+                        Some(Ok(_)) if state.graphics.ctm.determinant() != 0. => {
+                            Some("glyphs missing")
+                        }
+                        _ => None,
+                    };
+                    if let Some(reason) = reason {
+                        report
+                            .fonts
+                            .insert(format!("{} ({})", state.font().base_font, reason));
+                    }
+                    return text_object(operation, state);
+                };
+                report.converted += glyphs.len();
+                if path.elements().is_empty() {
+                    return vec![];
+                }
+                let paint = match text.rendering_mode {
+                    RenderingMode::Fill | RenderingMode::FillAndAddClippingPath => Some("f"),
+                    RenderingMode::Stroke | RenderingMode::StrokeAndAddClippingPath => Some("S"),
+                    RenderingMode::FillAndStroke | RenderingMode::FillStrokeAddClippingPath => {
+                        Some("B")
+                    }
+                    RenderingMode::Invisible | RenderingMode::AddClippingPath => None,
+                };
+                if adds_clip(&text.rendering_mode) {
+                    clip.get_or_insert_with(BezPath::new)
+                        .extend(path.elements().iter().cloned());
+                }
+                match paint {
+                    Some(paint) => {
+                        let mut operations = PathSerializer::new().serialize(&path);
+                        operations.push(Operation::new(paint, vec![]));
+                        operations
+                    }
+                    None => vec![],
+                }
+            }
+            _ => vec![operation],
+        }
+    });
+}
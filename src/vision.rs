@@ -0,0 +1,156 @@
+use crate::graphics::color::convert::ColorConverter;
+use crate::graphics::color::Color;
+use crate::{rewrite_colors, ColorInventory, PdfModifier};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deficiency {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl Deficiency {
+    // Machado, Oliveira and Fernandes (2009) at full severity, applied to
+    // linear RGB
+    fn matrix(&self) -> [[f32; 3]; 3] {
+        match self {
+            Self::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Self::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Self::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+// Okabe and Ito's palette, told apart with every kind of colour blindness.
+const SAFE_PALETTE: [(f32, f32, f32); 7] = [
+    (0.902, 0.624, 0.),
+    (0.337, 0.706, 0.914),
+    (0., 0.620, 0.451),
+    (0.941, 0.894, 0.259),
+    (0., 0.447, 0.698),
+    (0.835, 0.369, 0.),
+    (0.800, 0.475, 0.655),
+];
+
+#[derive(Debug, Clone)]
+pub struct ColorVision {
+    pub deficiency: Deficiency,
+    // CIEDE2000 difference below which two colours are taken to look alike
+    pub threshold: f32,
+}
+
+impl ColorVision {
+    pub fn new(deficiency: Deficiency) -> Self {
+        Self {
+            deficiency,
+            threshold: 10.,
+        }
+    }
+    // How a colour looks with the deficiency. Grays look the same.
+    pub fn simulate(&self, converter: &ColorConverter, color: &Color) -> Color {
+        if let Color::Gray(_) = color {
+            return color.clone();
+        }
+        let linear = |c: f32| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let gamma = |c: f32| {
+            let c = c.clamp(0., 1.);
+            if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1. / 2.4) - 0.055
+            }
+        };
+        let (r, g, b) = converter.to_rgb(color);
+        let rgb = [linear(r), linear(g), linear(b)];
+        let [r, g, b] = self
+            .deficiency
+            .matrix()
+            .map(|row| gamma(row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]));
+        Color::RGB(r, g, b)
+    }
+    // Pairs of colours that are told apart with normal vision but look alike
+    // with the deficiency.
+    pub fn confusable_pairs(
+        &self,
+        converter: &ColorConverter,
+        inventory: &ColorInventory,
+    ) -> Vec<(Color, Color)> {
+        let colors: Vec<&Color> = inventory.colors.iter().map(|u| &u.color).collect();
+        let mut pairs = Vec::new();
+        for (i, lhs) in colors.iter().enumerate() {
+            for rhs in &colors[i + 1..] {
+                if converter.delta_e(lhs, rhs) > 2. * self.threshold
+                    && converter.delta_e(
+                        &self.simulate(converter, lhs),
+                        &self.simulate(converter, rhs),
+                    ) < self.threshold
+                {
+                    pairs.push(((*lhs).clone(), (*rhs).clone()));
+                }
+            }
+        }
+        pairs
+    }
+    // Replacements from the safe palette for the chromatic colours of the
+    // confusable pairs, the most used colours choosing first. Each takes the
+    // closest palette colour not taken yet, in its own colour family.
+    pub fn safe_mapping(
+        &self,
+        converter: &ColorConverter,
+        inventory: &ColorInventory,
+    ) -> Vec<(Color, Color)> {
+        let pairs = self.confusable_pairs(converter, inventory);
+        let mut available: Vec<Color> = SAFE_PALETTE
+            .iter()
+            .map(|(r, g, b)| Color::RGB(*r, *g, *b))
+            .collect();
+        let mut mapping = Vec::new();
+        for usage in &inventory.colors {
+            let color = &usage.color;
+            let (_, a, b) = converter.to_lab(color);
+            if a.hypot(b) < self.threshold || !pairs.iter().any(|(l, r)| l == color || r == color) {
+                continue;
+            }
+            let Some(index) = (0..available.len()).min_by(|i, j| {
+                converter
+                    .delta_e(color, &available[*i])
+                    .total_cmp(&converter.delta_e(color, &available[*j]))
+            }) else {
+                break;
+            };
+            let safe = available.remove(index);
+            let family = converter.family(color);
+            mapping.push((color.clone(), converter.convert(&safe, family)));
+        }
+        mapping
+    }
+    // Shows the page as it looks with the deficiency.
+    pub fn apply(
+        &self,
+        modifier: &mut PdfModifier,
+        page_id: (u32, u16),
+        converter: &ColorConverter,
+    ) {
+        rewrite_colors(modifier, page_id, &mut |color, _| {
+            Some(self.simulate(converter, color))
+        });
+    }
+}